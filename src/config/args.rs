@@ -6,8 +6,11 @@ use clap::Subcommand;
 use clap::builder::ArgPredicate;
 use clap::{Parser, builder::BoolishValueParser};
 
+use crate::config::diff_format::DiffFormat;
+use crate::config::installed_order::InstalledOrder;
 use crate::config::options::Options;
 use crate::config::{CARGO_PKG_NAME, LONG};
+use crate::internal_log::DEFAULT_TAIL_LINES;
 
 use super::colors::styles;
 
@@ -24,6 +27,14 @@ pub struct Args {
   /// Install Type
   #[command(subcommand)]
   pub command: CommandType,
+
+  /// Named profile to read default options from, cli and env values take precedence
+  #[clap(env = "MOD_INSTALLER_PROFILE", long, global = true)]
+  pub profile: Option<String>,
+
+  /// Path to profiles file, defaults to profiles.toml in the config directory
+  #[clap(env = "MOD_INSTALLER_PROFILES_FILE", long, global = true, value_parser = path_must_exist)]
+  pub profiles_file: Option<PathBuf>,
 }
 
 /// Type of Command
@@ -37,6 +48,26 @@ pub enum CommandType {
   Languages(ScanLangauges),
   #[command()]
   Components(ScanComponents),
+  #[command()]
+  Config(ConfigCommand),
+  #[command()]
+  Logs(LogsCommand),
+  #[command()]
+  Serve(Serve),
+  #[command()]
+  Verify(Verify),
+  #[command()]
+  Sync(SyncCommand),
+  #[command()]
+  Upgrade(Upgrade),
+  #[command()]
+  Drift(DriftCommand),
+  #[command()]
+  Lint(LintCommand),
+  #[command()]
+  Fmt(FmtCommand),
+  #[command()]
+  Diff(DiffCommand),
 }
 
 /// Normal install for (BG1EE,BG2EE,IWDEE, EET)
@@ -108,6 +139,16 @@ pub struct ScanLangauges {
   #[clap(short, long, required = false, default_value = "")]
   pub filter_by_selected_language: String,
 
+  /// Read the tp2 and its tra files instead of running weidu for each mod and language
+  #[clap(
+        long,
+        num_args=0..=1,
+        action = clap::ArgAction::SetTrue,
+        default_value_t = false,
+        value_parser = BoolishValueParser::new(),
+    )]
+  pub native: bool,
+
   /// Common Options
   #[clap(flatten)]
   pub options: Options,
@@ -120,17 +161,265 @@ pub struct ScanComponents {
   #[clap(short, long, required = false, default_value = "")]
   pub filter_by_selected_language: String,
 
+  /// Read the tp2 and its tra files instead of running weidu for each mod and language
+  #[clap(
+        long,
+        num_args=0..=1,
+        action = clap::ArgAction::SetTrue,
+        default_value_t = false,
+        value_parser = BoolishValueParser::new(),
+    )]
+  pub native: bool,
+
+  /// Common Options
+  #[clap(flatten)]
+  pub options: Options,
+}
+
+/// Inspect configuration
+#[derive(Parser, Debug, PartialEq, Clone)]
+pub struct ConfigCommand {
+  #[command(subcommand)]
+  pub action: ConfigAction,
+}
+
+#[derive(Subcommand, Debug, PartialEq, Clone)]
+pub enum ConfigAction {
+  /// Print the effective configuration, pass a command to see its options merged with the profile
+  #[command()]
+  Show(ConfigShow),
+}
+
+#[derive(Parser, Debug, PartialEq, Clone)]
+pub struct ConfigShow {
+  /// Command to resolve, ie: eet --bg1-log-file bg1.log
+  #[clap(trailing_var_arg = true, allow_hyphen_values = true)]
+  pub command: Vec<String>,
+}
+
+/// Uninstall and reinstall the fewest components needed for a game to match a target log
+#[derive(Parser, Debug, PartialEq, Clone)]
+pub struct SyncCommand {
+  /// Path to target log
+  #[clap(env, long, short = 'f', value_parser = path_must_exist, required = true)]
+  pub log_file: PathBuf,
+
+  /// Absolute Path to game directory
+  #[clap(env, short, long, value_parser = parse_absolute_path, required = true)]
+  pub game_directory: PathBuf,
+
+  /// Only show the plan
+  #[clap(
+        long,
+        num_args=0..=1,
+        action = clap::ArgAction::SetTrue,
+        default_value_t = false,
+        value_parser = BoolishValueParser::new(),
+    )]
+  pub dry_run: bool,
+
+  /// Apply the plan without asking
+  #[clap(
+        long,
+        num_args=0..=1,
+        action = clap::ArgAction::SetTrue,
+        default_value_t = false,
+        value_parser = BoolishValueParser::new(),
+    )]
+  pub yes: bool,
+
+  /// Common Options
+  #[clap(flatten)]
+  pub options: Options,
+
+  /// Install Options
+  #[clap(flatten)]
+  pub install_options: InstallOptions,
+}
+
+/// Reinstall a mod and every component installed after it, with the mod folder replaced by a new version
+#[derive(Parser, Debug, PartialEq, Clone)]
+pub struct Upgrade {
+  /// Mod to upgrade, ie: bg1npc or setup-bg1npc.tp2
+  pub tp2: String,
+
+  /// Absolute Path to game directory
+  #[clap(env, short, long, value_parser = parse_absolute_path, required = true)]
+  pub game_directory: PathBuf,
+
+  /// Only show the plan
+  #[clap(
+        long,
+        num_args=0..=1,
+        action = clap::ArgAction::SetTrue,
+        default_value_t = false,
+        value_parser = BoolishValueParser::new(),
+    )]
+  pub dry_run: bool,
+
+  /// Apply the plan without asking
+  #[clap(
+        long,
+        num_args=0..=1,
+        action = clap::ArgAction::SetTrue,
+        default_value_t = false,
+        value_parser = BoolishValueParser::new(),
+    )]
+  pub yes: bool,
+
+  /// Common Options
+  #[clap(flatten)]
+  pub options: Options,
+
+  /// Install Options
+  #[clap(flatten)]
+  pub install_options: InstallOptions,
+}
+
+/// Compare a game's weidu.log with a target log, exits non-zero if they differ
+#[derive(Parser, Debug, PartialEq, Clone)]
+pub struct Verify {
+  /// Path to target log
+  #[clap(env, long, short = 'f', value_parser = path_must_exist, required = true)]
+  pub log_file: PathBuf,
+
+  /// Absolute Path to game directory
+  #[clap(env, short, long, value_parser = parse_absolute_path, required = true)]
+  pub game_directory: PathBuf,
+
+  /// Strict Version and Component/SubComponent matching
+  #[clap(
+        env,
+        short = 'x',
+        long,
+        num_args=0..=1,
+        action = clap::ArgAction::SetTrue,
+        default_value_t = false,
+        value_parser = BoolishValueParser::new(),
+    )]
+  pub strict_matching: bool,
+}
+
+/// Compare the components in a log with those its mods list now, and propose a remapped log
+#[derive(Parser, Debug, PartialEq, Clone)]
+pub struct DriftCommand {
+  /// Path to target log
+  #[clap(env, long, short = 'f', value_parser = path_must_exist, required = true)]
+  pub log_file: PathBuf,
+
+  /// Write the remapped log here, this can be the log itself
+  #[clap(long)]
+  pub output: Option<PathBuf>,
+
+  /// Common Options
+  #[clap(flatten)]
+  pub options: Options,
+}
+
+/// Report problems in a log, exits non-zero if there are any
+#[derive(Parser, Debug, PartialEq, Clone)]
+pub struct LintCommand {
+  /// Path to target log
+  #[clap(env, long, short = 'f', value_parser = path_must_exist, required = true)]
+  pub log_file: PathBuf,
+
+  /// Common Options
+  #[clap(flatten)]
+  pub options: Options,
+}
+
+/// Rewrite a log the way weidu writes it, with component names from the mods' tp2s
+#[derive(Parser, Debug, PartialEq, Clone)]
+pub struct FmtCommand {
+  /// Path to target log
+  #[clap(env, long, short = 'f', value_parser = path_must_exist, required = true)]
+  pub log_file: PathBuf,
+
+  /// Write the formatted log here instead of over the log
+  #[clap(long)]
+  pub output: Option<PathBuf>,
+
+  /// Only show the lines that would change, exits non-zero if there are any
+  #[clap(
+        long,
+        num_args=0..=1,
+        action = clap::ArgAction::SetTrue,
+        default_value_t = false,
+        value_parser = BoolishValueParser::new(),
+    )]
+  pub check: bool,
+
   /// Common Options
   #[clap(flatten)]
   pub options: Options,
 }
 
+/// Compare two logs, either can be a game directory to use its weidu.log, exits non-zero if they differ
+#[derive(Parser, Debug, PartialEq, Clone)]
+pub struct DiffCommand {
+  /// Log or game directory to compare from
+  #[clap(value_parser = path_must_exist)]
+  pub a: PathBuf,
+
+  /// Log or game directory to compare to
+  #[clap(value_parser = path_must_exist)]
+  pub b: PathBuf,
+
+  /// How to show the differences: text, json or side-by-side
+  #[clap(long, default_value = "text", value_parser = DiffFormat::value_parser)]
+  pub format: DiffFormat,
+}
+
+/// Serve a json api on localhost to start, watch, answer and stop installs
+#[derive(Parser, Debug, PartialEq, Clone)]
+pub struct Serve {
+  /// Path of a unix socket, or a loopback ip and port to serve http on, defaults to serve.sock in the config directory, or 127.0.0.1:7878 without unix sockets
+  #[clap(env = "MOD_INSTALLER_LISTEN", long)]
+  pub listen: Option<String>,
+
+  /// File the token every request must carry is written to, defaults to serve.token in the config directory
+  #[clap(env = "MOD_INSTALLER_TOKEN_FILE", long)]
+  pub token_file: Option<PathBuf>,
+}
+
+/// Browse the logs written for each install run
+#[derive(Parser, Debug, PartialEq, Clone)]
+pub struct LogsCommand {
+  #[command(subcommand)]
+  pub action: LogsAction,
+}
+
+#[derive(Subcommand, Debug, PartialEq, Clone)]
+pub enum LogsAction {
+  /// Print the outcome and weidu output for a component from the most recent run that installed it
+  #[command()]
+  Show(LogsShow),
+}
+
+#[derive(Parser, Debug, PartialEq, Clone)]
+pub struct LogsShow {
+  /// Component to show, ie: bg1npc, bg1npc:0 or a batch name like bg1npc-0-1
+  pub component: String,
+
+  /// Directory install runs were logged to, defaults to logs in the config directory
+  #[clap(env, long, value_parser = path_must_exist)]
+  pub log_directory: Option<PathBuf>,
+
+  /// Run to look in, defaults to searching from the most recent run
+  #[clap(long)]
+  pub run: Option<String>,
+}
+
 #[derive(Parser, Debug, PartialEq, Clone, Default)]
 pub struct InstallOptions {
   /// Game Language
   #[clap(short, long, default_value = "en_US")]
   pub language: String,
 
+  /// Extra languages to detect weidu questions and results in, the game language is always used
+  #[clap(env, long, use_value_delimiter = true, value_delimiter = ',')]
+  pub parser_languages: Vec<String>,
+
   /// Compare against installed weidu log, note this is best effort
   #[clap(
         env,
@@ -143,6 +432,14 @@ pub struct InstallOptions {
     )]
   pub skip_installed: bool,
 
+  /// How skip installed treats a weidu.log installed in a different order from the target: any, prefix or reinstall
+  #[clap(env, long, default_value = "any", value_parser = InstalledOrder::value_parser)]
+  pub installed_order: InstalledOrder,
+
+  /// Set by commands that have already shown and confirmed what reinstall uninstalls
+  #[clap(skip)]
+  pub uninstall_confirmed: bool,
+
   /// If a warning occurs in the weidu child process exit
   #[clap(
         env,
@@ -227,6 +524,43 @@ pub struct InstallOptions {
     )]
   pub check_last_installed: bool,
 
+  /// Compare the whole weidu.log with the target log once the install has finished
+  #[clap(
+        env,
+        long,
+        num_args=0..=1,
+        action = clap::ArgAction::SetTrue,
+        default_value_t = false,
+        value_parser = BoolishValueParser::new(),
+    )]
+  pub verify: bool,
+
+  /// Before installing, stop if a mod has renumbered, renamed or removed a component in the target log
+  #[clap(
+        env,
+        long,
+        num_args=0..=1,
+        action = clap::ArgAction::SetTrue,
+        default_value_t = false,
+        value_parser = BoolishValueParser::new(),
+    )]
+  pub check_drift: bool,
+
+  /// Before installing, check the target log against the requirements and conflicts in each tp2 and the rules file
+  #[clap(
+        env,
+        long,
+        num_args=0..=1,
+        action = clap::ArgAction::SetTrue,
+        default_value_t = false,
+        value_parser = BoolishValueParser::new(),
+    )]
+  pub validate: bool,
+
+  /// Requirements and conflicts to check on top of those in the tp2s, defaults to rules.toml in the config directory
+  #[clap(env, long, value_parser = path_must_exist)]
+  pub rules_file: Option<PathBuf>,
+
   /// Tick
   #[clap(env, short = 'i', long, default_value_t = 500)]
   pub tick: u64,
@@ -235,6 +569,10 @@ pub struct InstallOptions {
   #[clap(env, short = '0', long, default_value_t = 10)]
   pub lookback: usize,
 
+  /// Number of recent weidu output lines kept in memory and shown when a mod fails
+  #[clap(env, long, default_value_t = DEFAULT_TAIL_LINES)]
+  pub log_tail_lines: usize,
+
   /// Casefold only available for linux ext4
   #[clap(
         env,
@@ -250,6 +588,18 @@ pub struct InstallOptions {
   #[clap(short = 'k', long, use_value_delimiter = true, value_delimiter = ',')]
   pub generic_weidu_args: Vec<String>,
 
+  /// Record weidu output, answers and parser states per component to this directory
+  #[clap(env, long, value_parser = path_must_exist)]
+  pub record_transcript: Option<PathBuf>,
+
+  /// Directory to write a log per batch and an index of each run to, defaults to logs in the config directory
+  #[clap(env, long, value_parser = path_must_exist)]
+  pub log_directory: Option<PathBuf>,
+
+  /// Write newline delimited json install events to this file, fifo or unix socket
+  #[clap(env, long)]
+  pub events: Option<PathBuf>,
+
   /// Batch options
   #[clap(flatten)]
   pub batch: BatchOptions,
@@ -284,6 +634,29 @@ pub struct BatchOptions {
     required = false
   )]
   pub batch_skip: Vec<String>,
+
+  /// When a batch fails or warns, install its components one at a time to find the one at fault
+  #[clap(
+        env,
+        long,
+        num_args=0..=1,
+        action = clap::ArgAction::SetTrue,
+        default_value_t = false,
+        value_parser = BoolishValueParser::new(),
+   )]
+  pub isolate_failures: bool,
+
+  /// Before installing a failed batch one at a time, uninstall what it installed so every component is retried
+  #[clap(
+        env,
+        long,
+        num_args=0..=1,
+        action = clap::ArgAction::SetTrue,
+        default_value_t = false,
+        requires = "isolate_failures",
+        value_parser = BoolishValueParser::new(),
+   )]
+  pub uninstall_failed_batch: bool,
 }
 
 pub fn path_must_exist(arg: &str) -> Result<PathBuf, std::io::Error> {
@@ -0,0 +1,59 @@
+use std::error::Error;
+
+pub const DIFF_FORMAT_ERROR: &str = r"
+Please provide a valid diff format, options are:
+--format text          one line per difference, prefixed with - + > or ~
+--format json          the differences as a json array
+--format side-by-side  both logs in two columns, lined up by component
+";
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum DiffFormat {
+  #[default]
+  Text,
+  Json,
+  SideBySide,
+}
+
+impl TryFrom<&str> for DiffFormat {
+  type Error = Box<dyn Error>;
+
+  fn try_from(value: &str) -> Result<Self, Self::Error> {
+    match value.to_lowercase().as_str() {
+      "text" => Ok(DiffFormat::Text),
+      "json" => Ok(DiffFormat::Json),
+      "side-by-side" => Ok(DiffFormat::SideBySide),
+      _ => Err(format!("{DIFF_FORMAT_ERROR}, Provided {value}").into()),
+    }
+  }
+}
+
+impl DiffFormat {
+  pub fn value_parser(arg: &str) -> Result<DiffFormat, String> {
+    DiffFormat::try_from(arg).map_err(|err| err.to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_parse_diff_format() -> Result<(), Box<dyn Error>> {
+    let tests = vec![
+      ("text", Ok(DiffFormat::Text)),
+      ("JSON", Ok(DiffFormat::Json)),
+      ("side-by-side", Ok(DiffFormat::SideBySide)),
+      (
+        "unified",
+        Err(format!("{DIFF_FORMAT_ERROR}, Provided unified")),
+      ),
+    ];
+    for (test, expected) in tests {
+      assert_eq!(DiffFormat::value_parser(test), expected);
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,60 @@
+use std::error::Error;
+
+pub const INSTALLED_ORDER_ERROR: &str = r"
+Please provide a valid installed order, options are:
+--installed-order any        skip target components found anywhere in weidu.log
+--installed-order prefix     skip only the components installed in target order, stop if the game diverges
+--installed-order reinstall  skip only the components installed in target order, uninstall the rest and install again
+";
+
+/// How skip installed treats components that were installed in a different order from the target
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum InstalledOrder {
+  #[default]
+  Any,
+  Prefix,
+  Reinstall,
+}
+
+impl TryFrom<&str> for InstalledOrder {
+  type Error = Box<dyn Error>;
+
+  fn try_from(value: &str) -> Result<Self, Self::Error> {
+    match value.to_lowercase().as_str() {
+      "any" => Ok(InstalledOrder::Any),
+      "prefix" => Ok(InstalledOrder::Prefix),
+      "reinstall" => Ok(InstalledOrder::Reinstall),
+      _ => Err(format!("{INSTALLED_ORDER_ERROR}, Provided {value}").into()),
+    }
+  }
+}
+
+impl InstalledOrder {
+  pub fn value_parser(arg: &str) -> Result<InstalledOrder, String> {
+    InstalledOrder::try_from(arg).map_err(|err| err.to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_parse_installed_order() -> Result<(), Box<dyn Error>> {
+    let tests = vec![
+      ("any", Ok(InstalledOrder::Any)),
+      ("Prefix", Ok(InstalledOrder::Prefix)),
+      ("reinstall", Ok(InstalledOrder::Reinstall)),
+      (
+        "first",
+        Err(format!("{INSTALLED_ORDER_ERROR}, Provided first")),
+      ),
+    ];
+    for (test, expected) in tests {
+      assert_eq!(InstalledOrder::value_parser(test), expected);
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,81 @@
+use std::collections::BTreeSet;
+
+use crate::config::parser_config::ParserConfig;
+
+// Weidu prints its own messages in english, but the mods tra files and some weidu builds
+// translate them, so these are merged into the english defaults rather than replacing them
+#[derive(Debug, PartialEq)]
+pub(crate) struct LanguagePack {
+  pub(crate) code: &'static str,
+  pub(crate) in_progress_words: &'static [&'static str],
+  pub(crate) useful_status_words: &'static [&'static str],
+  pub(crate) choice_words: &'static [&'static str],
+  pub(crate) choice_phrase: &'static [&'static str],
+  pub(crate) completed_with_warnings: &'static [&'static str],
+  pub(crate) failed_with_error: &'static [&'static str],
+  pub(crate) finished: &'static [&'static str],
+}
+
+// Only phrases taken from a real tra file or weidu build belong here, a guessed phrase can
+// turn an ordinary status line into a question. None have been captured yet
+pub(crate) const LANGUAGE_PACKS: &[LanguagePack] = &[];
+
+impl LanguagePack {
+  /// Finds the pack for a weidu language, ie: fr_FR, de_DE, zh_CN
+  pub(crate) fn find(language: &str) -> Option<&'static LanguagePack> {
+    let code = language
+      .split(['_', '-'])
+      .next()
+      .unwrap_or_default()
+      .to_lowercase();
+    LANGUAGE_PACKS.iter().find(|pack| pack.code == code)
+  }
+}
+
+fn extend(set: &mut BTreeSet<String>, words: &[&str]) {
+  set.extend(words.iter().map(|word| word.to_lowercase()));
+}
+
+impl ParserConfig {
+  pub fn with_languages(&self, languages: &[String]) -> Self {
+    let mut config = self.clone();
+    for language in languages {
+      match LanguagePack::find(language) {
+        Some(pack) => {
+          log::debug!("Using parser language pack {}", pack.code);
+          extend(&mut config.in_progress_words, pack.in_progress_words);
+          extend(&mut config.useful_status_words, pack.useful_status_words);
+          extend(&mut config.choice_words, pack.choice_words);
+          extend(&mut config.choice_phrase, pack.choice_phrase);
+          extend(
+            &mut config.completed_with_warnings,
+            pack.completed_with_warnings,
+          );
+          extend(&mut config.failed_with_error, pack.failed_with_error);
+          extend(&mut config.finished, pack.finished);
+        },
+        None => log::debug!("No parser language pack for {language}"),
+      }
+    }
+    config
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::error::Error;
+
+  #[test]
+  fn unknown_language_keeps_the_english_config() -> Result<(), Box<dyn Error>> {
+    assert_eq!(LanguagePack::find("en_US"), None);
+    let english = ParserConfig::default();
+    assert_eq!(
+      english.with_languages(&["en_US".to_string(), "xx-XX".to_string()]),
+      english
+    );
+    Ok(())
+  }
+}
@@ -49,6 +49,14 @@ impl LogOptions {
   pub fn value_parser(arg: &str) -> Result<LogOptions, String> {
     LogOptions::try_from(arg).map_err(|err| err.to_string())
   }
+  /// Where weidu will write its --log file for a batch, if it has been asked to
+  pub fn log_path(&self, path: &str) -> Option<PathBuf> {
+    match self {
+      LogOptions::Log(path_buf) if path_buf.is_file() => Some(path_buf.clone()),
+      LogOptions::Log(path_buf) => Some(path_buf.join(path)),
+      _ => None,
+    }
+  }
   pub fn to_args(&self, path: &str) -> Vec<String> {
     match self {
       LogOptions::LogAppend => vec!["--logapp".to_string()],
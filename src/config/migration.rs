@@ -0,0 +1,271 @@
+use std::{
+  collections::BTreeSet,
+  error::Error,
+  fs,
+  path::{Path, PathBuf},
+};
+
+use crate::config::{CARGO_PKG_NAME, parser_config::ParserConfig};
+
+// Snapshot of the defaults a users config was generated from, this lets us tell
+// a user removal apart from a phrase that was added in a newer release
+const DEFAULTS_SUFFIX: &str = "defaults";
+
+pub(crate) fn defaults_location(parser_config_location: &str) -> String {
+  format!("{parser_config_location}-{DEFAULTS_SUFFIX}")
+}
+
+pub(crate) fn load_baseline(parser_config_location: &str) -> Option<ParserConfig> {
+  let path = confy::get_configuration_file_path(
+    CARGO_PKG_NAME,
+    defaults_location(parser_config_location).as_str(),
+  )
+  .ok()?;
+  if !path.exists() {
+    return None;
+  }
+  confy::load_path::<ParserConfig>(path).ok()
+}
+
+pub(crate) fn store_baseline(parser_config_location: &str) -> Result<(), Box<dyn Error>> {
+  confy::store(
+    CARGO_PKG_NAME,
+    defaults_location(parser_config_location).as_str(),
+    ParserConfig::default(),
+  )?;
+  Ok(())
+}
+
+pub(crate) fn backup(config_path: &Path, version: &str) -> Result<PathBuf, Box<dyn Error>> {
+  let file_stem = config_path
+    .file_stem()
+    .unwrap_or_default()
+    .to_str()
+    .unwrap_or_default();
+  let backup_path = config_path.with_file_name(format!("{file_stem}-{version}.toml.bak"));
+  fs::copy(config_path, &backup_path)?;
+  Ok(backup_path)
+}
+
+fn merge_set(
+  field: &str,
+  user: &BTreeSet<String>,
+  baseline: Option<&BTreeSet<String>>,
+  defaults: &BTreeSet<String>,
+  changes: &mut Vec<String>,
+) -> BTreeSet<String> {
+  let mut out = BTreeSet::new();
+  for entry in user {
+    match baseline {
+      Some(baseline) if baseline.contains(entry) && !defaults.contains(entry) => {
+        changes.push(format!("{field}: removed {entry:?}, no longer a default"));
+      },
+      _ => {
+        out.insert(entry.clone());
+      },
+    }
+  }
+  for entry in defaults.difference(user) {
+    match baseline {
+      Some(baseline) if baseline.contains(entry) => {
+        changes.push(format!("{field}: kept user removal of {entry:?}"));
+      },
+      _ => {
+        changes.push(format!("{field}: added {entry:?}"));
+        out.insert(entry.clone());
+      },
+    }
+  }
+  out
+}
+
+fn merge_value<T: PartialEq + Clone + std::fmt::Debug>(
+  field: &str,
+  user: &T,
+  baseline: Option<&T>,
+  defaults: &T,
+  changes: &mut Vec<String>,
+) -> T {
+  match baseline {
+    Some(baseline) if baseline == user && user != defaults => {
+      changes.push(format!("{field}: changed {user:?} to {defaults:?}"));
+      defaults.clone()
+    },
+    _ => user.clone(),
+  }
+}
+
+/// Merge new default entries into a users parser config, keeping their additions and removals.
+/// Returns the migrated config and a human readable list of what changed.
+pub(crate) fn migrate(
+  user: &ParserConfig,
+  baseline: Option<&ParserConfig>,
+  defaults: &ParserConfig,
+) -> (ParserConfig, Vec<String>) {
+  let mut changes = vec![];
+  let migrated = ParserConfig {
+    in_progress_words: merge_set(
+      "in_progress_words",
+      &user.in_progress_words,
+      baseline.map(|b| &b.in_progress_words),
+      &defaults.in_progress_words,
+      &mut changes,
+    ),
+    useful_status_words: merge_set(
+      "useful_status_words",
+      &user.useful_status_words,
+      baseline.map(|b| &b.useful_status_words),
+      &defaults.useful_status_words,
+      &mut changes,
+    ),
+    choice_words: merge_set(
+      "choice_words",
+      &user.choice_words,
+      baseline.map(|b| &b.choice_words),
+      &defaults.choice_words,
+      &mut changes,
+    ),
+    choice_phrase: merge_set(
+      "choice_phrase",
+      &user.choice_phrase,
+      baseline.map(|b| &b.choice_phrase),
+      &defaults.choice_phrase,
+      &mut changes,
+    ),
+    completed_with_warnings: merge_set(
+      "completed_with_warnings",
+      &user.completed_with_warnings,
+      baseline.map(|b| &b.completed_with_warnings),
+      &defaults.completed_with_warnings,
+      &mut changes,
+    ),
+    failed_with_error: merge_set(
+      "failed_with_error",
+      &user.failed_with_error,
+      baseline.map(|b| &b.failed_with_error),
+      &defaults.failed_with_error,
+      &mut changes,
+    ),
+    finished: merge_set(
+      "finished",
+      &user.finished,
+      baseline.map(|b| &b.finished),
+      &defaults.finished,
+      &mut changes,
+    ),
+    eet_auto_fill: merge_value(
+      "eet_auto_fill",
+      &user.eet_auto_fill,
+      baseline.map(|b| &b.eet_auto_fill),
+      &defaults.eet_auto_fill,
+      &mut changes,
+    ),
+    bell_character: merge_value(
+      "bell_character",
+      &user.bell_character,
+      baseline.map(|b| &b.bell_character),
+      &defaults.bell_character,
+      &mut changes,
+    ),
+    metadata: defaults.metadata.clone(),
+  };
+  (migrated, changes)
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::error::Error;
+
+  fn set(values: &[&str]) -> BTreeSet<String> {
+    values.iter().map(|value| value.to_string()).collect()
+  }
+
+  #[test]
+  fn keeps_user_additions_and_removals() -> Result<(), Box<dyn Error>> {
+    let baseline = ParserConfig {
+      choice_phrase: set(&["do you want", "please enter"]),
+      ..Default::default()
+    };
+    let user = ParserConfig {
+      choice_phrase: set(&["do you want", "my own phrase"]),
+      ..Default::default()
+    };
+    let defaults = ParserConfig {
+      choice_phrase: set(&["do you want", "please enter", "brand new phrase"]),
+      ..Default::default()
+    };
+    let (migrated, changes) = migrate(&user, Some(&baseline), &defaults);
+    assert_eq!(
+      migrated.choice_phrase,
+      set(&["brand new phrase", "do you want", "my own phrase"])
+    );
+    assert_eq!(
+      changes,
+      vec![
+        r#"choice_phrase: added "brand new phrase""#.to_string(),
+        r#"choice_phrase: kept user removal of "please enter""#.to_string(),
+      ]
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn drops_entries_removed_from_defaults() -> Result<(), Box<dyn Error>> {
+    let baseline = ParserConfig {
+      finished: set(&["process ended", "old phrase"]),
+      ..Default::default()
+    };
+    let user = baseline.clone();
+    let defaults = ParserConfig {
+      finished: set(&["process ended"]),
+      ..Default::default()
+    };
+    let (migrated, changes) = migrate(&user, Some(&baseline), &defaults);
+    assert_eq!(migrated.finished, set(&["process ended"]));
+    assert_eq!(
+      changes,
+      vec![r#"finished: removed "old phrase", no longer a default"#.to_string()]
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn without_baseline_only_adds() -> Result<(), Box<dyn Error>> {
+    let user = ParserConfig {
+      choice_words: set(&["mine"]),
+      eet_auto_fill: "custom".to_string(),
+      ..Default::default()
+    };
+    let defaults = ParserConfig {
+      choice_words: set(&["choose"]),
+      ..Default::default()
+    };
+    let (migrated, _) = migrate(&user, None, &defaults);
+    assert_eq!(migrated.choice_words, set(&["choose", "mine"]));
+    assert_eq!(migrated.eet_auto_fill, "custom");
+    Ok(())
+  }
+
+  #[test]
+  fn updates_untouched_values() -> Result<(), Box<dyn Error>> {
+    let baseline = ParserConfig {
+      eet_auto_fill: "old".to_string(),
+      ..Default::default()
+    };
+    let user = baseline.clone();
+    let defaults = ParserConfig {
+      eet_auto_fill: "new".to_string(),
+      ..Default::default()
+    };
+    let (migrated, changes) = migrate(&user, Some(&baseline), &defaults);
+    assert_eq!(migrated.eet_auto_fill, "new");
+    assert_eq!(
+      changes,
+      vec![r#"eet_auto_fill: changed "old" to "new""#.to_string()]
+    );
+    Ok(())
+  }
+}
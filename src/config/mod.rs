@@ -6,10 +6,17 @@ use parser_config::ParserConfig;
 
 pub mod args;
 mod colors;
+pub mod diff_format;
+pub mod installed_order;
+pub mod language_pack;
 pub mod log_options;
 mod meta;
+mod migration;
 pub mod options;
 pub mod parser_config;
+pub mod profile;
+pub mod rules;
+pub mod show;
 pub mod state;
 pub mod weidu_log_options;
 
@@ -54,25 +61,70 @@ pub struct Config {
 
 impl Config {
   pub fn new(parser_config_location: &str) -> Self {
-    let parser_config: Arc<ParserConfig> = if let Ok(config) =
-      confy::load::<ParserConfig>(CARGO_PKG_NAME, parser_config_location)
-      && config.metadata.mod_installer_version == env!("CARGO_PKG_VERSION")
-    {
-      log::debug!("Using existing config: {:?}", config);
-      Arc::new(config)
-    } else {
-      log::debug!("Creating new config");
-      let config = Arc::new(ParserConfig::default());
-      let _ = confy::store(
-        CARGO_PKG_NAME,
-        parser_config_location,
-        config.clone().as_ref(),
-      );
-      config
+    let parser_config: Arc<ParserConfig> =
+      match confy::load::<ParserConfig>(CARGO_PKG_NAME, parser_config_location) {
+        Ok(config) if config.metadata.mod_installer_version == env!("CARGO_PKG_VERSION") => {
+          log::debug!("Using existing config: {:?}", config);
+          if migration::load_baseline(parser_config_location).is_none()
+            && let Err(err) = migration::store_baseline(parser_config_location)
+          {
+            log::warn!("Failed to store parser config defaults, {err}");
+          }
+          Arc::new(config)
+        },
+        Ok(config) => {
+          log::info!(
+            "Migrating parser config from version {} to {}",
+            config.metadata.mod_installer_version,
+            env!("CARGO_PKG_VERSION")
+          );
+          Self::backup(
+            parser_config_location,
+            &config.metadata.mod_installer_version,
+          );
+          let baseline = migration::load_baseline(parser_config_location);
+          let (migrated, changes) =
+            migration::migrate(&config, baseline.as_ref(), &ParserConfig::default());
+          for change in changes.iter() {
+            log::info!("Parser config {change}");
+          }
+          Self::store(parser_config_location, migrated)
+        },
+        Err(err) => {
+          log::debug!("Creating new config, could not load existing config: {err}");
+          Self::backup(parser_config_location, "invalid");
+          Self::store(parser_config_location, ParserConfig::default())
+        },
+      };
+    let raw_args = std::env::args_os().collect::<Vec<_>>();
+    let args = match profile::resolve_args(raw_args) {
+      Ok(args) => Args::parse_from(args),
+      Err(err) => clap::Error::raw(clap::error::ErrorKind::InvalidValue, format!("{err}\n")).exit(),
     };
     Self {
-      args: Args::parse(),
+      args,
       parser: parser_config,
     }
   }
+
+  fn backup(parser_config_location: &str, version: &str) {
+    if let Ok(path) = confy::get_configuration_file_path(CARGO_PKG_NAME, parser_config_location)
+      && path.exists()
+    {
+      match migration::backup(&path, version) {
+        Ok(backup_path) => log::info!("Backed up parser config to {backup_path:?}"),
+        Err(err) => log::warn!("Failed to back up parser config {path:?}, {err}"),
+      }
+    }
+  }
+
+  fn store(parser_config_location: &str, config: ParserConfig) -> Arc<ParserConfig> {
+    if let Err(err) = confy::store(CARGO_PKG_NAME, parser_config_location, &config) {
+      log::warn!("Failed to store parser config, {err}");
+    }
+    if let Err(err) = migration::store_baseline(parser_config_location) {
+      log::warn!("Failed to store parser config defaults, {err}");
+    }
+    Arc::new(config)
+  }
 }
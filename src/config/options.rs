@@ -75,6 +75,7 @@ impl clap::Args for Options {
           .env("DEPTH")
           .long("depth")
           .short('d')
+          .value_parser(clap::value_parser!(usize))
           .required(false),
       )
       .arg(
@@ -4,7 +4,7 @@ use serde_derive::{Deserialize, Serialize};
 
 use crate::{config::meta::Metadata, config::state::State};
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ParserConfig {
   pub in_progress_words: BTreeSet<String>,
   pub useful_status_words: BTreeSet<String>,
@@ -85,7 +85,7 @@ impl Default for ParserConfig {
 
 impl ParserConfig {
   pub fn string_looks_like_question(&self, weidu_output: &str) -> bool {
-    let comparable_output = weidu_output.trim().to_ascii_lowercase();
+    let comparable_output = weidu_output.trim().to_lowercase();
     if let Some(bell) = self.bell_character
       && comparable_output.contains(bell)
     {
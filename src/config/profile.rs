@@ -0,0 +1,256 @@
+use std::{
+  collections::BTreeMap,
+  error::Error,
+  ffi::OsString,
+  path::{Path, PathBuf},
+};
+
+use clap::{ArgAction, CommandFactory, parser::ValueSource};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::config::{CARGO_PKG_NAME, args::Args};
+
+pub const PROFILES_LOCATION: &str = "profiles";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ProfileValue {
+  Bool(bool),
+  Integer(i64),
+  String(String),
+  List(Vec<String>),
+}
+
+impl ProfileValue {
+  fn to_arg_value(&self) -> String {
+    match self {
+      ProfileValue::Bool(value) => value.to_string(),
+      ProfileValue::Integer(value) => value.to_string(),
+      ProfileValue::String(value) => value.clone(),
+      ProfileValue::List(values) => values.join(","),
+    }
+  }
+}
+
+/// Named sets of cli options, keyed by the long flag name, ie:
+/// [profiles.eet-full]
+/// timeout = 3600
+/// batch-skip = ["setup-stratagems.tp2"]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Profiles {
+  #[serde(default)]
+  pub profiles: BTreeMap<String, BTreeMap<String, ProfileValue>>,
+}
+
+impl Profiles {
+  pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+    if !path.exists() {
+      return Err(format!("Profiles file {path:?} does not exist").into());
+    }
+    Ok(confy::load_path(path)?)
+  }
+
+  pub fn default_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(confy::get_configuration_file_path(
+      CARGO_PKG_NAME,
+      PROFILES_LOCATION,
+    )?)
+  }
+}
+
+/// Fill in any option not set on the command line or by an environment variable from the selected
+/// profile, so the precedence is cli > env > profile > defaults
+pub fn resolve_args(raw_args: Vec<OsString>) -> Result<Vec<OsString>, Box<dyn Error>> {
+  let lenient = Args::command()
+    .ignore_errors(true)
+    .disable_help_flag(true)
+    .disable_version_flag(true)
+    .disable_help_subcommand(true)
+    .mut_subcommands(|sub_command| {
+      sub_command
+        .disable_help_flag(true)
+        .mut_args(|arg| arg.required(false))
+    });
+  let matches = match lenient.try_get_matches_from(&raw_args) {
+    Ok(matches) => matches,
+    Err(_) => return Ok(raw_args),
+  };
+  let profile_name = match matches.get_one::<String>("profile") {
+    Some(profile_name) => profile_name.clone(),
+    None => return Ok(raw_args),
+  };
+  let profiles_path = match matches.get_one::<PathBuf>("profiles_file") {
+    Some(path) => path.clone(),
+    None => Profiles::default_path()?,
+  };
+  let profiles = Profiles::load(&profiles_path)?;
+  let profile = profiles.profiles.get(&profile_name).ok_or(format!(
+    "Could not find profile {profile_name:?} in {profiles_path:?}"
+  ))?;
+  log::debug!("Using profile {profile_name}: {profile:?}");
+
+  let (sub_command_name, sub_matches) = match matches.subcommand() {
+    Some(("config", _)) | None => return Ok(raw_args),
+    Some(sub_command) => sub_command,
+  };
+  let command = Args::command();
+  let sub_command = command
+    .find_subcommand(sub_command_name)
+    .ok_or(format!("Unknown command {sub_command_name}"))?;
+
+  let mut out = raw_args;
+  for (key, value) in profile {
+    let arg = match sub_command
+      .get_arguments()
+      .find(|arg| arg.get_long() == Some(key.as_str()) || arg.get_id() == key.as_str())
+    {
+      Some(arg) => arg,
+      None => {
+        log::warn!("Profile {profile_name} option {key} is not used by {sub_command_name}");
+        continue;
+      },
+    };
+    let long = match arg.get_long() {
+      Some(long) => long,
+      None => continue,
+    };
+    match sub_matches.value_source(arg.get_id().as_str()) {
+      Some(ValueSource::CommandLine) | Some(ValueSource::EnvVariable) => {
+        log::debug!("Profile option {key} overridden");
+      },
+      // Not every flag takes a value, the bare flag is passed when it sets what the profile asks for
+      _ => match (value, arg.get_action()) {
+        (ProfileValue::Bool(true), ArgAction::SetTrue)
+        | (ProfileValue::Bool(false), ArgAction::SetFalse) => out.push(format!("--{long}").into()),
+        (ProfileValue::Bool(_), ArgAction::SetTrue | ArgAction::SetFalse) => {},
+        _ => out.push(format!("--{long}={}", value.to_arg_value()).into()),
+      },
+    }
+  }
+  Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use clap::Parser;
+  use pretty_assertions::assert_eq;
+  use std::{error::Error, fs};
+
+  fn write_profiles() -> Result<tempfile::NamedTempFile, Box<dyn Error>> {
+    let file = tempfile::Builder::new().suffix(".toml").tempfile()?;
+    fs::write(
+      file.path(),
+      r#"
+[profiles.test]
+game-directory = "fixtures"
+timeout = 60
+batch-skip = ["a.tp2", "b.tp2"]
+overwrite = true
+download = true
+check-last-installed = false
+bg1-game-directory = "fixtures"
+"#,
+    )?;
+    Ok(file)
+  }
+
+  #[test]
+  fn fills_in_from_profile() -> Result<(), Box<dyn Error>> {
+    let profiles = write_profiles()?;
+    let raw: Vec<OsString> = vec![
+      "mod_installer".into(),
+      "--profiles-file".into(),
+      profiles.path().into(),
+      "--profile".into(),
+      "test".into(),
+      "normal".into(),
+      "--log-file".into(),
+      "fixtures/test.log".into(),
+      "--timeout".into(),
+      "10".into(),
+    ];
+    let result = resolve_args(raw.clone())?;
+    let mut expected = raw;
+    expected.extend([
+      OsString::from("--batch-skip=a.tp2,b.tp2"),
+      OsString::from("--check-last-installed"),
+      OsString::from("--game-directory=fixtures"),
+      OsString::from("--overwrite"),
+    ]);
+    assert_eq!(result, expected);
+    Ok(())
+  }
+
+  #[test]
+  fn cli_overrides_profile() -> Result<(), Box<dyn Error>> {
+    let profiles = write_profiles()?;
+    let raw: Vec<OsString> = vec![
+      "mod_installer".into(),
+      "--profiles-file".into(),
+      profiles.path().into(),
+      "--profile".into(),
+      "test".into(),
+      "normal".into(),
+      "-f".into(),
+      "fixtures/test.log".into(),
+      "-t".into(),
+      "10".into(),
+    ];
+    let args = Args::try_parse_from(resolve_args(raw)?)?;
+    match args.command {
+      crate::config::args::CommandType::Normal(normal) => {
+        assert_eq!(normal.install_options.timeout, 10);
+        assert_eq!(normal.install_options.overwrite, true);
+        assert_eq!(normal.install_options.download, true);
+        assert_eq!(normal.install_options.check_last_installed, false);
+        assert_eq!(
+          normal.install_options.batch.batch_skip,
+          vec!["a.tp2".to_string(), "b.tp2".to_string()]
+        );
+      },
+      command => return Err(format!("Unexpected command {command:?}").into()),
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn bare_flag_for_bool_without_value() -> Result<(), Box<dyn Error>> {
+    let file = tempfile::Builder::new().suffix(".toml").tempfile()?;
+    fs::write(file.path(), "[profiles.fetch]\nfetch-weidu-binary = true\n")?;
+    let raw: Vec<OsString> = vec![
+      "mod_installer".into(),
+      "--profiles-file".into(),
+      file.path().into(),
+      "--profile".into(),
+      "fetch".into(),
+      "normal".into(),
+      "-f".into(),
+      "fixtures/test.log".into(),
+      "-g".into(),
+      "fixtures".into(),
+    ];
+    let result = resolve_args(raw)?;
+    assert_eq!(result.last(), Some(&OsString::from("--fetch-weidu-binary")));
+    let matches = Args::command().try_get_matches_from(result)?;
+    let (_, normal) = matches.subcommand().ok_or("Missing command")?;
+    assert_eq!(normal.get_flag("fetch_weidu_binary"), true);
+    Ok(())
+  }
+
+  #[test]
+  fn missing_profile() -> Result<(), Box<dyn Error>> {
+    let profiles = write_profiles()?;
+    let raw: Vec<OsString> = vec![
+      "mod_installer".into(),
+      "--profiles-file".into(),
+      profiles.path().into(),
+      "--profile".into(),
+      "missing".into(),
+      "normal".into(),
+    ];
+    assert!(resolve_args(raw).is_err());
+    Ok(())
+  }
+}
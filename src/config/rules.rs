@@ -0,0 +1,110 @@
+use std::{
+  error::Error,
+  path::{Path, PathBuf},
+};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::config::CARGO_PKG_NAME;
+
+pub const RULES_LOCATION: &str = "rules";
+
+/// Components are named by tp2 and an optional number, "setup-ascension.tp2 #0" or "ascension"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RequireRule {
+  pub component: String,
+  pub requires: String,
+  #[serde(default)]
+  pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForbidRule {
+  pub component: String,
+  pub forbids: String,
+  #[serde(default)]
+  pub reason: String,
+}
+
+/// Requirements and conflicts the tp2s do not declare, ie:
+/// [[require]]
+/// component = "setup-ascension.tp2 #0"
+/// requires = "setup-bg2fixpack.tp2 #0"
+/// reason = "Ascension patches fixpack files"
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Rules {
+  #[serde(default)]
+  pub require: Vec<RequireRule>,
+  #[serde(default)]
+  pub forbid: Vec<ForbidRule>,
+}
+
+impl Rules {
+  pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+    if !path.exists() {
+      return Err(format!("Rules file {path:?} does not exist").into());
+    }
+    Ok(confy::load_path(path)?)
+  }
+
+  pub fn default_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(confy::get_configuration_file_path(
+      CARGO_PKG_NAME,
+      RULES_LOCATION,
+    )?)
+  }
+
+  /// The rules file given, or rules.toml in the config directory if there is one
+  pub fn find(path: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+    match path {
+      Some(path) => Self::load(path),
+      None => match Self::default_path() {
+        Ok(path) if path.exists() => Self::load(&path),
+        _ => Ok(Self::default()),
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::{error::Error, fs};
+
+  #[test]
+  fn loads_rules() -> Result<(), Box<dyn Error>> {
+    let file = tempfile::Builder::new().suffix(".toml").tempfile()?;
+    fs::write(
+      file.path(),
+      r#"
+[[require]]
+component = "setup-ascension.tp2 #0"
+requires = "setup-bg2fixpack.tp2 #0"
+reason = "Ascension patches fixpack files"
+
+[[forbid]]
+component = "a"
+forbids = "b #3"
+"#,
+    )?;
+    assert_eq!(
+      Rules::find(Some(file.path()))?,
+      Rules {
+        require: vec![RequireRule {
+          component: "setup-ascension.tp2 #0".into(),
+          requires: "setup-bg2fixpack.tp2 #0".into(),
+          reason: "Ascension patches fixpack files".into(),
+        }],
+        forbid: vec![ForbidRule {
+          component: "a".into(),
+          forbids: "b #3".into(),
+          reason: "".into(),
+        }],
+      }
+    );
+    assert!(Rules::load(Path::new("fixtures/missing-rules.toml")).is_err());
+    Ok(())
+  }
+}
@@ -0,0 +1,47 @@
+use std::{error::Error, ffi::OsString};
+
+use clap::Parser;
+
+use crate::config::{
+  CARGO_PKG_NAME,
+  args::{Args, ConfigShow},
+  parser_config::ParserConfig,
+  profile::{Profiles, resolve_args},
+  rules::Rules,
+};
+
+pub(crate) fn show_config(
+  command: &ConfigShow,
+  args: &Args,
+  parser_config: &ParserConfig,
+) -> Result<(), Box<dyn Error>> {
+  let profiles_path = match &args.profiles_file {
+    Some(path) => path.clone(),
+    None => Profiles::default_path()?,
+  };
+  println!("Profiles file: {profiles_path:?}");
+  println!("Rules file: {:?}", Rules::default_path()?);
+  if let Some(profile_name) = &args.profile {
+    let profiles = Profiles::load(&profiles_path)?;
+    let profile = profiles.profiles.get(profile_name).ok_or(format!(
+      "Could not find profile {profile_name:?} in {profiles_path:?}"
+    ))?;
+    println!("Profile {profile_name}: {profile:#?}");
+  }
+
+  if !command.command.is_empty() {
+    let mut raw_args: Vec<OsString> = vec![CARGO_PKG_NAME.into()];
+    if let Some(profile_name) = &args.profile {
+      raw_args.extend(["--profile".into(), profile_name.into()]);
+    }
+    if let Some(profiles_file) = &args.profiles_file {
+      raw_args.extend(["--profiles-file".into(), profiles_file.into()]);
+    }
+    raw_args.extend(command.command.iter().map(OsString::from));
+    let resolved = Args::try_parse_from(resolve_args(raw_args)?)?;
+    println!("Command: {:#?}", resolved.command);
+  }
+
+  println!("Parser: {parser_config:#?}");
+  Ok(())
+}
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use crate::config::log_options::LogOptions;
 
 pub struct WeiduLogOptions(Vec<LogOptions>);
@@ -6,6 +8,9 @@ impl WeiduLogOptions {
   pub fn new(options: Vec<LogOptions>) -> Self {
     Self(options)
   }
+  pub fn log_path(&self, path: &str) -> Option<PathBuf> {
+    self.0.iter().find_map(|log| log.log_path(path))
+  }
   pub fn to_args(&self, path: &str) -> Vec<String> {
     let mut out = vec![];
     if self.0.contains(&LogOptions::LogAppend) {
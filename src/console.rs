@@ -0,0 +1,185 @@
+use std::{error::Error, thread};
+
+use crate::{control::Control, events};
+
+// Lines starting with this are commands for the installer, anything else is an answer for weidu
+pub(crate) const PREFIX: char = ':';
+const DEFAULT_TAIL: usize = 20;
+
+pub(crate) const HELP: &str = r"
+Console commands, anything else is sent to weidu when it asks a question:
+:skip      stop weidu and skip the component being installed
+:pause     pause once the current batch has finished
+:resume    resume a paused install
+:abort     stop weidu and the install
+:tail [N]  show the last N lines of weidu output
+:status    show what is being installed
+:help      show this message
+Start an answer with :: to send an answer beginning with :
+";
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum ConsoleCommand {
+  Skip,
+  Pause,
+  Resume,
+  Abort,
+  Tail(usize),
+  Status,
+  Help,
+}
+
+impl TryFrom<&str> for ConsoleCommand {
+  type Error = Box<dyn Error>;
+
+  fn try_from(value: &str) -> Result<Self, Self::Error> {
+    let mut parts = value.trim().trim_start_matches(PREFIX).split_whitespace();
+    match (parts.next(), parts.next()) {
+      (Some("skip"), None) => Ok(ConsoleCommand::Skip),
+      (Some("pause"), None) => Ok(ConsoleCommand::Pause),
+      (Some("resume"), None) => Ok(ConsoleCommand::Resume),
+      (Some("abort"), None) => Ok(ConsoleCommand::Abort),
+      (Some("tail"), None) => Ok(ConsoleCommand::Tail(DEFAULT_TAIL)),
+      (Some("tail"), Some(lines)) => Ok(ConsoleCommand::Tail(lines.parse()?)),
+      (Some("status"), None) => Ok(ConsoleCommand::Status),
+      (Some("help"), None) => Ok(ConsoleCommand::Help),
+      _ => Err(format!("Unknown command {:?}", value.trim()).into()),
+    }
+  }
+}
+
+fn run(control: &Control, command: ConsoleCommand) {
+  match command {
+    ConsoleCommand::Skip => match control.skip() {
+      Ok(_) => log::warn!("Skipping the current component"),
+      Err(err) => println!("{err}"),
+    },
+    ConsoleCommand::Pause => {
+      log::info!("Pausing once the current batch has finished");
+      control.pause();
+    },
+    ConsoleCommand::Resume => {
+      log::info!("Resuming install");
+      control.resume();
+    },
+    ConsoleCommand::Abort => {
+      log::warn!("Aborting install");
+      control.abort();
+    },
+    ConsoleCommand::Tail(lines) => match control.log() {
+      Some(log) => println!("{}", log.tail(lines).join("\n")),
+      None => println!("Weidu is not running"),
+    },
+    ConsoleCommand::Status => {
+      let batch = events::current_batch();
+      if batch.is_empty() {
+        println!("Not installing a batch");
+      }
+      for component in batch {
+        println!(
+          "Installing {} #{} {}",
+          component.full_component_name(),
+          component.component,
+          component.component_name
+        );
+      }
+      println!(
+        "Paused: {}, aborted: {}, skip requested: {}",
+        control.is_paused(),
+        control.is_aborted(),
+        control.is_skipped()
+      );
+    },
+    ConsoleCommand::Help => println!("{HELP}"),
+  }
+}
+
+/// Sorts a line typed on stdin into a console command or an answer for weidu
+pub(crate) fn handle_line(control: &Control, line: String) {
+  if let Some(answer) = line.strip_prefix("::") {
+    control.typed(format!("{PREFIX}{answer}"));
+    return;
+  }
+  if !line.starts_with(PREFIX) {
+    control.typed(line);
+    return;
+  }
+  match ConsoleCommand::try_from(line.as_str()) {
+    Ok(command) => run(control, command),
+    Err(err) => println!("{err}\n{HELP}"),
+  }
+}
+
+/// Takes over stdin for the rest of the process, does nothing if already started
+pub(crate) fn start(control: &'static Control) {
+  if control.set_console(true) {
+    return;
+  }
+  log::info!("Type {PREFIX}help for console commands");
+  thread::spawn(move || {
+    let stdin = std::io::stdin();
+    loop {
+      let mut line = String::new();
+      match stdin.read_line(&mut line) {
+        Ok(0) => {
+          log::debug!("Stdin closed, no more console commands");
+          return;
+        },
+        Ok(_) => handle_line(control, line),
+        Err(err) => {
+          log::error!("Failed to read stdin, {err}");
+          return;
+        },
+      }
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::error::Error;
+
+  #[test]
+  fn parse_commands() -> Result<(), Box<dyn Error>> {
+    let tests = vec![
+      (":skip\n", ConsoleCommand::Skip),
+      (":pause", ConsoleCommand::Pause),
+      (":tail", ConsoleCommand::Tail(DEFAULT_TAIL)),
+      (":tail 5\n", ConsoleCommand::Tail(5)),
+      (":status", ConsoleCommand::Status),
+    ];
+    for (test, expected) in tests {
+      assert_eq!(ConsoleCommand::try_from(test)?, expected);
+    }
+    assert!(ConsoleCommand::try_from(":tail many").is_err());
+    assert!(ConsoleCommand::try_from(":install").is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn sorts_commands_from_answers() -> Result<(), Box<dyn Error>> {
+    let control: &'static Control = Box::leak(Box::new(Control::new()));
+    control.set_console(true);
+    handle_line(control, "y\n".to_string());
+    handle_line(control, ":pause\n".to_string());
+    handle_line(control, "::colon\n".to_string());
+    assert_eq!(control.is_paused(), true);
+    assert_eq!(control.user_input("Install?", 1)?, "y\n");
+    assert_eq!(control.user_input("Install?", 1)?, ":colon\n");
+    // Between batches there is nothing to skip, so the next batch is not skipped
+    handle_line(control, ":skip\n".to_string());
+    assert_eq!(control.is_skipped(), false);
+    control.set_child(Some(1));
+    handle_line(control, ":skip\n".to_string());
+    assert_eq!(
+      control
+        .user_input("Install?", 1)
+        .map_err(|err| err.to_string()),
+      Err(crate::control::SKIPPED.to_string())
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,354 @@
+use std::{
+  collections::VecDeque,
+  error::Error,
+  sync::{
+    Mutex,
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+  },
+};
+
+use serde_derive::Serialize;
+
+use crate::{
+  events,
+  internal_log::InternalLog,
+  utils::{get_user_input, sleep},
+  weidu::component::WeiduComponent,
+};
+
+pub(crate) const ABORTED: &str = "Install aborted";
+pub(crate) const SKIPPED: &str = "Component skipped";
+pub(crate) const STOPPED: &str = "Install stopped";
+pub(crate) const NOT_INSTALLING: &str = "Weidu is not running, there is nothing to skip";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct Question {
+  pub(crate) id: usize,
+  pub(crate) question: String,
+  pub(crate) components: Vec<WeiduComponent>,
+}
+
+/// Shared between the install and whatever is driving it, ie: the serve command, so
+/// it can pause between batches, abort, and answer questions instead of stdin
+#[derive(Debug)]
+pub(crate) struct Control {
+  paused: AtomicBool,
+  aborted: AtomicBool,
+  remote: AtomicBool,
+  console: AtomicBool,
+  skip: AtomicBool,
+  stop: AtomicBool,
+  child: Mutex<Option<u32>>,
+  next_id: AtomicUsize,
+  questions: Mutex<Vec<Question>>,
+  answers: Mutex<Vec<(usize, String)>>,
+  typed: Mutex<VecDeque<String>>,
+  log: Mutex<Option<InternalLog>>,
+}
+
+pub(crate) static CONTROL: Control = Control::new();
+
+impl Control {
+  pub(crate) const fn new() -> Self {
+    Self {
+      paused: AtomicBool::new(false),
+      aborted: AtomicBool::new(false),
+      remote: AtomicBool::new(false),
+      console: AtomicBool::new(false),
+      skip: AtomicBool::new(false),
+      stop: AtomicBool::new(false),
+      child: Mutex::new(None),
+      next_id: AtomicUsize::new(0),
+      questions: Mutex::new(vec![]),
+      answers: Mutex::new(vec![]),
+      typed: Mutex::new(VecDeque::new()),
+      log: Mutex::new(None),
+    }
+  }
+
+  /// Clears pause and abort, ready for a new install
+  pub(crate) fn reset(&self) {
+    self.paused.store(false, Ordering::Relaxed);
+    self.aborted.store(false, Ordering::Relaxed);
+    self.skip.store(false, Ordering::Relaxed);
+    self.stop.store(false, Ordering::Relaxed);
+    if let Ok(mut questions) = self.questions.lock() {
+      questions.clear();
+    }
+    if let Ok(mut answers) = self.answers.lock() {
+      answers.clear();
+    }
+    if let Ok(mut typed) = self.typed.lock() {
+      typed.clear();
+    }
+  }
+
+  pub(crate) fn set_remote(&self, remote: bool) {
+    self.remote.store(remote, Ordering::Relaxed);
+  }
+
+  pub(crate) fn is_remote(&self) -> bool {
+    self.remote.load(Ordering::Relaxed)
+  }
+
+  /// Marks stdin as owned by the console, answers then arrive through typed.
+  /// Returns if it was already set
+  pub(crate) fn set_console(&self, console: bool) -> bool {
+    self.console.swap(console, Ordering::Relaxed)
+  }
+
+  pub(crate) fn typed(&self, text: String) {
+    if let Ok(mut typed) = self.typed.lock() {
+      typed.push_back(text);
+    }
+  }
+
+  /// Stop the weidu process for the current batch and carry on with the next.
+  /// Refused between batches, so it never lands on a batch that has not started yet
+  pub(crate) fn skip(&self) -> Result<(), Box<dyn Error>> {
+    let child = self.child.lock().map_err(|err| err.to_string())?;
+    if child.is_none() {
+      return Err(NOT_INSTALLING.into());
+    }
+    self.skip.store(true, Ordering::Relaxed);
+    Ok(())
+  }
+
+  pub(crate) fn is_skipped(&self) -> bool {
+    self.skip.load(Ordering::Relaxed)
+  }
+
+  pub(crate) fn take_skip(&self) -> bool {
+    self.skip.swap(false, Ordering::Relaxed)
+  }
+
+  /// Finish the current batch, then stop the install
+  pub(crate) fn stop(&self) {
+    self.stop.store(true, Ordering::Relaxed);
+  }
+
+  pub(crate) fn is_stopping(&self) -> bool {
+    self.stop.load(Ordering::Relaxed)
+  }
+
+  /// Process id of the running weidu
+  pub(crate) fn set_child(&self, child: Option<u32>) {
+    if let Ok(mut current) = self.child.lock() {
+      *current = child;
+    }
+  }
+
+  pub(crate) fn child(&self) -> Option<u32> {
+    *self.child.lock().ok()?
+  }
+
+  /// The output of the batch being installed, if any
+  pub(crate) fn set_log(&self, log: Option<InternalLog>) {
+    if let Ok(mut current) = self.log.lock() {
+      *current = log;
+    }
+  }
+
+  pub(crate) fn log(&self) -> Option<InternalLog> {
+    self.log.lock().ok()?.clone()
+  }
+
+  pub(crate) fn pause(&self) {
+    self.paused.store(true, Ordering::Relaxed);
+  }
+
+  pub(crate) fn resume(&self) {
+    self.paused.store(false, Ordering::Relaxed);
+  }
+
+  pub(crate) fn abort(&self) {
+    self.aborted.store(true, Ordering::Relaxed);
+  }
+
+  pub(crate) fn is_paused(&self) -> bool {
+    self.paused.load(Ordering::Relaxed)
+  }
+
+  pub(crate) fn is_aborted(&self) -> bool {
+    self.aborted.load(Ordering::Relaxed)
+  }
+
+  /// Called between batches, blocks while paused
+  pub(crate) fn wait_while_paused(&self, tick: u64) -> Result<(), Box<dyn Error>> {
+    if self.is_stopping() {
+      return Err(STOPPED.into());
+    }
+    if self.is_paused() {
+      log::info!("Install paused, waiting to be resumed");
+    }
+    while self.is_paused() && !self.is_aborted() {
+      sleep(tick);
+    }
+    if self.is_aborted() {
+      return Err(ABORTED.into());
+    }
+    Ok(())
+  }
+
+  pub(crate) fn questions(&self) -> Vec<Question> {
+    match self.questions.lock() {
+      Ok(questions) => questions.clone(),
+      Err(_) => vec![],
+    }
+  }
+
+  pub(crate) fn answer(&self, id: usize, text: &str) -> Result<(), Box<dyn Error>> {
+    let pending = self
+      .questions
+      .lock()
+      .map_err(|err| err.to_string())?
+      .iter()
+      .any(|question| question.id == id);
+    if !pending {
+      return Err(format!("No pending question with id {id}").into());
+    }
+    let text = if text.ends_with('\n') {
+      text.to_string()
+    } else {
+      format!("{text}\n")
+    };
+    self
+      .answers
+      .lock()
+      .map_err(|err| err.to_string())?
+      .push((id, text));
+    Ok(())
+  }
+
+  fn take_answer(&self, id: usize) -> Option<String> {
+    let mut answers = self.answers.lock().ok()?;
+    let position = answers.iter().position(|(answer_id, _)| *answer_id == id)?;
+    let (_, text) = answers.remove(position);
+    if let Ok(mut questions) = self.questions.lock() {
+      questions.retain(|question| question.id != id);
+    }
+    Some(text)
+  }
+
+  fn wait_for_typed(&self, tick: u64) -> Result<String, Box<dyn Error>> {
+    loop {
+      if let Some(answer) = self
+        .typed
+        .lock()
+        .ok()
+        .and_then(|mut typed| typed.pop_front())
+      {
+        return Ok(answer);
+      }
+      if self.is_aborted() {
+        return Err(ABORTED.into());
+      }
+      if self.is_skipped() {
+        return Err(SKIPPED.into());
+      }
+      sleep(tick);
+    }
+  }
+
+  /// Reads an answer from stdin, or when driven remotely waits for one to be submitted
+  pub(crate) fn user_input(&self, question: &str, tick: u64) -> Result<String, Box<dyn Error>> {
+    if !self.is_remote() {
+      if self.console.load(Ordering::Relaxed) {
+        return self.wait_for_typed(tick);
+      }
+      return get_user_input(tick);
+    }
+    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+    self
+      .questions
+      .lock()
+      .map_err(|err| err.to_string())?
+      .push(Question {
+        id,
+        question: question.to_string(),
+        components: events::current_batch(),
+      });
+    log::info!("Waiting for an answer to question {id}");
+    loop {
+      if let Some(answer) = self.take_answer(id) {
+        return Ok(answer);
+      }
+      if self.is_aborted() {
+        return Err(ABORTED.into());
+      }
+      if self.is_skipped() {
+        if let Ok(mut questions) = self.questions.lock() {
+          questions.retain(|question| question.id != id);
+        }
+        return Err(SKIPPED.into());
+      }
+      sleep(tick);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::{error::Error, thread};
+
+  #[test]
+  fn answers_remote_questions() -> Result<(), Box<dyn Error>> {
+    let control: &'static Control = Box::leak(Box::new(Control::new()));
+    control.set_remote(true);
+    let asking = thread::spawn(|| {
+      control
+        .user_input("Do you want to install?", 1)
+        .map_err(|err| err.to_string())
+    });
+    while control.questions().is_empty() {
+      sleep(1);
+    }
+    let questions = control.questions();
+    assert_eq!(questions[0].question, "Do you want to install?");
+    assert!(control.answer(questions[0].id + 1, "y").is_err());
+    control.answer(questions[0].id, "y")?;
+    assert_eq!(asking.join().map_err(|_| "thread panicked")??, "y\n");
+    assert_eq!(control.questions(), vec![]);
+    Ok(())
+  }
+
+  #[test]
+  fn abort_stops_waiting() -> Result<(), Box<dyn Error>> {
+    let control: &'static Control = Box::leak(Box::new(Control::new()));
+    control.set_remote(true);
+    control.pause();
+    let waiting = thread::spawn(|| control.wait_while_paused(1).map_err(|err| err.to_string()));
+    control.abort();
+    assert_eq!(
+      waiting.join().map_err(|_| "thread panicked")?,
+      Err(ABORTED.to_string())
+    );
+    assert!(control.user_input("Do you want to install?", 1).is_err());
+    control.typed("y\n".to_string());
+    control.reset();
+    assert_eq!(control.is_aborted(), false);
+    assert_eq!(
+      control.typed.lock().map_err(|err| err.to_string())?.len(),
+      0
+    );
+    assert_eq!(control.wait_while_paused(1).is_ok(), true);
+    Ok(())
+  }
+
+  #[test]
+  fn stop_after_batch() -> Result<(), Box<dyn Error>> {
+    let control = Control::new();
+    assert_eq!(control.wait_while_paused(1).is_ok(), true);
+    control.stop();
+    assert_eq!(
+      control.wait_while_paused(1).map_err(|err| err.to_string()),
+      Err(STOPPED.to_string())
+    );
+    control.reset();
+    assert_eq!(control.is_stopping(), false);
+    Ok(())
+  }
+}
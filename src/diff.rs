@@ -0,0 +1,353 @@
+use std::{
+  error::Error,
+  fmt::Display,
+  path::{Path, PathBuf},
+};
+
+use serde_derive::Serialize;
+
+use crate::{
+  config::{args::DiffCommand, diff_format::DiffFormat},
+  weidu::{
+    component::WeiduComponent,
+    verify::{LogEntry, longest_increasing, read_log},
+  },
+};
+
+/// A field of a component that differs between the two logs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct Change {
+  pub(crate) field: &'static str,
+  pub(crate) a: String,
+  pub(crate) b: String,
+}
+
+impl Display for Change {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} {:?} -> {:?}", self.field, self.a, self.b)
+  }
+}
+
+/// Both logs lined up by component, in the order of b with what is only in a where it was
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum Row {
+  Same {
+    a: LogEntry,
+    b: LogEntry,
+  },
+  Changed {
+    a: LogEntry,
+    b: LogEntry,
+    changes: Vec<Change>,
+  },
+  Moved {
+    a: LogEntry,
+    b: LogEntry,
+    changes: Vec<Change>,
+  },
+  OnlyInA {
+    a: LogEntry,
+  },
+  OnlyInB {
+    b: LogEntry,
+  },
+}
+
+impl Row {
+  fn sides(&self) -> (Option<&LogEntry>, Option<&LogEntry>) {
+    match self {
+      Row::Same { a, b } | Row::Changed { a, b, .. } | Row::Moved { a, b, .. } => {
+        (Some(a), Some(b))
+      },
+      Row::OnlyInA { a } => (Some(a), None),
+      Row::OnlyInB { b } => (None, Some(b)),
+    }
+  }
+
+  fn marker(&self) -> char {
+    match self {
+      Row::Same { .. } => ' ',
+      Row::Changed { .. } => '|',
+      Row::Moved { .. } => '~',
+      Row::OnlyInA { .. } => '<',
+      Row::OnlyInB { .. } => '>',
+    }
+  }
+}
+
+fn list(changes: &[Change]) -> String {
+  match changes {
+    [] => String::new(),
+    changes => {
+      let changes: Vec<String> = changes.iter().map(ToString::to_string).collect();
+      format!(": {}", changes.join(", "))
+    },
+  }
+}
+
+impl Display for Row {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Row::Same { b, .. } => write!(f, "  {}", b.component),
+      Row::Changed { a, b, changes } => write!(
+        f,
+        "| {}, a line {} b line {}{}",
+        b.component,
+        a.line,
+        b.line,
+        list(changes)
+      ),
+      Row::Moved { a, b, changes } => write!(
+        f,
+        "~ {}, moved from a line {} to b line {}{}",
+        b.component,
+        a.line,
+        b.line,
+        list(changes)
+      ),
+      Row::OnlyInA { a } => write!(f, "- {}, a line {}", a.component, a.line),
+      Row::OnlyInB { b } => write!(f, "+ {}, b line {}", b.component, b.line),
+    }
+  }
+}
+
+/// Same mod and component number, a different language is shown as a change
+fn same_component(a: &WeiduComponent, b: &WeiduComponent) -> bool {
+  a.full_component_name() == b.full_component_name() && a.component == b.component
+}
+
+fn changes(a: &WeiduComponent, b: &WeiduComponent) -> Vec<Change> {
+  if a == b && a.strict_matching(b) {
+    return vec![];
+  }
+  [
+    ("language", &a.lang, &b.lang),
+    ("component", &a.component_name, &b.component_name),
+    ("subcomponent", &a.sub_component, &b.sub_component),
+    ("version", &a.version, &b.version),
+  ]
+  .into_iter()
+  .filter(|(_, a, b)| a != b)
+  .map(|(field, a, b)| Change {
+    field,
+    a: a.clone(),
+    b: b.clone(),
+  })
+  .collect()
+}
+
+pub(crate) fn diff(a: &[LogEntry], b: &[LogEntry]) -> Vec<Row> {
+  // Each component of b is paired with the first unpaired copy of it in a
+  let mut paired = vec![false; a.len()];
+  let pairs: Vec<Option<usize>> = b
+    .iter()
+    .map(|entry| {
+      let index = a.iter().enumerate().position(|(index, other)| {
+        !paired[index] && same_component(&other.component, &entry.component)
+      })?;
+      paired[index] = true;
+      Some(index)
+    })
+    .collect();
+  // The most components that are in the same order in both logs, the others moved
+  let a_indexes: Vec<usize> = pairs.iter().flatten().copied().collect();
+  let in_order: Vec<usize> = longest_increasing(&a_indexes)
+    .into_iter()
+    .map(|position| a_indexes[position])
+    .collect();
+
+  let mut rows = vec![];
+  let mut next = 0;
+  let only_in_a = |rows: &mut Vec<Row>, from: usize, to: usize| {
+    for index in from..to {
+      if !paired[index] {
+        rows.push(Row::OnlyInA {
+          a: a[index].clone(),
+        });
+      }
+    }
+  };
+  for (position, (entry, pair)) in b.iter().zip(&pairs).enumerate() {
+    let Some(index) = *pair else {
+      // As in a unified diff, what was removed before this point is shown before what was added
+      let until = pairs[position..]
+        .iter()
+        .flatten()
+        .find(|index| in_order.contains(index))
+        .map_or(a.len(), |index| *index);
+      only_in_a(&mut rows, next, until);
+      next = next.max(until);
+      rows.push(Row::OnlyInB { b: entry.clone() });
+      continue;
+    };
+    let changes = changes(&a[index].component, &entry.component);
+    if !in_order.contains(&index) {
+      rows.push(Row::Moved {
+        a: a[index].clone(),
+        b: entry.clone(),
+        changes,
+      });
+      continue;
+    }
+    only_in_a(&mut rows, next, index);
+    next = index + 1;
+    rows.push(match changes.is_empty() {
+      true => Row::Same {
+        a: a[index].clone(),
+        b: entry.clone(),
+      },
+      false => Row::Changed {
+        a: a[index].clone(),
+        b: entry.clone(),
+        changes,
+      },
+    });
+  }
+  only_in_a(&mut rows, next, a.len());
+  rows
+}
+
+/// The line number and log line of one side of a row, empty when the component is not on that side
+type Cell = (String, String);
+
+/// Two columns with the line numbers of each log, marked like `diff --side-by-side`
+pub(crate) fn side_by_side(rows: &[Row], a_name: &str, b_name: &str) -> String {
+  let cell = |entry: Option<&LogEntry>| -> Cell {
+    entry
+      .map(|entry| (entry.line.to_string(), entry.component.to_log_line()))
+      .unwrap_or_default()
+  };
+  let cells: Vec<(Cell, char, Cell)> = rows
+    .iter()
+    .map(|row| {
+      let (a, b) = row.sides();
+      (cell(a), row.marker(), cell(b))
+    })
+    .collect();
+  let number = cells
+    .iter()
+    .flat_map(|((a, _), _, (b, _))| [a.len(), b.len()])
+    .max()
+    .unwrap_or_default();
+  let width = cells
+    .iter()
+    .map(|((_, a), _, _)| a.chars().count())
+    .chain([a_name.chars().count()])
+    .max()
+    .unwrap_or_default();
+  let mut table = format!("{:number$} {a_name:width$}   {:number$} {b_name}\n", "", "");
+  for ((a_line, a), marker, (b_line, b)) in cells {
+    table
+      .push_str(format!("{a_line:>number$} {a:width$} {marker} {b_line:>number$} {b}").trim_end());
+    table.push('\n');
+  }
+  table
+}
+
+/// A game directory stands for the weidu.log in it
+fn log_path(path: &Path) -> PathBuf {
+  if path.is_dir() {
+    path.join("weidu.log")
+  } else {
+    path.to_path_buf()
+  }
+}
+
+pub(crate) fn diff_command(command: &DiffCommand) -> Result<(), Box<dyn Error>> {
+  let (a_path, b_path) = (log_path(&command.a), log_path(&command.b));
+  let rows = diff(&read_log(&a_path)?, &read_log(&b_path)?);
+  let differences: Vec<&Row> = rows
+    .iter()
+    .filter(|row| !matches!(row, Row::Same { .. }))
+    .collect();
+  match command.format {
+    DiffFormat::Text => {
+      println!("--- {}\n+++ {}", a_path.display(), b_path.display());
+      for row in &differences {
+        println!("{row}");
+      }
+    },
+    DiffFormat::Json => println!("{}", serde_json::to_string_pretty(&differences)?),
+    DiffFormat::SideBySide => print!(
+      "{}",
+      side_by_side(
+        &rows,
+        &a_path.display().to_string(),
+        &b_path.display().to_string()
+      )
+    ),
+  }
+  if differences.is_empty() {
+    log::info!("{a_path:?} and {b_path:?} have the same components");
+    return Ok(());
+  }
+  Err(
+    format!(
+      "{a_path:?} and {b_path:?} differ in {} places",
+      differences.len()
+    )
+    .into(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use crate::weidu::log_file::parse_log;
+  use pretty_assertions::assert_eq;
+  use std::error::Error;
+
+  fn entries(log: &str) -> Result<Vec<LogEntry>, Box<dyn Error>> {
+    Ok(parse_log(log, None).into_iter().collect::<Result<_, _>>()?)
+  }
+
+  #[test]
+  fn lines_up_logs_by_component() -> Result<(), Box<dyn Error>> {
+    let a = entries(
+      "~A/A.TP2~ #0 #0 // Core: v1\n\
+      ~A/A.TP2~ #0 #1 // Tweak -> Fast: v1\n\
+      ~B/B.TP2~ #0 #0 // B\n\
+      ~C/C.TP2~ #0 #0 // Gone\n\
+      ~D/D.TP2~ #0 #0 // D\n",
+    )?;
+    let b = entries(
+      "~A/A.TP2~ #0 #0 // Core: v2\n\
+      ~D/D.TP2~ #0 #0 // D\n\
+      ~A/A.TP2~ #1 #1 // Tweak -> Slow: v1\n\
+      ~B/B.TP2~ #0 #0 // B\n\
+      ~E/E.TP2~ #0 #0 // New\n",
+    )?;
+    let rows = diff(&a, &b);
+    assert_eq!(
+      rows.iter().map(ToString::to_string).collect::<Vec<_>>(),
+      vec![
+        r#"| a/a.tp2 #0 #0 // Core, a line 1 b line 1: version "v1" -> "v2""#,
+        r#"~ d/d.tp2 #0 #0 // D, moved from a line 5 to b line 2"#,
+        r#"| a/a.tp2 #1 #1 // Tweak, a line 2 b line 3: language "0" -> "1", subcomponent "Fast" -> "Slow""#,
+        "  b/b.tp2 #0 #0 // B",
+        "- c/c.tp2 #0 #0 // Gone, a line 4",
+        "+ e/e.tp2 #0 #0 // New, b line 5",
+      ]
+    );
+    assert_eq!(
+      side_by_side(&rows, "a.log", "b.log")
+        .lines()
+        .collect::<Vec<_>>(),
+      vec![
+        "  a.log                                    b.log",
+        "1 ~A/A.TP2~ #0 #0 // Core: v1          | 1 ~A/A.TP2~ #0 #0 // Core: v2",
+        "5 ~D/D.TP2~ #0 #0 // D                 ~ 2 ~D/D.TP2~ #0 #0 // D",
+        "2 ~A/A.TP2~ #0 #1 // Tweak -> Fast: v1 | 3 ~A/A.TP2~ #1 #1 // Tweak -> Slow: v1",
+        "3 ~B/B.TP2~ #0 #0 // B                   4 ~B/B.TP2~ #0 #0 // B",
+        "4 ~C/C.TP2~ #0 #0 // Gone              <",
+        "                                       > 5 ~E/E.TP2~ #0 #0 // New",
+      ]
+    );
+    let json: serde_json::Value = serde_json::to_value(&rows[4])?;
+    assert_eq!(json["kind"], "only_in_a");
+    assert_eq!(json["a"]["line"], 4);
+    Ok(())
+  }
+}
@@ -0,0 +1,395 @@
+use std::{error::Error, fmt::Display, path::Path};
+
+use crate::{
+  config::{args::DriftCommand, options::Options},
+  scan::components::list_components,
+  utils::search_mod_folders,
+  weidu::{
+    component::WeiduComponent,
+    log_file::{LogComponent, LogLine, WeiduLog},
+    tp2::{Tp2, find_tp2},
+    verify::{LogEntry, read_log},
+  },
+};
+
+/// How a component in the target log differs from what its mod lists now
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ComponentDrift {
+  Renumbered {
+    entry: LogEntry,
+    now: WeiduComponent,
+  },
+  Renamed {
+    entry: LogEntry,
+    now: WeiduComponent,
+  },
+  /// The number has another name and no label says it is the same component, so it is left for review
+  Changed {
+    entry: LogEntry,
+    now: WeiduComponent,
+  },
+  Removed {
+    entry: LogEntry,
+  },
+}
+
+impl ComponentDrift {
+  pub(crate) fn entry(&self) -> &LogEntry {
+    match self {
+      ComponentDrift::Renumbered { entry, .. }
+      | ComponentDrift::Renamed { entry, .. }
+      | ComponentDrift::Changed { entry, .. }
+      | ComponentDrift::Removed { entry } => entry,
+    }
+  }
+
+  /// The log line with this drift applied, removed and changed components are commented out
+  fn remap(&self, line: &LogLine) -> LogLine {
+    let LogLine::Component(found) = line else {
+      return line.clone();
+    };
+    let component = match self {
+      ComponentDrift::Renumbered { now, .. } => WeiduComponent {
+        component: now.component.clone(),
+        ..found.component.clone()
+      },
+      ComponentDrift::Renamed { now, .. } => WeiduComponent {
+        component_name: now.component_name.clone(),
+        sub_component: now.sub_component.clone(),
+        version: now.version.clone(),
+        ..found.component.clone()
+      },
+      ComponentDrift::Changed { now, .. } => {
+        return LogLine::Text(format!(
+          "// Review, component #{} is now {:?}: {line}",
+          now.component, now.component_name
+        ));
+      },
+      ComponentDrift::Removed { .. } => {
+        return LogLine::Text(format!("// Removed from the mod: {line}"));
+      },
+    };
+    LogLine::Component(Box::new(LogComponent::new(
+      component,
+      found.annotations.clone(),
+    )))
+  }
+}
+
+impl Display for ComponentDrift {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ComponentDrift::Renumbered { entry, now } => write!(
+        f,
+        "log line {}: {} is now component #{}",
+        entry.line, entry.component, now.component
+      ),
+      ComponentDrift::Renamed { entry, now } => write!(
+        f,
+        "log line {}: component #{} of {} is now {:?}",
+        entry.line, entry.component.component, entry.component.tp_file, now.component_name
+      ),
+      ComponentDrift::Changed { entry, now } => write!(
+        f,
+        "log line {}: component #{} of {} is now {:?}, check it is the same component",
+        entry.line, entry.component.component, entry.component.tp_file, now.component_name
+      ),
+      ComponentDrift::Removed { entry } => write!(
+        f,
+        "log line {}: {} is no longer in {}",
+        entry.line, entry.component, entry.component.tp_file
+      ),
+    }
+  }
+}
+
+/// Versions are expected to change, so only the component and subcomponent names are compared
+fn same_name(left: &WeiduComponent, right: &WeiduComponent) -> bool {
+  left.component_name.trim().to_lowercase() == right.component_name.trim().to_lowercase()
+    && left.sub_component.trim().to_lowercase() == right.sub_component.trim().to_lowercase()
+}
+
+/// A labelled component is found by its label, whatever its number and name are now
+fn detect_by_label(entry: &LogEntry, of_mod: &[&WeiduComponent]) -> Option<ComponentDrift> {
+  let found = of_mod
+    .iter()
+    .find(|component| component.label.eq_ignore_ascii_case(&entry.component.label));
+  match found {
+    Some(component) if component.component != entry.component.component => {
+      Some(ComponentDrift::Renumbered {
+        entry: entry.clone(),
+        now: (*component).clone(),
+      })
+    },
+    Some(component)
+      if same_name(component, &entry.component) || entry.component.component_name.is_empty() =>
+    {
+      None
+    },
+    Some(component) => Some(ComponentDrift::Renamed {
+      entry: entry.clone(),
+      now: (*component).clone(),
+    }),
+    None => Some(ComponentDrift::Removed {
+      entry: entry.clone(),
+    }),
+  }
+}
+
+/// Compares the target log with the components listed for its mods, mods that were not listed
+/// are left out. Labels are compared when both have them, otherwise names
+pub(crate) fn detect(target: &[LogEntry], listed: &[WeiduComponent]) -> Vec<ComponentDrift> {
+  target
+    .iter()
+    .filter_map(|entry| {
+      let of_mod: Vec<&WeiduComponent> = listed
+        .iter()
+        .filter(|component| {
+          component.full_component_name() == entry.component.full_component_name()
+            && component.lang == entry.component.lang
+        })
+        .collect();
+      if of_mod.is_empty() {
+        return None;
+      }
+      if !entry.component.label.is_empty()
+        && of_mod.iter().any(|component| !component.label.is_empty())
+      {
+        return detect_by_label(entry, &of_mod);
+      }
+      let by_number = of_mod
+        .iter()
+        .find(|component| component.component == entry.component.component);
+      match by_number {
+        Some(component)
+          if same_name(component, &entry.component)
+            || entry.component.component_name.is_empty() =>
+        {
+          None
+        },
+        _ => match of_mod
+          .iter()
+          .find(|component| same_name(component, &entry.component))
+        {
+          Some(component) => Some(ComponentDrift::Renumbered {
+            entry: entry.clone(),
+            now: (*component).clone(),
+          }),
+          None => Some(match by_number {
+            Some(component) => ComponentDrift::Changed {
+              entry: entry.clone(),
+              now: (*component).clone(),
+            },
+            None => ComponentDrift::Removed {
+              entry: entry.clone(),
+            },
+          }),
+        },
+      }
+    })
+    .collect()
+}
+
+/// Applies the drifts to the log, other lines are kept as they were
+pub(crate) fn remap(log: &WeiduLog, drifts: &[ComponentDrift]) -> WeiduLog {
+  let lines = log
+    .lines
+    .iter()
+    .enumerate()
+    .map(
+      |(index, line)| match drifts.iter().find(|drift| drift.entry().line == index + 1) {
+        Some(drift) => drift.remap(line),
+        None => line.clone(),
+      },
+    )
+    .collect();
+  WeiduLog {
+    lines,
+    ..log.clone()
+  }
+}
+
+/// weidu does not list labels, so they are read from the tp2
+fn with_labels(components: Vec<WeiduComponent>, tp2: &Path) -> Vec<WeiduComponent> {
+  let labelled = match Tp2::read(tp2) {
+    Ok(tp2) => tp2.components,
+    Err(err) => {
+      log::debug!("Comparing the components of {tp2:?} by name, {err}");
+      return components;
+    },
+  };
+  components
+    .into_iter()
+    .map(|component| {
+      let label = labelled
+        .iter()
+        .find(|labelled| labelled.number.to_string() == component.component)
+        .and_then(|labelled| labelled.labels.first().cloned())
+        .unwrap_or_default();
+      WeiduComponent { label, ..component }
+    })
+    .collect()
+}
+
+/// Lists the components of each mod in the log, from the mod directories
+pub(crate) fn list_mods(target: &[LogEntry], options: &Options) -> Vec<WeiduComponent> {
+  let mut listed: Vec<WeiduComponent> = vec![];
+  let mut seen: Vec<(String, String)> = vec![];
+  for entry in target {
+    let key = (
+      entry.component.full_component_name(),
+      entry.component.lang.clone(),
+    );
+    if seen.contains(&key) {
+      continue;
+    }
+    seen.push(key);
+    let components = search_mod_folders(&options.mod_directories, &entry.component, options.depth)
+      .and_then(|mod_folder| {
+        find_tp2(&mod_folder, &entry.component.tp_file)
+          .ok_or(format!("{mod_folder:?} has no {}", entry.component.tp_file).into())
+      })
+      .and_then(|tp2| {
+        let components = list_components(&options.weidu_binary, &tp2, &entry.component.lang)?;
+        Ok(with_labels(components, &tp2))
+      });
+    match components {
+      Ok(components) => listed.extend(components),
+      Err(err) => log::warn!(
+        "Could not list the components of {}, {err}",
+        entry.component.tp_file
+      ),
+    }
+  }
+  listed
+}
+
+/// Stops an install before it starts if the target log no longer matches its mods
+pub(crate) fn check_drift(log_file: &Path, options: &Options) -> Result<(), Box<dyn Error>> {
+  let target = read_log(log_file)?;
+  let drifts = detect(&target, &list_mods(&target, options));
+  if drifts.is_empty() {
+    return Ok(());
+  }
+  for drift in &drifts {
+    log::warn!("{drift}");
+  }
+  Err(
+    format!(
+      "{} components of {log_file:?} no longer match their mods, \
+      review the remapped log with `drift --log-file {log_file:?}`",
+      drifts.len()
+    )
+    .into(),
+  )
+}
+
+pub(crate) fn drift_command(command: &DriftCommand) -> Result<(), Box<dyn Error>> {
+  let target = read_log(&command.log_file)?;
+  let drifts = detect(&target, &list_mods(&target, &command.options));
+  if drifts.is_empty() {
+    log::info!("{:?} matches its mods", command.log_file);
+    return Ok(());
+  }
+  let log = WeiduLog::read(&command.log_file)?;
+  for drift in &drifts {
+    if let Some(line) = log.lines.get(drift.entry().line - 1) {
+      println!("{drift}\n- {line}\n+ {}", drift.remap(line));
+    }
+  }
+  match &command.output {
+    Some(output) => {
+      remap(&log, &drifts).write(output)?;
+      log::info!("Wrote the remapped log to {output:?}");
+      Ok(())
+    },
+    None => Err(
+      format!(
+        "{} components of {:?} no longer match their mods, pass --output to write the remapped log",
+        drifts.len(),
+        command.log_file
+      )
+      .into(),
+    ),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::{error::Error, fs};
+
+  fn components(log: &str) -> Result<Vec<WeiduComponent>, Box<dyn Error>> {
+    log
+      .lines()
+      .map(|line| WeiduComponent::try_from(line.to_string()))
+      .collect()
+  }
+
+  #[test]
+  fn detects_and_remaps_drift() -> Result<(), Box<dyn Error>> {
+    let source = "// Log of Currently Installed WeiDU Mods\r\n\
+      ~A/SETUP-A.TP2~ #0 #0 // Core: v1\r\n\
+      ~A/SETUP-A.TP2~ #0 #1 // Tweaks -> Fast: v1\r\n\
+      ~A/SETUP-A.TP2~ #0 #2 // Portraits: v1 // @label A_PORTRAITS\r\n\
+      ~A/SETUP-A.TP2~ #0 #3 // Extras: v1\r\n\
+      ~A/SETUP-A.TP2~ #0 #4 // Music: v1\r\n\
+      ~A/SETUP-A.TP2~ #0 #5 // Old Name: v1 // @label A_RENAMED\r\n\
+      ~B/B.TP2~ #0 #0 // Unlisted\r\n";
+    let log = tempfile::NamedTempFile::new()?;
+    fs::write(log.path(), source)?;
+    let listed = components(
+      "~A/SETUP-A.TP2~ #0 #0 // Core: v2\n\
+      ~A/SETUP-A.TP2~ #0 #2 // New Portraits: v2\n\
+      ~A/SETUP-A.TP2~ #0 #4 // Sounds: v2\n\
+      ~A/SETUP-A.TP2~ #0 #5 // New Name: v2 // @label A_RENAMED\n\
+      ~A/SETUP-A.TP2~ #0 #10 // Tweaks -> Fast: v2\n\
+      ~A/SETUP-A.TP2~ #0 #11 // Pictures: v2 // @label A_PORTRAITS\n",
+    )?;
+    let drifts = detect(&read_log(log.path())?, &listed);
+    assert_eq!(
+      drifts.iter().map(ToString::to_string).collect::<Vec<_>>(),
+      vec![
+        "log line 3: a/setup-a.tp2 #0 #1 // Tweaks is now component #10",
+        "log line 4: a/setup-a.tp2 #0 #2 // Portraits is now component #11",
+        "log line 5: a/setup-a.tp2 #0 #3 // Extras is no longer in SETUP-A.TP2",
+        r#"log line 6: component #4 of SETUP-A.TP2 is now "Sounds", check it is the same component"#,
+        r#"log line 7: component #5 of SETUP-A.TP2 is now "New Name""#,
+      ]
+    );
+    assert_eq!(
+      remap(&WeiduLog::parse(source, None)?, &drifts).to_string(),
+      "// Log of Currently Installed WeiDU Mods\r\n\
+      ~A/SETUP-A.TP2~ #0 #0 // Core: v1\r\n\
+      ~A/SETUP-A.TP2~ #0 #10 // Tweaks -> Fast: v1\r\n\
+      ~A/SETUP-A.TP2~ #0 #11 // Portraits: v1 // @label A_PORTRAITS\r\n\
+      // Removed from the mod: ~A/SETUP-A.TP2~ #0 #3 // Extras: v1\r\n\
+      // Review, component #4 is now \"Sounds\": ~A/SETUP-A.TP2~ #0 #4 // Music: v1\r\n\
+      ~A/SETUP-A.TP2~ #0 #5 // New Name: v2 // @label A_RENAMED\r\n\
+      ~B/B.TP2~ #0 #0 // Unlisted\r\n"
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn reads_labels_from_the_tp2() -> Result<(), Box<dyn Error>> {
+    let listed = with_labels(
+      components(
+        "~LABELS/LABELS.TP2~ #0 #0 // Core\n\
+        ~LABELS/LABELS.TP2~ #0 #10 // Tweak\n\
+        ~LABELS/LABELS.TP2~ #0 #11 // Extras\n",
+      )?,
+      Path::new("fixtures/tp2/labels.tp2"),
+    );
+    assert_eq!(
+      listed
+        .iter()
+        .map(|component| component.label.as_str())
+        .collect::<Vec<_>>(),
+      vec!["LABELS_CORE", "LABELS_TWEAK", ""]
+    );
+    Ok(())
+  }
+}
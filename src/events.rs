@@ -0,0 +1,394 @@
+use std::{
+  error::Error,
+  fs::OpenOptions,
+  io::Write,
+  path::{Path, PathBuf},
+  sync::{
+    Mutex,
+    mpsc::{SyncSender, TrySendError, sync_channel},
+  },
+  thread::{self, JoinHandle, sleep},
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde_derive::Serialize;
+
+use crate::{
+  config::state::State, run_log::Outcome, transcript::Stream, weidu::component::WeiduComponent,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum Event {
+  BatchStarted {
+    index: usize,
+  },
+  BatchFinished {
+    outcome: Outcome,
+  },
+  Output {
+    stream: Stream,
+    text: String,
+  },
+  State {
+    state: State,
+  },
+  Question {
+    question: String,
+  },
+  Answer {
+    text: String,
+  },
+  Download {
+    url: String,
+  },
+  Copy {
+    from: PathBuf,
+    to: PathBuf,
+  },
+  Summary {
+    game_directory: PathBuf,
+    installed: usize,
+    warnings: usize,
+    failed: usize,
+    skipped: usize,
+  },
+}
+
+#[derive(Debug, Serialize)]
+struct Envelope<'a> {
+  at_ms: u128,
+  components: &'a [WeiduComponent],
+  #[serde(flatten)]
+  event: &'a Event,
+}
+
+type Sink = Box<dyn Write + Send>;
+
+/// Lines a sink can fall behind by before it is dropped
+const SINK_BACKLOG: usize = 1024;
+
+/// How long the last events get to reach the sinks on exit
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A sink written from its own thread, so a slow reader never holds up the install
+struct Writer {
+  path: Option<PathBuf>,
+  lines: SyncSender<String>,
+  thread: JoinHandle<()>,
+}
+
+impl Writer {
+  fn spawn(path: Option<PathBuf>, mut sink: Sink) -> Self {
+    let (lines, receiver) = sync_channel::<String>(SINK_BACKLOG);
+    let name = format!("{path:?}");
+    let thread = thread::spawn(move || {
+      for line in receiver {
+        if let Err(err) = writeln!(sink, "{line}").and_then(|_| sink.flush()) {
+          log::debug!("Dropping event sink {name}, {err}");
+          return;
+        }
+      }
+    });
+    Self {
+      path,
+      lines,
+      thread,
+    }
+  }
+}
+
+/// Opens a file or fifo for writing, or connects to a unix socket
+fn open(path: &Path) -> Result<Sink, Box<dyn Error>> {
+  #[cfg(unix)]
+  {
+    use std::os::unix::{fs::FileTypeExt, net::UnixStream};
+    if path
+      .metadata()
+      .is_ok_and(|meta| meta.file_type().is_socket())
+    {
+      return Ok(Box::new(UnixStream::connect(path)?));
+    }
+  }
+  Ok(Box::new(
+    OpenOptions::new().create(true).append(true).open(path)?,
+  ))
+}
+
+/// Newline delimited json, one event per line, tagged with the batch being installed
+pub(crate) struct EventStream {
+  sinks: Mutex<Vec<Writer>>,
+  batch: Mutex<Vec<WeiduComponent>>,
+}
+
+static EVENTS: EventStream = EventStream::new();
+
+impl EventStream {
+  pub(crate) const fn new() -> Self {
+    Self {
+      sinks: Mutex::new(vec![]),
+      batch: Mutex::new(vec![]),
+    }
+  }
+
+  /// Adds a sink for a path, a path that is already open is left alone
+  pub(crate) fn open(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut sinks = self.sinks.lock().map_err(|err| err.to_string())?;
+    if sinks
+      .iter()
+      .any(|writer| writer.path.as_deref() == Some(path))
+    {
+      return Ok(());
+    }
+    log::info!("Writing events to {path:?}");
+    sinks.push(Writer::spawn(Some(path.to_path_buf()), open(path)?));
+    Ok(())
+  }
+
+  /// Adds a sink that is dropped once it can no longer be written to or falls behind
+  pub(crate) fn subscribe(&self, sink: Sink) {
+    if let Ok(mut sinks) = self.sinks.lock() {
+      sinks.push(Writer::spawn(None, sink));
+    }
+  }
+
+  /// Stops taking events and gives the sinks a moment to write what they were sent
+  pub(crate) fn close(&self) {
+    let threads: Vec<JoinHandle<()>> = match self.sinks.lock() {
+      Ok(mut sinks) => sinks.drain(..).map(|writer| writer.thread).collect(),
+      Err(_) => return,
+    };
+    let deadline = Instant::now() + CLOSE_TIMEOUT;
+    for thread in threads {
+      while !thread.is_finished() && Instant::now() < deadline {
+        sleep(Duration::from_millis(10));
+      }
+      if thread.is_finished() {
+        let _ = thread.join();
+      }
+    }
+  }
+
+  pub(crate) fn current_batch(&self) -> Vec<WeiduComponent> {
+    match self.batch.lock() {
+      Ok(batch) => batch.clone(),
+      Err(_) => vec![],
+    }
+  }
+
+  pub(crate) fn set_batch(&self, components: Vec<WeiduComponent>) {
+    if let Ok(mut batch) = self.batch.lock() {
+      *batch = components;
+    }
+  }
+
+  pub(crate) fn emit(&self, event: &Event) {
+    if self.sinks.lock().is_ok_and(|sinks| sinks.is_empty()) {
+      return;
+    }
+    let at_ms = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|duration| duration.as_millis())
+      .unwrap_or_default();
+    let line = match self.batch.lock() {
+      Ok(batch) => serde_json::to_string(&Envelope {
+        at_ms,
+        components: &batch,
+        event,
+      }),
+      Err(_) => return,
+    };
+    match line {
+      Ok(line) => {
+        if let Ok(mut sinks) = self.sinks.lock() {
+          sinks.retain(|writer| match writer.lines.try_send(line.clone()) {
+            Ok(_) => true,
+            Err(TrySendError::Full(_)) => {
+              log::warn!("Dropping event sink {:?}, it fell behind", writer.path);
+              false
+            },
+            Err(TrySendError::Disconnected(_)) => false,
+          });
+        }
+      },
+      Err(err) => log::warn!("Failed to serialize event {event:?}, {err}"),
+    }
+  }
+}
+
+pub(crate) fn init(path: &Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+  match path {
+    Some(path) => EVENTS.open(path),
+    None => Ok(()),
+  }
+}
+
+pub(crate) fn subscribe(sink: Sink) {
+  EVENTS.subscribe(sink);
+}
+
+pub(crate) fn close() {
+  EVENTS.close();
+}
+
+pub(crate) fn emit(event: Event) {
+  EVENTS.emit(&event);
+}
+
+pub(crate) fn set_batch(components: Vec<WeiduComponent>) {
+  EVENTS.set_batch(components);
+}
+
+pub(crate) fn current_batch() -> Vec<WeiduComponent> {
+  EVENTS.current_batch()
+}
+
+/// Counts batch outcomes and emits the summary event when dropped, so early returns are covered
+pub(crate) struct RunSummary {
+  game_directory: PathBuf,
+  installed: usize,
+  warnings: usize,
+  failed: usize,
+  skipped: usize,
+}
+
+impl RunSummary {
+  pub(crate) fn new(game_directory: &Path) -> Self {
+    Self {
+      game_directory: game_directory.to_path_buf(),
+      installed: 0,
+      warnings: 0,
+      failed: 0,
+      skipped: 0,
+    }
+  }
+
+  pub(crate) fn finish_batch(&mut self, outcome: Outcome) {
+    match outcome {
+      Outcome::Success => self.installed += 1,
+      Outcome::Warnings { .. } => self.warnings += 1,
+      Outcome::Failed { .. } => self.failed += 1,
+      Outcome::Skipped => self.skipped += 1,
+      Outcome::Running => {},
+    }
+    emit(Event::BatchFinished { outcome });
+    set_batch(vec![]);
+  }
+}
+
+impl Drop for RunSummary {
+  fn drop(&mut self) {
+    emit(Event::Summary {
+      game_directory: self.game_directory.clone(),
+      installed: self.installed,
+      warnings: self.warnings,
+      failed: self.failed,
+      skipped: self.skipped,
+    });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::{error::Error, fs};
+
+  #[test]
+  fn writes_events_with_component_identity() -> Result<(), Box<dyn Error>> {
+    let file = tempfile::NamedTempFile::new()?;
+    let events = EventStream::new();
+    events.open(file.path())?;
+    events.open(file.path())?;
+    events.emit(&Event::Download {
+      url: "https://github.com/Gibberlings3/BG1NPC".to_string(),
+    });
+    events.set_batch(vec![WeiduComponent {
+      tp_file: "SETUP-BG1NPC.TP2".to_string(),
+      name: "bg1npc".to_string(),
+      lang: "0".to_string(),
+      component: "0".to_string(),
+      component_name: "Required Modifications".to_string(),
+      ..Default::default()
+    }]);
+    events.emit(&Event::State {
+      state: State::RequiresInput {
+        question: "Do you want to install?".to_string(),
+      },
+    });
+
+    events.close();
+    let lines: Vec<serde_json::Value> = fs::read_to_string(file.path())?
+      .lines()
+      .map(serde_json::from_str)
+      .collect::<Result<_, _>>()?;
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["event"], "download");
+    assert_eq!(lines[0]["components"], serde_json::json!([]));
+    assert_eq!(lines[1]["event"], "state");
+    assert_eq!(
+      lines[1]["state"],
+      serde_json::json!({"RequiresInput": {"question": "Do you want to install?"}})
+    );
+    assert_eq!(lines[1]["components"][0]["tp_file"], "SETUP-BG1NPC.TP2");
+    assert_eq!(lines[1]["components"][0]["component"], "0");
+    Ok(())
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn writes_to_unix_socket() -> Result<(), Box<dyn Error>> {
+    use std::{
+      io::{BufRead, BufReader},
+      os::unix::net::UnixListener,
+    };
+    let directory = tempfile::tempdir()?;
+    let path = directory.path().join("events.sock");
+    let listener = UnixListener::bind(&path)?;
+    let events = EventStream::new();
+    events.open(&path)?;
+    let (stream, _) = listener.accept()?;
+    events.emit(&Event::Answer {
+      text: "y\n".to_string(),
+    });
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let event: serde_json::Value = serde_json::from_str(&line)?;
+    assert_eq!(event["event"], "answer");
+    assert_eq!(event["text"], "y\n");
+    Ok(())
+  }
+
+  /// Blocks every write until the test lets go of it
+  struct Stalled(std::sync::mpsc::Receiver<()>);
+
+  impl Write for Stalled {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+      self
+        .0
+        .recv()
+        .map(|_| buf.len())
+        .map_err(|err| std::io::Error::other(err.to_string()))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn drops_sinks_that_fall_behind() -> Result<(), Box<dyn Error>> {
+    let (release, stalled) = std::sync::mpsc::channel();
+    let events = EventStream::new();
+    events.subscribe(Box::new(Stalled(stalled)));
+    for _ in 0..SINK_BACKLOG + 2 {
+      events.emit(&Event::Answer {
+        text: "y\n".to_string(),
+      });
+    }
+    assert_eq!(events.sinks.lock().map_err(|err| err.to_string())?.len(), 0);
+    drop(release);
+    Ok(())
+  }
+}
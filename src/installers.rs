@@ -1,17 +1,42 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::ffi::OsString;
 use std::path::PathBuf;
 use std::{error::Error, path::Path, sync::Arc};
 
-use crate::config::args::{Eet, InstallOptions, Normal};
+use crate::config::args::{BatchOptions, Eet, InstallOptions, Normal};
+use crate::config::installed_order::InstalledOrder;
 use crate::config::options::Options;
 use crate::config::parser_config::ParserConfig;
+use crate::config::weidu_log_options::WeiduLogOptions;
+use crate::console;
+use crate::control::CONTROL;
+use crate::drift::check_drift;
+use crate::events::{self, Event, RunSummary};
+use crate::internal_log::InternalLog;
+use crate::lock::GameLock;
+use crate::run_log::{Outcome, RunLog};
 use crate::runner::{self, WeiduExitStatus};
-use crate::utils::{copy_folder, mod_folder_present_in_game_directory};
+use crate::signals;
+use crate::transcript::TranscriptRecorder;
+use crate::utils::{copy_folder, create_folder, mod_folder_present_in_game_directory};
 use crate::utils::{delete_folder, get_last_installed, search_or_download};
+use crate::validate::validate;
 use crate::weidu::batched_components::WeiduBatchedComponents;
+use crate::weidu::component::WeiduComponent;
 use crate::weidu::install_block::WeiduInstallBlock;
 use crate::weidu::install_order::WeiduBatchedInstallOrder;
+use crate::weidu::labels::Labels;
+use crate::weidu::verify::{check_batch, installed, installed_prefix, read_log, verify_entries};
+
+/// Events, the console and Ctrl-C handling, set up once per install
+fn start_install(install_options: &InstallOptions) -> Result<(), Box<dyn Error>> {
+  events::init(&install_options.events)?;
+  if !CONTROL.is_remote() {
+    console::start(&CONTROL);
+    signals::install_handler(&CONTROL)?;
+  }
+  Ok(())
+}
 
 pub(crate) fn normal_install(
   command: &Normal,
@@ -19,16 +44,12 @@ pub(crate) fn normal_install(
   mod_folder_cache: &mut HashMap<OsString, PathBuf>,
 ) -> Result<(), Box<dyn Error>> {
   log::info!("Beginning normal install process");
-  let game_directory = if let Some(new_directory) = &command.generate_directory {
-    copy_folder(
-      &command.game_directory,
-      new_directory,
-      command.install_options.casefold,
-    )?;
-    new_directory.clone()
-  } else {
-    command.game_directory.clone()
-  };
+  start_install(&command.install_options)?;
+  let (game_directory, _lock) = lock_game_directory(
+    &command.game_directory,
+    command.generate_directory.as_ref(),
+    command.install_options.casefold,
+  )?;
 
   install(
     &command.log_file,
@@ -41,22 +62,44 @@ pub(crate) fn normal_install(
   )
 }
 
+/// A normal install into a game directory the caller already holds the lock for
+pub(crate) fn install_locked(
+  command: &Normal,
+  _lock: &GameLock,
+  parser_config: Arc<ParserConfig>,
+  mod_folder_cache: &mut HashMap<OsString, PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+  log::info!("Beginning normal install process");
+  start_install(&command.install_options)?;
+  install(
+    &command.log_file,
+    &command.game_directory,
+    &command.options,
+    &command.install_options,
+    parser_config,
+    None,
+    mod_folder_cache,
+  )
+}
+
 pub(crate) fn eet_install(
   command: &Eet,
   parser_config: Arc<ParserConfig>,
   mod_folder_cache: &mut HashMap<OsString, PathBuf>,
 ) -> Result<(), Box<dyn Error>> {
   log::info!("Beginning pre eet install process");
-  let pre_eet_game_directory = if let Some(new_directory) = &command.new_pre_eet_dir {
-    copy_folder(
-      &command.bg1_game_directory,
-      new_directory,
-      command.install_options.casefold,
-    )?;
-    new_directory.clone()
-  } else {
-    command.bg1_game_directory.clone()
-  };
+  start_install(&command.install_options)?;
+  // Both are locked before anything is installed, so a busy eet directory is found up front
+  let (pre_eet_game_directory, _pre_eet_lock) = lock_game_directory(
+    &command.bg1_game_directory,
+    command.new_pre_eet_dir.as_ref(),
+    command.install_options.casefold,
+  )?;
+  let (game_directory, _eet_lock) = lock_game_directory(
+    &command.bg2_game_directory,
+    command.new_eet_dir.as_ref(),
+    command.install_options.casefold,
+  )?;
 
   install(
     &command.bg1_log_file,
@@ -69,16 +112,6 @@ pub(crate) fn eet_install(
   )?;
 
   log::info!("Beginning eet install process");
-  let game_directory = if let Some(new_directory) = &command.new_eet_dir {
-    copy_folder(
-      &command.bg2_game_directory,
-      new_directory,
-      command.install_options.casefold,
-    )?;
-    new_directory.clone()
-  } else {
-    command.bg2_game_directory.clone()
-  };
   install(
     &command.bg2_log_file,
     &game_directory,
@@ -90,6 +123,29 @@ pub(crate) fn eet_install(
   )
 }
 
+/// Locks the game directory, or when installing into a copy of it, locks the copy before copying.
+/// The source is only locked while it is being copied
+fn lock_game_directory(
+  game_directory: &Path,
+  new_directory: Option<&PathBuf>,
+  casefold: bool,
+) -> Result<(PathBuf, GameLock), Box<dyn Error>> {
+  let new_directory = match new_directory {
+    Some(new_directory) => new_directory,
+    None => {
+      return Ok((
+        game_directory.to_path_buf(),
+        GameLock::acquire(game_directory)?,
+      ));
+    },
+  };
+  let _source_lock = GameLock::acquire(game_directory)?;
+  create_folder(new_directory, casefold)?;
+  let lock = GameLock::acquire(new_directory)?;
+  copy_folder(game_directory, new_directory, casefold)?;
+  Ok((new_directory.clone(), lock))
+}
+
 fn install(
   log_file_path: &Path,
   game_directory: &Path,
@@ -99,19 +155,121 @@ fn install(
   pre_eet_game_directory: Option<&PathBuf>,
   mod_folder_cache: &mut HashMap<OsString, PathBuf>,
 ) -> Result<(), Box<dyn Error>> {
-  let mut components_to_be_installed: WeiduBatchedComponents =
-    WeiduBatchedComponents::try_from(log_file_path.to_path_buf())?;
-  if install_options.skip_installed {
-    components_to_be_installed.remove_existing(install_options.strict_matching, game_directory)?;
+  let mut parser_languages = vec![install_options.language.clone()];
+  parser_languages.extend(install_options.parser_languages.iter().cloned());
+  let parser_config = Arc::new(parser_config.with_languages(&parser_languages));
+  if install_options.check_drift {
+    check_drift(log_file_path, options)?;
+  }
+  if install_options.validate {
+    validate(log_file_path, game_directory, options, install_options)?;
+  }
+  // Labelled components already in the game directory are compared by the number weidu.log has
+  let mut labels = Labels::default();
+  let target = labels.resolve_entries(
+    &read_log(log_file_path)?,
+    game_directory,
+    &options.weidu_binary,
+  );
+  let mut components_to_be_installed = WeiduBatchedComponents::from(
+    target
+      .iter()
+      .map(|entry| entry.component.clone())
+      .collect::<Vec<_>>(),
+  );
+  match install_options.installed_order {
+    _ if !install_options.skip_installed => {},
+    InstalledOrder::Any => {
+      components_to_be_installed.remove_existing(install_options.strict_matching, game_directory)?
+    },
+    order => {
+      let prefix = installed_prefix(
+        &target,
+        &installed(game_directory)?,
+        install_options.strict_matching,
+      );
+      if !prefix.diverged.is_empty() {
+        log::warn!("{prefix}");
+        if order == InstalledOrder::Prefix {
+          return Err(
+            format!(
+              "{game_directory:?} was installed in a different order from {log_file_path:?}, \
+              use --installed-order reinstall to uninstall the {} components after the divergence and install them again",
+              prefix.diverged.len()
+            )
+            .into(),
+          );
+        }
+        // Anything installed past the end of the target log is not installed again
+        let past_end = prefix
+          .diverged
+          .iter()
+          .skip(target.len() - prefix.matched)
+          .map(|entry| entry.component.to_string())
+          .collect::<Vec<_>>();
+        if !past_end.is_empty()
+          && !install_options.uninstall_confirmed
+          && !confirm_uninstall(&past_end, install_options.tick)?
+        {
+          return Err(
+            format!(
+              "Not uninstalling {} components of {game_directory:?} that are not in {log_file_path:?}",
+              past_end.len()
+            )
+            .into(),
+          );
+        }
+        let diverged: Vec<WeiduComponent> = prefix
+          .diverged
+          .iter()
+          .map(|entry| entry.component.clone())
+          .collect();
+        uninstall(
+          &diverged,
+          game_directory,
+          options,
+          install_options,
+          parser_config.clone(),
+        )?;
+      }
+      log::info!(
+        "Number of mods found: {}, Number of mods already installed in order: {}",
+        components_to_be_installed.len(),
+        prefix.matched
+      );
+      components_to_be_installed.remove_first(prefix.matched);
+    },
   }
   let mods_to_be_installed =
     WeiduBatchedInstallOrder::new(components_to_be_installed, &install_options.batch)?;
-  for components in mods_to_be_installed.into_iter() {
+  let mut run_log = RunLog::create(install_options, log_file_path)?;
+  let mut summary = RunSummary::new(game_directory);
+  // Failed batches put their components back on the front, one at a time, marked as isolated
+  let mut queue: VecDeque<(WeiduBatchedComponents, bool)> = mods_to_be_installed
+    .into_iter()
+    .map(|components| (components, false))
+    .collect();
+  // Isolated components that failed, the rest of their batch is installed before stopping
+  let mut offenders: Vec<String> = vec![];
+  let mut started = 0;
+  while let Some((components, isolated)) = queue.pop_front() {
+    if !isolated && !offenders.is_empty() {
+      return Err(offenders_error(&offenders));
+    }
+    let components = &components;
+    let index = started;
+    started += 1;
     let first_mod = if let Some(weidu_mod) = components.first() {
       weidu_mod
     } else {
       continue;
     };
+    if let Err(err) = CONTROL.wait_while_paused(install_options.tick) {
+      log_resume_point(first_mod, log_file_path, install_options);
+      return Err(err);
+    }
+    events::set_batch(components.into_iter().cloned().collect());
+    events::emit(Event::BatchStarted { index });
     let mod_folder =
       if let Some(entry) = mod_folder_cache.get::<OsString>(&components.log_file_name().into()) {
         entry.to_path_buf()
@@ -127,9 +285,17 @@ fn install(
           Err(err) if install_options.never_abort => {
             log::error!("{:?}", err);
             log::info!("failed but never abort set, so continuing");
+            summary.finish_batch(Outcome::Failed {
+              details: err.to_string(),
+            });
             continue;
           },
-          Err(err) => return Err(err),
+          Err(err) => {
+            summary.finish_batch(Outcome::Failed {
+              details: err.to_string(),
+            });
+            return Err(err);
+          },
         };
         mod_folder_cache.insert(first_mod.tp_file.clone().into(), entry.clone());
         entry
@@ -148,7 +314,10 @@ fn install(
         game_directory.join(&first_mod.name)
       );
       copy_folder(mod_folder, game_directory.join(&first_mod.name), false)?;
+      labels.forget(first_mod);
     }
+    let components = &labels.resolve(components, game_directory, &options.weidu_binary)?;
+    let first_mod = components.first().unwrap_or(first_mod);
     log::info!("Installing mod {:?}", components);
     let bg1_game_directory = if first_mod
       .component_name
@@ -164,32 +333,88 @@ fn install(
       &install_options.language,
       &install_options.generic_weidu_args,
     );
-    match runner::spawn(
+    let log_path = run_log.start(
+      components,
+      WeiduLogOptions::new(options.weidu_log_mode.clone()).log_path(&components.log_file_name()),
+    )?;
+    let log = InternalLog::new()
+      .with_tail_lines(install_options.log_tail_lines)
+      .with_file(&log_path)?;
+    let log = match &install_options.record_transcript {
+      Some(directory) => log.with_transcript(TranscriptRecorder::create(
+        &directory.join(Path::new(&components.log_file_name()).with_extension("ndjson")),
+        install_options,
+        weidu_args,
+      )?),
+      None => log,
+    };
+    let installed_before = installed(game_directory)?.len();
+    let result = runner::spawn(
       game_directory,
       parser_config.clone(),
       options,
       install_options,
       weidu_args,
       bg1_game_directory,
-    ) {
-      Ok(WeiduExitStatus::Success)
-        if install_options.check_last_installed && !install_options.never_abort =>
-      {
-        if let Ok(last_installed) = get_last_installed(game_directory) {
-          if last_installed.ne(first_mod) {
-            return Err(format!(
-                            "Last installed {last_installed:?} does not match component installed: {components:?}"
-                        )
-                        .into());
-          }
-          log::info!("Installed mod {:?}", last_installed);
-        } else {
-          log::warn!("Could not open weidu log, to validate if last component was installed");
+      log.clone(),
+    );
+    // A batch only counts as installed once weidu.log shows it was
+    let result = result.and_then(|status| {
+      check_installed(
+        &status,
+        components,
+        installed_before,
+        game_directory,
+        install_options,
+        &log,
+        &log_path,
+      )?;
+      Ok(status)
+    });
+    run_log.finish(Outcome::from(&result));
+    summary.finish_batch(Outcome::from(&result));
+    if result.is_err() {
+      log::info!("Weidu output for {components:?} was written to {log_path:?}");
+    }
+    if install_options.batch.isolate_failures
+      && components.len() > 1
+      && matches!(result, Err(_) | Ok(WeiduExitStatus::Warnings(_)))
+      && !CONTROL.is_aborted()
+    {
+      log::warn!(
+        "Batch {components:?} did not install cleanly, installing it one component at a time"
+      );
+      let singles: Vec<WeiduBatchedComponents> = isolate(
+        components,
+        installed_before,
+        game_directory,
+        options,
+        install_options,
+        parser_config.clone(),
+      )?
+      .into_iter()
+      .collect();
+      if !singles.is_empty() {
+        for single in singles.into_iter().rev() {
+          queue.push_front((single, true));
         }
-      },
+        continue;
+      }
+      log::warn!(
+        "Every component of {components:?} was installed, use --uninstall-failed-batch to find the one at fault"
+      );
+    }
+    match result {
       Ok(WeiduExitStatus::Success) => {
         log::info!("Installed mod {:?}", components);
       },
+      Ok(WeiduExitStatus::Skipped) => {
+        log::warn!("Skipped mod {components:?}, it may be partially installed");
+      },
+      Ok(WeiduExitStatus::Warnings(msg)) if install_options.abort_on_warnings && isolated => {
+        log::error!("Installed {first_mod} with warnings: \n{msg}\n");
+        offenders.push(format!("{first_mod}: {msg}"));
+      },
       Ok(WeiduExitStatus::Warnings(msg)) if install_options.abort_on_warnings => {
         return Err(
           format!("Installed mod {components:?} with warnings: \n{msg}\n, stopping").into(),
@@ -202,10 +427,202 @@ fn install(
         log::error!("{:?}", err);
         log::info!("failed but never abort set, so continuing");
       },
+      Err(err) if isolated && !CONTROL.is_aborted() => {
+        log::error!("Failed to install {first_mod}: {err}");
+        offenders.push(format!("{first_mod}: {err}"));
+      },
       Err(err) => {
+        if CONTROL.is_stopping() {
+          log_resume_point(first_mod, log_file_path, install_options);
+        }
         return Err(err);
       },
     }
   }
+  if !offenders.is_empty() {
+    return Err(offenders_error(&offenders));
+  }
+  if install_options.verify {
+    verify_entries(
+      &labels.resolve_entries(
+        &read_log(log_file_path)?,
+        game_directory,
+        &options.weidu_binary,
+      ),
+      log_file_path,
+      game_directory,
+      install_options.strict_matching,
+    )?;
+  }
   Ok(())
 }
+
+/// Checks weidu.log after weidu reported success or warnings, so the outcome of a batch
+/// includes what weidu actually wrote there
+fn check_installed(
+  status: &WeiduExitStatus,
+  components: &WeiduBatchedComponents,
+  installed_before: usize,
+  game_directory: &Path,
+  install_options: &InstallOptions,
+  log: &InternalLog,
+  log_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+  if matches!(
+    status,
+    WeiduExitStatus::Success | WeiduExitStatus::Warnings(_)
+  ) {
+    let divergences = check_batch(
+      components,
+      installed_before,
+      game_directory,
+      install_options.strict_matching,
+      &log.skip_lines(),
+    )?;
+    for divergence in &divergences {
+      log::warn!("{divergence}");
+    }
+    if !divergences.is_empty() && install_options.abort_on_warnings {
+      return Err(
+        format!("Weidu did not install all of {components:?}, see {log_path:?}, stopping").into(),
+      );
+    }
+  }
+  if matches!(status, WeiduExitStatus::Success)
+    && install_options.check_last_installed
+    && !install_options.never_abort
+    && let Some(first_mod) = components.first()
+  {
+    match get_last_installed(game_directory) {
+      Ok(last_installed) if last_installed.ne(first_mod) => {
+        return Err(
+          format!(
+            "Last installed {last_installed:?} does not match component installed: {components:?}"
+          )
+          .into(),
+        );
+      },
+      Ok(last_installed) => log::info!("Installed mod {:?}", last_installed),
+      Err(_) => {
+        log::warn!("Could not open weidu log, to validate if last component was installed")
+      },
+    }
+  }
+  Ok(())
+}
+
+/// Asks through the console or the serve api, as stdin belongs to the console once it is running
+fn confirm_uninstall(components: &[String], tick: u64) -> Result<bool, Box<dyn Error>> {
+  let question = format!(
+    "Uninstall these components, which are past the end of the target log and will not be installed again? [y/N]\n{}",
+    components.join("\n")
+  );
+  log::warn!("{question}");
+  Ok(
+    CONTROL
+      .user_input(&question, tick)?
+      .trim()
+      .eq_ignore_ascii_case("y"),
+  )
+}
+
+fn offenders_error(offenders: &[String]) -> Box<dyn Error> {
+  format!(
+    "Installing a failed batch one component at a time found:\n{}",
+    offenders.join("\n")
+  )
+  .into()
+}
+
+/// Uninstalls what a failed batch added to weidu.log when asked to, then splits the batch into
+/// single components. Without the uninstall only the components weidu did not install are retried
+fn isolate(
+  components: &WeiduBatchedComponents,
+  installed_before: usize,
+  game_directory: &Path,
+  options: &Options,
+  install_options: &InstallOptions,
+  parser_config: Arc<ParserConfig>,
+) -> Result<WeiduBatchedInstallOrder, Box<dyn Error>> {
+  let appended: Vec<WeiduComponent> = installed(game_directory)?
+    .into_iter()
+    .skip(installed_before)
+    .map(|entry| entry.component)
+    .filter(|component| components.into_iter().any(|wanted| wanted == component))
+    .collect();
+  let mut retry = components.clone();
+  if install_options.batch.uninstall_failed_batch && !appended.is_empty() {
+    uninstall(
+      &appended,
+      game_directory,
+      options,
+      install_options,
+      parser_config,
+    )?;
+  } else {
+    retry.retain(|component| !appended.contains(component));
+  }
+  WeiduBatchedInstallOrder::new(retry, &BatchOptions::default())
+}
+
+/// Uninstalls components given in install order, the last installed first,
+/// with one weidu run per group of consecutive components from the same mod
+pub(crate) fn uninstall(
+  components: &[WeiduComponent],
+  game_directory: &Path,
+  options: &Options,
+  install_options: &InstallOptions,
+  parser_config: Arc<ParserConfig>,
+) -> Result<(), Box<dyn Error>> {
+  let mut groups: Vec<WeiduBatchedComponents> = vec![];
+  for component in components {
+    match groups.last_mut() {
+      Some(group)
+        if group
+          .last()
+          .is_some_and(|last| last.full_component_name() == component.full_component_name()) =>
+      {
+        group.push(component.clone())
+      },
+      _ => groups.push(vec![component.clone()].into()),
+    }
+  }
+  for group in groups.iter().rev() {
+    log::info!("Uninstalling {group:?}");
+    let weidu_args = group.generate_weidu_uninstall_args(
+      options.weidu_log_mode.clone(),
+      &install_options.language,
+      &install_options.generic_weidu_args,
+    );
+    let log = InternalLog::new().with_tail_lines(install_options.log_tail_lines);
+    match runner::spawn(
+      game_directory,
+      parser_config.clone(),
+      options,
+      install_options,
+      &weidu_args,
+      None,
+      log,
+    ) {
+      Ok(WeiduExitStatus::Skipped) => {
+        return Err(format!("Uninstall of {group:?} was skipped").into());
+      },
+      Ok(_) => {},
+      Err(err) => return Err(format!("Failed to uninstall {group:?}, {err}").into()),
+    }
+  }
+  Ok(())
+}
+
+fn log_resume_point(
+  component: &WeiduComponent,
+  log_file_path: &Path,
+  install_options: &InstallOptions,
+) {
+  log::warn!("Resume point is {component}, from {log_file_path:?}");
+  if install_options.skip_installed {
+    log::warn!("Run the same command again to resume from there");
+  } else {
+    log::warn!("Skip installed is off, turn it back on and run the same command again to resume");
+  }
+}
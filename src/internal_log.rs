@@ -1,23 +1,208 @@
-use std::sync::{Arc, RwLock};
+use std::{
+  collections::VecDeque,
+  error::Error,
+  fs::File,
+  io::Write,
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex, RwLock},
+  time::Instant,
+};
 
+use crate::{
+  config::state::State,
+  events::{self, Event},
+  transcript::{Stream, TranscriptRecorder},
+};
+
+pub(crate) const DEFAULT_TAIL_LINES: usize = 50;
+// Weidu explains components it did not install with lines containing these
+const SKIP_MARKERS: [&str; 2] = ["skipping", "not installed"];
+
+// Only the most recent lines are kept in memory, mods like SCS or EET core print hundreds of
+// megabytes, the full output goes to the file set by with_file
 #[derive(Debug, Clone)]
-pub(crate) struct InternalLog(Arc<RwLock<String>>);
+pub(crate) struct InternalLog {
+  tail: Arc<RwLock<VecDeque<String>>>,
+  tail_lines: usize,
+  skips: Arc<RwLock<Vec<String>>>,
+  transcript: Option<TranscriptRecorder>,
+  file: Option<(PathBuf, Arc<Mutex<File>>)>,
+  start: Instant,
+}
 
 impl InternalLog {
   pub(crate) fn new() -> Self {
-    Self(Arc::new(RwLock::new(String::new())))
+    Self {
+      tail: Arc::new(RwLock::new(VecDeque::with_capacity(DEFAULT_TAIL_LINES))),
+      tail_lines: DEFAULT_TAIL_LINES,
+      skips: Arc::new(RwLock::new(vec![])),
+      transcript: None,
+      file: None,
+      start: Instant::now(),
+    }
+  }
+  pub(crate) fn with_tail_lines(mut self, tail_lines: usize) -> Self {
+    self.tail = Arc::new(RwLock::new(VecDeque::with_capacity(tail_lines)));
+    self.tail_lines = tail_lines;
+    self
+  }
+  pub(crate) fn with_transcript(mut self, transcript: TranscriptRecorder) -> Self {
+    self.transcript = Some(transcript);
+    self
+  }
+  /// Also write every line to a file, prefixed with the seconds since weidu started
+  pub(crate) fn with_file(mut self, path: &Path) -> Result<Self, Box<dyn Error>> {
+    self.file = Some((
+      path.to_path_buf(),
+      Arc::new(Mutex::new(File::create(path)?)),
+    ));
+    Ok(self)
+  }
+  fn write_file(&self, label: &str, text: &str) {
+    if let Some((_, file)) = &self.file
+      && let Ok(mut file) = file.lock()
+    {
+      let elapsed = self.start.elapsed().as_secs_f64();
+      let newline = if text.ends_with('\n') { "" } else { "\n" };
+      if let Err(err) = write!(file, "[{elapsed:>9.3}s {label}] {text}{newline}") {
+        log::warn!("Failed to write log file, {err}");
+      }
+    }
   }
-  pub(crate) fn write(&self, line: &str) {
-    if !line.is_empty()
-      && let Ok(mut writer) = self.0.write()
+  pub(crate) fn write(&self, stream: Stream, line: &str) {
+    if line.is_empty() {
+      return;
+    }
+    if let Some(transcript) = &self.transcript {
+      transcript.output(stream, line);
+    }
+    events::emit(Event::Output {
+      stream,
+      text: line.to_string(),
+    });
+    self.write_file(&stream.to_string(), line);
+    let line = line.trim_end();
+    let lowercase = line.to_lowercase();
+    if SKIP_MARKERS.iter().any(|marker| lowercase.contains(marker))
+      && let Ok(mut skips) = self.skips.write()
     {
-      writer.push_str(line);
+      skips.push(line.trim().to_string());
+    }
+    if line.is_empty() || self.tail_lines == 0 {
+      return;
+    }
+    if let Ok(mut tail) = self.tail.write() {
+      if tail.len() == self.tail_lines {
+        tail.pop_front();
+      }
+      tail.push_back(line.to_string());
+    }
+  }
+  pub(crate) fn input(&self, text: &str) {
+    if let Some(transcript) = &self.transcript {
+      transcript.input(text);
     }
+    events::emit(Event::Answer {
+      text: text.to_string(),
+    });
+    self.write_file("input", text);
   }
-  pub(crate) fn read(&self) -> String {
-    if let Ok(log) = self.0.read() {
-      return log.clone();
+  pub(crate) fn state(&self, state: &State) {
+    if let Some(transcript) = &self.transcript {
+      transcript.state(state);
     }
-    String::new()
+    events::emit(Event::State {
+      state: state.clone(),
+    });
+    if let State::RequiresInput { question } = state {
+      events::emit(Event::Question {
+        question: question.clone(),
+      });
+    }
+  }
+  pub(crate) fn tail(&self, lines: usize) -> Vec<String> {
+    match self.tail.read() {
+      Ok(tail) => tail
+        .iter()
+        .skip(tail.len().saturating_sub(lines))
+        .cloned()
+        .collect(),
+      Err(_) => vec![],
+    }
+  }
+  /// Every line where weidu said it skipped or did not install something
+  pub(crate) fn skip_lines(&self) -> Vec<String> {
+    match self.skips.read() {
+      Ok(skips) => skips.clone(),
+      Err(_) => vec![],
+    }
+  }
+  /// The last non blank lines of output, followed by where to find the rest
+  pub(crate) fn report(&self) -> String {
+    let mut out = String::new();
+    if let Ok(tail) = self.tail.read() {
+      out += &format!("last {} lines of weidu output:\n", tail.len());
+      for line in tail.iter() {
+        out += line;
+        out += "\n";
+      }
+    }
+    if let Some((path, _)) = &self.file {
+      out += &format!("full weidu output: {path:?}");
+    }
+    out
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::{error::Error, fs};
+
+  #[test]
+  fn keeps_recent_lines_and_spills_to_file() -> Result<(), Box<dyn Error>> {
+    let file = tempfile::NamedTempFile::new()?;
+    let log = InternalLog::new()
+      .with_tail_lines(2)
+      .with_file(file.path())?;
+    for line in ["Installing [Portraits]\n", "\n", "Copying 3 files\n"] {
+      log.write(Stream::Stdout, line);
+    }
+    log.input("y\n");
+    log.write(Stream::Stderr, "SUCCESSFULLY INSTALLED      Portraits\n");
+
+    assert_eq!(
+      log.report(),
+      format!(
+        "last 2 lines of weidu output:\nCopying 3 files\nSUCCESSFULLY INSTALLED      Portraits\nfull weidu output: {:?}",
+        file.path()
+      )
+    );
+    let written: Vec<String> = fs::read_to_string(file.path())?
+      .lines()
+      .map(|line| {
+        line
+          .split_once("] ")
+          .map(|(_, text)| text)
+          .unwrap_or(line)
+          .to_string()
+      })
+      .collect();
+    assert_eq!(
+      written,
+      vec![
+        "Installing [Portraits]",
+        "",
+        "Copying 3 files",
+        "y",
+        "SUCCESSFULLY INSTALLED      Portraits"
+      ]
+    );
+    assert_eq!(log.skip_lines(), Vec::<String>::new());
+    log.write(Stream::Stdout, "SKIPPING: [Portraits] requires BG2\n");
+    assert_eq!(log.skip_lines(), vec!["SKIPPING: [Portraits] requires BG2"]);
+    Ok(())
   }
 }
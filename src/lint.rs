@@ -0,0 +1,446 @@
+use std::{
+  collections::HashMap,
+  error::Error,
+  fmt::Display,
+  fs,
+  path::{Path, PathBuf},
+};
+
+use crate::{
+  config::{
+    args::{FmtCommand, LintCommand},
+    options::Options,
+  },
+  scan::components::read_components,
+  utils::search_mod_folders,
+  weidu::{
+    component::WeiduComponent,
+    log_file::{
+      LogComponent, LogLine, ParseError, WEIDU_LOG_HEADER, WeiduLog, parse_log, read_source,
+      tp2_path,
+    },
+    tp2::{Tp2, find_tp2},
+    verify::LogEntry,
+  },
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Lint {
+  Unparseable(ParseError),
+  Duplicate {
+    line: usize,
+    first_line: usize,
+    component: WeiduComponent,
+  },
+  MixedSeparator {
+    line: usize,
+    separator: char,
+    first_line: usize,
+  },
+  ModNotFound {
+    line: usize,
+    component: WeiduComponent,
+  },
+  UnknownComponent {
+    line: usize,
+    component: WeiduComponent,
+  },
+  UnknownLanguage {
+    line: usize,
+    component: WeiduComponent,
+    languages: usize,
+  },
+  VersionMismatch {
+    line: usize,
+    component: WeiduComponent,
+    version: String,
+  },
+}
+
+impl Display for Lint {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Lint::Unparseable(error) => write!(f, "{error}"),
+      Lint::Duplicate {
+        line,
+        first_line,
+        component,
+      } => write!(
+        f,
+        "line {line}: {component} is already on line {first_line}"
+      ),
+      Lint::MixedSeparator {
+        line,
+        separator,
+        first_line,
+      } => write!(
+        f,
+        "line {line}: uses {separator:?} as the path separator, line {first_line} uses the other one"
+      ),
+      Lint::ModNotFound { line, component } => write!(
+        f,
+        "line {line}: {} is not in the mod directories",
+        component.full_component_name()
+      ),
+      Lint::UnknownComponent { line, component } => write!(
+        f,
+        "line {line}: {} has no component #{}",
+        component.tp_file, component.component
+      ),
+      Lint::UnknownLanguage {
+        line,
+        component,
+        languages,
+      } => write!(
+        f,
+        "line {line}: {} has no language #{}, it has {languages}",
+        component.tp_file, component.lang
+      ),
+      Lint::VersionMismatch {
+        line,
+        component,
+        version,
+      } => write!(
+        f,
+        "line {line}: {component} is version {:?}, the mod is {version:?}",
+        component.version
+      ),
+    }
+  }
+}
+
+/// What the mod directories have for a mod in the log
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct ModListing {
+  pub(crate) languages: usize,
+  /// In each language of the mod the log uses
+  pub(crate) components: Vec<WeiduComponent>,
+}
+
+impl ModListing {
+  /// A tp2 without LANGUAGE is installed as language 0
+  fn has_language(&self, lang: &str) -> bool {
+    lang
+      .parse::<usize>()
+      .is_ok_and(|lang| lang < self.languages.max(1))
+  }
+
+  fn find(&self, component: &WeiduComponent) -> Option<&WeiduComponent> {
+    self
+      .components
+      .iter()
+      .find(|listed| listed.component == component.component && listed.lang == component.lang)
+  }
+}
+
+fn separator(text: &str) -> Option<char> {
+  let path = tp2_path(text)?;
+  path
+    .contains('\\')
+    .then_some('\\')
+    .or(path.contains('/').then_some('/'))
+}
+
+/// Mods missing from the listings were not found in the mod directories
+pub(crate) fn lint(source: &str, listings: &HashMap<String, ModListing>) -> Vec<Lint> {
+  let mut lints = vec![];
+  let mut seen: Vec<(usize, WeiduComponent)> = vec![];
+  let mut first_separator: Option<(usize, char)> = None;
+  let mut not_found: Vec<String> = vec![];
+  let lines: Vec<&str> = source.lines().collect();
+  for parsed in parse_log(source, None) {
+    let LogEntry { line, component } = match parsed {
+      Ok(entry) => entry,
+      Err(error) => {
+        lints.push(Lint::Unparseable(error));
+        continue;
+      },
+    };
+    if let Some(separator) = separator(lines[line - 1]) {
+      match first_separator {
+        Some((first_line, first)) if first != separator => lints.push(Lint::MixedSeparator {
+          line,
+          separator,
+          first_line,
+        }),
+        None => first_separator = Some((line, separator)),
+        _ => {},
+      }
+    }
+    if let Some((first_line, _)) = seen.iter().find(|(_, other)| *other == component) {
+      lints.push(Lint::Duplicate {
+        line,
+        first_line: *first_line,
+        component,
+      });
+      continue;
+    }
+    seen.push((line, component.clone()));
+    let Some(listing) = listings.get(&component.full_component_name()) else {
+      if !not_found.contains(&component.full_component_name()) {
+        not_found.push(component.full_component_name());
+        lints.push(Lint::ModNotFound { line, component });
+      }
+      continue;
+    };
+    if !listing
+      .components
+      .iter()
+      .any(|listed| listed.component == component.component)
+    {
+      lints.push(Lint::UnknownComponent {
+        line,
+        component: component.clone(),
+      });
+    }
+    if !listing.has_language(&component.lang) {
+      lints.push(Lint::UnknownLanguage {
+        line,
+        component,
+        languages: listing.languages.max(1),
+      });
+      continue;
+    }
+    if let Some(listed) = listing.find(&component)
+      && !component.version.is_empty()
+      && !listed.version.is_empty()
+      && component.version != listed.version
+    {
+      lints.push(Lint::VersionMismatch {
+        line,
+        version: listed.version.clone(),
+        component,
+      });
+    }
+  }
+  lints
+}
+
+/// The component as weidu would write it, with the names taken from the mod when it lists it
+fn canonical(component: &WeiduComponent, listing: Option<&ModListing>) -> WeiduComponent {
+  let component = listing
+    .and_then(|listing| listing.find(component))
+    .map(|listed| WeiduComponent {
+      label: component.label.clone(),
+      ..listed.clone()
+    })
+    .unwrap_or(component.clone());
+  WeiduComponent {
+    name: component.name.to_uppercase(),
+    tp_file: component.tp_file.to_uppercase(),
+    ..component
+  }
+}
+
+/// Rewrites the log in the form weidu writes it, comments other than the header are kept
+pub(crate) fn format(
+  source: &str,
+  listings: &HashMap<String, ModListing>,
+) -> Result<String, Box<dyn Error>> {
+  let log = WeiduLog::parse(source, None)?;
+  let mut lines: Vec<LogLine> = WEIDU_LOG_HEADER
+    .iter()
+    .map(|header| LogLine::Text(header.to_string()))
+    .collect();
+  for line in log.lines {
+    match line {
+      LogLine::Text(text) => {
+        let text = text.trim();
+        if !text.is_empty() && !WEIDU_LOG_HEADER.contains(&text) {
+          lines.push(LogLine::Text(text.to_string()));
+        }
+      },
+      LogLine::Component(found) => lines.push(LogLine::Component(Box::new(LogComponent::new(
+        canonical(
+          &found.component,
+          listings.get(&found.component.full_component_name()),
+        ),
+        found.annotations,
+      )))),
+    }
+  }
+  Ok(
+    WeiduLog {
+      lines,
+      final_line_ending: true,
+      ..log
+    }
+    .to_string(),
+  )
+}
+
+fn list_mod(tp2_path: &Path, langs: &[String]) -> Result<ModListing, Box<dyn Error>> {
+  let tp2 = Tp2::read(tp2_path)?;
+  let mut listing = ModListing {
+    languages: tp2.languages.len(),
+    components: vec![],
+  };
+  let mut langs: Vec<&String> = langs
+    .iter()
+    .filter(|lang| listing.has_language(lang))
+    .collect();
+  let default = "0".to_string();
+  if langs.is_empty() {
+    langs.push(&default);
+  }
+  for lang in langs {
+    listing.components.extend(read_components(tp2_path, lang)?);
+  }
+  Ok(listing)
+}
+
+/// Reads the tp2 of each mod in the log from the mod directories
+pub(crate) fn list_mods(source: &str, options: &Options) -> HashMap<String, ModListing> {
+  let mut langs: Vec<(WeiduComponent, Vec<String>)> = vec![];
+  for entry in parse_log(source, None) {
+    let Ok(LogEntry { component, .. }) = entry else {
+      continue;
+    };
+    match langs
+      .iter_mut()
+      .find(|(other, _)| other.full_component_name() == component.full_component_name())
+    {
+      Some((_, langs)) if !langs.contains(&component.lang) => langs.push(component.lang),
+      Some(_) => {},
+      None => {
+        let lang = component.lang.clone();
+        langs.push((component, vec![lang]));
+      },
+    }
+  }
+  let mut listings = HashMap::new();
+  for (component, langs) in langs {
+    let tp2_path: Result<PathBuf, Box<dyn Error>> =
+      search_mod_folders(&options.mod_directories, &component, options.depth).and_then(
+        |mod_folder| {
+          find_tp2(&mod_folder, &component.tp_file)
+            .ok_or(format!("{mod_folder:?} has no {}", component.tp_file).into())
+        },
+      );
+    let Ok(tp2_path) = tp2_path else { continue };
+    match list_mod(&tp2_path, &langs) {
+      Ok(listing) => {
+        listings.insert(component.full_component_name(), listing);
+      },
+      Err(err) => log::warn!("Could not read {tp2_path:?}, {err}"),
+    }
+  }
+  listings
+}
+
+pub(crate) fn lint_command(command: &LintCommand) -> Result<(), Box<dyn Error>> {
+  let source = read_source(&command.log_file)?;
+  let lints = lint(&source, &list_mods(&source, &command.options));
+  for found in &lints {
+    println!("{}:{found}", command.log_file.display());
+  }
+  if lints.is_empty() {
+    log::info!("{:?} has no problems", command.log_file);
+    return Ok(());
+  }
+  Err(format!("{:?} has {} problems", command.log_file, lints.len()).into())
+}
+
+pub(crate) fn fmt_command(command: &FmtCommand) -> Result<(), Box<dyn Error>> {
+  let source = read_source(&command.log_file)?;
+  let formatted = format(&source, &list_mods(&source, &command.options))
+    .map_err(|err| format!("Could not format {:?}\n{err}", command.log_file))?;
+  if command.check {
+    if formatted == source {
+      return Ok(());
+    }
+    let before: Vec<&str> = source.lines().collect();
+    for (index, line) in formatted.lines().enumerate() {
+      if before.get(index) != Some(&line) {
+        println!(
+          "line {}:\n- {}\n+ {line}",
+          index + 1,
+          before.get(index).copied().unwrap_or_default()
+        );
+      }
+    }
+    return Err(format!("{:?} is not formatted", command.log_file).into());
+  }
+  let output = command.output.as_ref().unwrap_or(&command.log_file);
+  fs::write(output, formatted)?;
+  log::info!("Wrote the formatted log to {output:?}");
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::error::Error;
+
+  fn listings() -> Result<HashMap<String, ModListing>, Box<dyn Error>> {
+    let components = [
+      "~A/SETUP-A.TP2~ #0 #0 // Core: v2",
+      "~A/SETUP-A.TP2~ #0 #1 // Tweaks -> Fast: v2",
+    ]
+    .iter()
+    .map(|line| WeiduComponent::try_from(line.to_string()))
+    .collect::<Result<Vec<_>, _>>()?;
+    Ok(HashMap::from([(
+      "a/setup-a.tp2".replace('/', std::path::MAIN_SEPARATOR_STR),
+      ModListing {
+        languages: 2,
+        components,
+      },
+    )]))
+  }
+
+  #[test]
+  fn lints_logs() -> Result<(), Box<dyn Error>> {
+    let source = "// Log of Currently Installed WeiDU Mods\n\
+      ~A/SETUP-A.TP2~ #0 #0 // Core: v1\n\
+      ~a\\setup-a.tp2~ #0 #1 // Fast\n\
+      not a component\n\
+      ~A/SETUP-A.TP2~ #0 #0 // Core: v2\n\
+      ~A/SETUP-A.TP2~ #3 #7 // Gone\n\
+      ~B/B.TP2~ #0 #0 // B\n\
+      ~B/B.TP2~ #0 #1 // B\n";
+    assert_eq!(
+      lint(source, &listings()?)
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>(),
+      vec![
+        r#"line 2: a/setup-a.tp2 #0 #0 // Core is version "v1", the mod is "v2""#,
+        r#"line 3: uses '\\' as the path separator, line 2 uses the other one"#,
+        "line 4, column 1: expected the tp2 path, between ~ or \"\n4 | not a component\n  | ^",
+        "line 5: a/setup-a.tp2 #0 #0 // Core is already on line 2",
+        "line 6: SETUP-A.TP2 has no component #7",
+        "line 6: SETUP-A.TP2 has no language #3, it has 2",
+        "line 7: b/b.tp2 is not in the mod directories",
+      ]
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn formats_logs() -> Result<(), Box<dyn Error>> {
+    let source = "~a\\setup-a.tp2~ #0 #1 // Old name // @label A_FAST\r\n\
+      \r\n\
+      // Removed by hand\r\n\
+      ~b/b.tp2~ #0 #0 // B: v1\r\n";
+    let formatted = format(source, &listings()?)?;
+    assert_eq!(
+      formatted,
+      format!(
+        "{}\r\n\
+        ~A/SETUP-A.TP2~ #0 #1 // Tweaks -> Fast: v2 // @label A_FAST\r\n\
+        // Removed by hand\r\n\
+        ~B/B.TP2~ #0 #0 // B: v1\r\n",
+        WEIDU_LOG_HEADER.join("\r\n")
+      )
+    );
+    assert_eq!(format(&formatted, &listings()?)?, formatted);
+    match format("~a/a.tp2\n", &listings()?) {
+      Ok(_) => return Err("formatted an unparseable line".into()),
+      Err(err) => assert!(err.to_string().starts_with("line 1, column 1: ")),
+    }
+    Ok(())
+  }
+}
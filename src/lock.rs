@@ -0,0 +1,206 @@
+use std::{
+  error::Error,
+  fs::{self, OpenOptions},
+  io::{ErrorKind, Write},
+  path::{Path, PathBuf},
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_derive::{Deserialize, Serialize};
+
+pub(crate) const LOCK_FILE_NAME: &str = "mod_installer.lock";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct LockOwner {
+  pub(crate) pid: u32,
+  pub(crate) host: String,
+  pub(crate) started: u64,
+}
+
+impl LockOwner {
+  fn current() -> Result<Self, Box<dyn Error>> {
+    Ok(Self {
+      pid: std::process::id(),
+      host: gethostname::gethostname().to_string_lossy().to_string(),
+      started: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+    })
+  }
+
+  /// Only a process on this host can be checked, a lock from another host is never stale
+  fn is_stale(&self, current: &LockOwner) -> bool {
+    self.host == current.host && self.pid != current.pid && !is_running(self.pid)
+  }
+}
+
+/// A process owned by another user can not be signalled but is still running
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+  use nix::{errno::Errno, sys::signal::kill, unistd::Pid};
+  !matches!(kill(Pid::from_raw(pid as i32), None), Err(Errno::ESRCH))
+}
+
+#[cfg(not(unix))]
+fn is_running(pid: u32) -> bool {
+  std::process::Command::new("tasklist")
+    .args(["/FI", &format!("PID eq {pid}"), "/NH"])
+    .output()
+    .map(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()))
+    .unwrap_or(true)
+}
+
+/// Advisory lock on a game directory, removed when dropped.
+/// Stops two installs, or an install and weidu run by hand that checks for it, sharing a directory
+#[derive(Debug)]
+pub(crate) struct GameLock {
+  path: PathBuf,
+}
+
+impl GameLock {
+  pub(crate) fn acquire(game_directory: &Path) -> Result<Self, Box<dyn Error>> {
+    let path = game_directory.join(LOCK_FILE_NAME);
+    let owner = LockOwner::current()?;
+    // Second attempt is after removing a stale lock
+    for _ in 0..2 {
+      match OpenOptions::new().write(true).create_new(true).open(&path) {
+        Ok(mut file) => {
+          file.write_all(serde_json::to_string_pretty(&owner)?.as_bytes())?;
+          log::debug!("Locked {game_directory:?}");
+          return Ok(Self { path });
+        },
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => {},
+        Err(err) => return Err(format!("Failed to create lock file {path:?}, {err}").into()),
+      }
+      let holder = match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str::<LockOwner>(&contents).ok(),
+        // Released between the two calls
+        Err(err) if err.kind() == ErrorKind::NotFound => continue,
+        Err(err) => return Err(format!("Failed to read lock file {path:?}, {err}").into()),
+      };
+      match holder {
+        Some(holder) if !holder.is_stale(&owner) => {
+          return Err(held_by(&holder, game_directory, &path, &owner).into());
+        },
+        Some(holder) => log::warn!(
+          "Removing stale lock on {game_directory:?}, process {} is no longer running",
+          holder.pid
+        ),
+        None => log::warn!("Removing unreadable lock file {path:?}"),
+      }
+      fs::remove_file(&path)?;
+    }
+    Err(format!("Could not lock {game_directory:?}, {path:?} keeps reappearing").into())
+  }
+}
+
+fn held_by(holder: &LockOwner, game_directory: &Path, path: &Path, current: &LockOwner) -> String {
+  let ago = current.started.saturating_sub(holder.started);
+  let who = if holder.pid == current.pid && holder.host == current.host {
+    "this process".to_string()
+  } else {
+    format!("process {} on {}", holder.pid, holder.host)
+  };
+  format!(
+    "{game_directory:?} is locked by {who}, started {ago}s ago. \
+    If that install is no longer running delete {path:?}"
+  )
+}
+
+impl Drop for GameLock {
+  fn drop(&mut self) {
+    if let Err(err) = fs::remove_file(&self.path) {
+      log::warn!("Failed to remove lock file {:?}, {err}", self.path);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::error::Error;
+
+  fn write_lock(game_directory: &Path, owner: &LockOwner) -> Result<(), Box<dyn Error>> {
+    fs::write(
+      game_directory.join(LOCK_FILE_NAME),
+      serde_json::to_string(owner)?,
+    )?;
+    Ok(())
+  }
+
+  #[test]
+  fn only_one_lock_per_directory() -> Result<(), Box<dyn Error>> {
+    let game_directory = tempfile::tempdir()?;
+    let lock = GameLock::acquire(game_directory.path())?;
+    let owner: LockOwner = serde_json::from_str(&fs::read_to_string(
+      game_directory.path().join(LOCK_FILE_NAME),
+    )?)?;
+    assert_eq!(owner.pid, std::process::id());
+
+    let err = match GameLock::acquire(game_directory.path()) {
+      Ok(_) => return Err("Directory should be locked".into()),
+      Err(err) => err.to_string(),
+    };
+    assert!(err.contains("locked by this process"), "{err}");
+
+    drop(lock);
+    assert_eq!(game_directory.path().join(LOCK_FILE_NAME).exists(), false);
+    GameLock::acquire(game_directory.path())?;
+    Ok(())
+  }
+
+  #[test]
+  fn reports_and_replaces_locks() -> Result<(), Box<dyn Error>> {
+    let game_directory = tempfile::tempdir()?;
+    let current = LockOwner::current()?;
+    let other_host = LockOwner {
+      pid: 1234,
+      host: format!("not-{}", current.host),
+      started: current.started - 60,
+    };
+    write_lock(game_directory.path(), &other_host)?;
+    let err = match GameLock::acquire(game_directory.path()) {
+      Ok(_) => return Err("Lock from another host is never stale".into()),
+      Err(err) => err.to_string(),
+    };
+    assert!(
+      err.contains(&format!(
+        "process 1234 on {}, started 60s ago",
+        other_host.host
+      )),
+      "{err}"
+    );
+
+    let mut finished = std::process::Command::new(std::env::current_exe()?)
+      .arg("--help")
+      .stdout(std::process::Stdio::null())
+      .spawn()?;
+    let dead_pid = finished.id();
+    finished.wait()?;
+    write_lock(
+      game_directory.path(),
+      &LockOwner {
+        pid: dead_pid,
+        ..current.clone()
+      },
+    )?;
+    drop(GameLock::acquire(game_directory.path())?);
+
+    // Init is always running, and owned by root when this is not
+    #[cfg(unix)]
+    {
+      write_lock(
+        game_directory.path(),
+        &LockOwner {
+          pid: 1,
+          ..current.clone()
+        },
+      )?;
+      assert!(GameLock::acquire(game_directory.path()).is_err());
+    }
+
+    fs::write(game_directory.path().join(LOCK_FILE_NAME), "half written")?;
+    GameLock::acquire(game_directory.path())?;
+    Ok(())
+  }
+}
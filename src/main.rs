@@ -1,21 +1,47 @@
 use std::process::ExitCode;
 
-use config::{Config, args::CommandType};
+use config::{
+  Config,
+  args::{CommandType, ConfigAction, LogsAction},
+  show::show_config,
+};
+use diff::diff_command;
+use drift::drift_command;
 use env_logger::Env;
 use installers::{eet_install, normal_install};
+use lint::{fmt_command, lint_command};
+use run_log::show_logs;
 use scan::components::scan_components;
 use scan::languages::scan_langauges;
+use serve::serve;
+use sync::sync;
+use upgrade::upgrade;
 
 use utils::find_all_mods;
+use weidu::verify::verify_command;
 
 mod config;
+mod console;
+mod control;
+mod diff;
+mod drift;
+mod events;
 mod installers;
 mod internal_log;
+mod lint;
+mod lock;
 mod parser;
 mod raw_reciever;
+mod run_log;
 mod runner;
 mod scan;
+mod serve;
+mod signals;
+mod sync;
+mod transcript;
+mod upgrade;
 mod utils;
+mod validate;
 mod weidu;
 
 const PARSER_CONFIG_LOCATION: &str = "parser";
@@ -40,7 +66,30 @@ fn main() -> ExitCode {
     ),
     CommandType::Languages(command) => scan_langauges(&command),
     CommandType::Components(command) => scan_components(&command),
+    CommandType::Config(ref command) => match &command.action {
+      ConfigAction::Show(show) => show_config(show, &config.args, &config.parser),
+    },
+    CommandType::Logs(command) => match &command.action {
+      LogsAction::Show(show) => show_logs(show),
+    },
+    CommandType::Serve(command) => serve(&command, config.parser.clone()),
+    CommandType::Verify(command) => verify_command(&command),
+    CommandType::Sync(command) => sync(
+      &command,
+      config.parser.clone(),
+      &mut find_all_mods(&command.options.mod_directories, command.options.depth),
+    ),
+    CommandType::Upgrade(command) => upgrade(
+      &command,
+      config.parser.clone(),
+      &mut find_all_mods(&command.options.mod_directories, command.options.depth),
+    ),
+    CommandType::Drift(command) => drift_command(&command),
+    CommandType::Lint(command) => lint_command(&command),
+    CommandType::Fmt(command) => fmt_command(&command),
+    CommandType::Diff(command) => diff_command(&command),
   };
+  events::close();
 
   match status {
     Err(err) => {
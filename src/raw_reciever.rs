@@ -1,13 +1,12 @@
 use std::{
   io::{BufRead, BufReader, ErrorKind, Read},
-  process::{ChildStderr, ChildStdout},
   sync::mpsc::{self, Receiver, Sender},
   thread,
 };
 
-use crate::internal_log::InternalLog;
+use crate::{internal_log::InternalLog, transcript::Stream};
 
-fn read_stream<R: Read>(label: &str, stream: R, log: InternalLog, sender: Sender<String>) {
+fn read_stream<R: Read>(label: Stream, stream: R, log: InternalLog, sender: Sender<String>) {
   let mut buffered_reader = BufReader::new(stream);
   loop {
     let mut buf = vec![];
@@ -18,7 +17,7 @@ fn read_stream<R: Read>(label: &str, stream: R, log: InternalLog, sender: Sender
       },
       Ok(_) => {
         if let Ok(line) = std::str::from_utf8(&buf) {
-          log.write(line);
+          log.write(label, line);
 
           if let Err(err) = sender.send(line.to_string()) {
             log::warn!("Failed to send line: {}, with error {}", line, err);
@@ -40,15 +39,15 @@ fn read_stream<R: Read>(label: &str, stream: R, log: InternalLog, sender: Sender
 }
 
 pub(crate) fn create_raw_reciever(
-  stdout: ChildStdout,
-  stderr: ChildStderr,
+  stdout: impl Read + Send + 'static,
+  stderr: impl Read + Send + 'static,
   log: InternalLog,
 ) -> Receiver<String> {
   let (sender, receiver) = mpsc::channel::<String>();
   let sender_stdout = sender.clone();
   let log_stdout = log.clone();
-  thread::spawn(move || read_stream("stdout", stdout, log_stdout, sender_stdout));
-  thread::spawn(move || read_stream("stderr", stderr, log, sender));
+  thread::spawn(move || read_stream(Stream::Stdout, stdout, log_stdout, sender_stdout));
+  thread::spawn(move || read_stream(Stream::Stderr, stderr, log, sender));
 
   receiver
 }
@@ -63,7 +62,7 @@ mod tests {
     let (sender, receiver) = mpsc::channel::<String>();
     let (reader, mut writer) = os_pipe::pipe()?;
 
-    thread::spawn(move || read_stream("test", reader, InternalLog::new(), sender));
+    thread::spawn(move || read_stream(Stream::Stdout, reader, InternalLog::new(), sender));
 
     writer.write(input)?;
     drop(writer);
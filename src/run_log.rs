@@ -0,0 +1,358 @@
+use std::{
+  error::Error,
+  fmt::Display,
+  fs,
+  path::{Path, PathBuf},
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+  config::{
+    CARGO_PKG_NAME,
+    args::{InstallOptions, LogsShow},
+  },
+  runner::WeiduExitStatus,
+  weidu::{
+    batched_components::WeiduBatchedComponents, component::WeiduComponent,
+    install_block::WeiduInstallBlock,
+  },
+};
+
+pub(crate) const LOGS_LOCATION: &str = "logs";
+pub(crate) const INDEX_FILE_NAME: &str = "index.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub(crate) enum Outcome {
+  Running,
+  Success,
+  Warnings { details: String },
+  Failed { details: String },
+  Skipped,
+}
+
+impl Display for Outcome {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Outcome::Running => write!(f, "running, or the install was interrupted"),
+      Outcome::Success => write!(f, "success"),
+      Outcome::Warnings { details } => write!(f, "warnings: {details}"),
+      Outcome::Failed { details } => write!(f, "failed: {details}"),
+      Outcome::Skipped => write!(f, "skipped"),
+    }
+  }
+}
+
+impl From<&Result<WeiduExitStatus, Box<dyn Error>>> for Outcome {
+  fn from(result: &Result<WeiduExitStatus, Box<dyn Error>>) -> Self {
+    match result {
+      Ok(WeiduExitStatus::Success) => Outcome::Success,
+      Ok(WeiduExitStatus::Warnings(details)) => Outcome::Warnings {
+        details: details.clone(),
+      },
+      Ok(WeiduExitStatus::Skipped) => Outcome::Skipped,
+      Err(err) => Outcome::Failed {
+        details: err.to_string(),
+      },
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct IndexComponent {
+  pub(crate) tp_file: String,
+  pub(crate) name: String,
+  pub(crate) component: String,
+  pub(crate) component_name: String,
+}
+
+impl From<&WeiduComponent> for IndexComponent {
+  fn from(component: &WeiduComponent) -> Self {
+    Self {
+      tp_file: component.tp_file.clone(),
+      name: component.name.clone(),
+      component: component.component.clone(),
+      component_name: component.component_name.clone(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct IndexEntry {
+  pub(crate) index: usize,
+  pub(crate) batch: String,
+  pub(crate) components: Vec<IndexComponent>,
+  pub(crate) transcript: PathBuf,
+  pub(crate) weidu_log: Option<PathBuf>,
+  pub(crate) outcome: Outcome,
+}
+
+impl IndexEntry {
+  /// Matches a mod name (bg1npc), a mod and component (bg1npc:0) or a batch (bg1npc-0-1)
+  fn matches(&self, query: &str) -> bool {
+    let query = query.to_lowercase();
+    if self.batch.trim_end_matches(".log") == query {
+      return true;
+    }
+    let (name, component) = match query.split_once(':') {
+      Some((name, component)) => (name, Some(component)),
+      None => (query.as_str(), None),
+    };
+    self.components.iter().any(|entry| {
+      (entry.name.to_lowercase() == name
+        || entry.tp_file.to_lowercase().trim_end_matches(".tp2") == name
+        || entry.tp_file.to_lowercase() == name)
+        && component.is_none_or(|component| entry.component == component)
+    })
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Index {
+  pub(crate) entries: Vec<IndexEntry>,
+}
+
+impl Index {
+  pub(crate) fn load(run_directory: &Path) -> Result<Self, Box<dyn Error>> {
+    let index = fs::read_to_string(run_directory.join(INDEX_FILE_NAME))?;
+    Ok(serde_json::from_str(&index)?)
+  }
+}
+
+/// One directory per install run, holding a timestamped log per batch and an index
+/// pointing at each batch's log, its weidu log file and how it went
+#[derive(Debug)]
+pub(crate) struct RunLog {
+  directory: PathBuf,
+  index: Index,
+}
+
+pub(crate) fn default_directory() -> Result<PathBuf, Box<dyn Error>> {
+  let config_path = confy::get_configuration_file_path(CARGO_PKG_NAME, LOGS_LOCATION)?;
+  Ok(
+    config_path
+      .parent()
+      .ok_or(format!(
+        "Could not find config directory from {config_path:?}"
+      ))?
+      .join(LOGS_LOCATION),
+  )
+}
+
+/// Where runs are logged, --log-directory or the default
+pub(crate) fn log_directory(install_options: &InstallOptions) -> Result<PathBuf, Box<dyn Error>> {
+  match &install_options.log_directory {
+    Some(directory) => Ok(directory.clone()),
+    None => default_directory(),
+  }
+}
+
+impl RunLog {
+  pub(crate) fn create(
+    install_options: &InstallOptions,
+    log_file_path: &Path,
+  ) -> Result<Self, Box<dyn Error>> {
+    let log_directory = log_directory(install_options)?;
+    let started = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let stem = log_file_path
+      .file_stem()
+      .unwrap_or_default()
+      .to_string_lossy()
+      .to_lowercase();
+    let mut directory = log_directory.join(format!("{started}-{stem}"));
+    let mut attempt = 1;
+    while directory.exists() {
+      directory = log_directory.join(format!("{started}-{stem}-{attempt}"));
+      attempt += 1;
+    }
+    fs::create_dir_all(&directory)?;
+    log::info!("Logging this run to {directory:?}");
+    let run_log = Self {
+      directory,
+      index: Index::default(),
+    };
+    run_log.write_index()?;
+    Ok(run_log)
+  }
+
+  /// Adds a running entry for the batch and returns the path its output should be written to
+  pub(crate) fn start(
+    &mut self,
+    components: &WeiduBatchedComponents,
+    weidu_log: Option<PathBuf>,
+  ) -> Result<PathBuf, Box<dyn Error>> {
+    let index = self.index.entries.len();
+    let batch = components.log_file_name();
+    let transcript = self
+      .directory
+      .join(format!("{index}-{batch}"))
+      .with_extension("txt");
+    self.index.entries.push(IndexEntry {
+      index,
+      batch,
+      components: components.into_iter().map(IndexComponent::from).collect(),
+      transcript: transcript.clone(),
+      weidu_log,
+      outcome: Outcome::Running,
+    });
+    self.write_index()?;
+    Ok(transcript)
+  }
+
+  pub(crate) fn finish(&mut self, outcome: Outcome) {
+    if let Some(entry) = self.index.entries.last_mut() {
+      entry.outcome = outcome;
+    }
+    if let Err(err) = self.write_index() {
+      log::warn!("Failed to write run log index, {err}");
+    }
+  }
+
+  fn write_index(&self) -> Result<(), Box<dyn Error>> {
+    fs::write(
+      self.directory.join(INDEX_FILE_NAME),
+      serde_json::to_string_pretty(&self.index)?,
+    )?;
+    Ok(())
+  }
+}
+
+fn runs(log_directory: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+  let mut runs: Vec<PathBuf> = fs::read_dir(log_directory)?
+    .flatten()
+    .map(|entry| entry.path())
+    .filter(|path| path.join(INDEX_FILE_NAME).is_file())
+    .collect();
+  runs.sort();
+  runs.reverse();
+  Ok(runs)
+}
+
+fn find(command: &LogsShow) -> Result<(PathBuf, Vec<IndexEntry>), Box<dyn Error>> {
+  let log_directory = match &command.log_directory {
+    Some(directory) => directory.clone(),
+    None => default_directory()?,
+  };
+  let runs = match &command.run {
+    Some(run) => vec![log_directory.join(run)],
+    None => runs(&log_directory)?,
+  };
+  for run in runs {
+    let index = Index::load(&run)?;
+    let entries: Vec<IndexEntry> = index
+      .entries
+      .into_iter()
+      .filter(|entry| entry.matches(&command.component))
+      .collect();
+    if !entries.is_empty() {
+      return Ok((run, entries));
+    }
+  }
+  Err(
+    format!(
+      "Could not find {} in any run in {log_directory:?}",
+      command.component
+    )
+    .into(),
+  )
+}
+
+pub(crate) fn show_logs(command: &LogsShow) -> Result<(), Box<dyn Error>> {
+  let (run, entries) = find(command)?;
+  println!("Run: {run:?}");
+  for entry in entries {
+    println!("Batch {}: {}", entry.index, entry.batch);
+    for component in &entry.components {
+      println!(
+        "  {}/{} #{} // {}",
+        component.name, component.tp_file, component.component, component.component_name
+      );
+    }
+    println!("Outcome: {}", entry.outcome);
+    if let Some(weidu_log) = &entry.weidu_log {
+      println!("Weidu log: {weidu_log:?}");
+    }
+    println!("Transcript: {:?}", entry.transcript);
+    match fs::read_to_string(&entry.transcript) {
+      Ok(transcript) => println!("{transcript}"),
+      Err(err) => log::warn!("Could not read {:?}, {err}", entry.transcript),
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::error::Error;
+
+  fn components() -> WeiduBatchedComponents {
+    WeiduBatchedComponents::from(vec![
+      WeiduComponent {
+        tp_file: "SETUP-BG1NPC.TP2".to_string(),
+        name: "bg1npc".to_string(),
+        component: "0".to_string(),
+        component_name: "Required Modifications".to_string(),
+        ..Default::default()
+      },
+      WeiduComponent {
+        tp_file: "SETUP-BG1NPC.TP2".to_string(),
+        name: "bg1npc".to_string(),
+        component: "1".to_string(),
+        component_name: "Banters".to_string(),
+        ..Default::default()
+      },
+    ])
+  }
+
+  #[test]
+  fn writes_index_and_finds_component() -> Result<(), Box<dyn Error>> {
+    let log_directory = tempfile::tempdir()?;
+    let install_options = InstallOptions {
+      log_directory: Some(log_directory.path().to_path_buf()),
+      ..Default::default()
+    };
+    let mut run_log = RunLog::create(&install_options, Path::new("weidu.log"))?;
+    let transcript = run_log.start(&components(), None)?;
+    assert_eq!(
+      transcript.file_name().and_then(|name| name.to_str()),
+      Some("0-bg1npc-0-1.txt")
+    );
+    fs::write(&transcript, "[    0.001s stdout] Installing [Banters]\n")?;
+    run_log.finish(Outcome::Failed {
+      details: "not installed due to errors".to_string(),
+    });
+
+    for query in ["bg1npc", "BG1NPC:1", "bg1npc-0-1", "setup-bg1npc"] {
+      let (run, entries) = find(&LogsShow {
+        component: query.to_string(),
+        log_directory: Some(log_directory.path().to_path_buf()),
+        run: None,
+      })?;
+      assert_eq!(run, run_log.directory);
+      assert_eq!(
+        entries, run_log.index.entries,
+        "Query {query} did not match"
+      );
+    }
+    assert_eq!(
+      Index::load(&run_log.directory)?.entries[0].outcome,
+      Outcome::Failed {
+        details: "not installed due to errors".to_string()
+      }
+    );
+    assert!(
+      find(&LogsShow {
+        component: "bg1npc:2".to_string(),
+        log_directory: Some(log_directory.path().to_path_buf()),
+        run: None,
+      })
+      .is_err()
+    );
+    Ok(())
+  }
+}
@@ -2,7 +2,7 @@ use std::{
   error::Error,
   io::Write,
   path::{Path, PathBuf},
-  process::{Child, ChildStdin, Command, Stdio},
+  process::{Child, Command, Stdio},
   sync::{
     Arc,
     atomic::AtomicUsize,
@@ -12,38 +12,40 @@ use std::{
 
 use crate::{
   config::{args::InstallOptions, options::Options, parser_config::ParserConfig, state::State},
+  control::{ABORTED, CONTROL},
   internal_log::InternalLog,
   parser::parse_raw_output,
   raw_reciever::create_raw_reciever,
-  utils::{get_user_input, sleep},
+  signals,
+  utils::sleep,
 };
 
-#[cfg(windows)]
-pub(crate) const LINE_ENDING: &str = "\r\n";
-#[cfg(not(windows))]
-pub(crate) const LINE_ENDING: &str = "\n";
-
 pub(crate) enum WeiduExitStatus {
   Success,
   Warnings(String),
+  Skipped,
 }
 
 pub(crate) type InstallationResult = Result<WeiduExitStatus, Box<dyn Error>>;
 
-fn run(
+/// Answers a weidu question
+pub(crate) type UserInput<'a> = dyn FnMut(&str) -> Result<String, Box<dyn Error>> + 'a;
+
+pub(crate) fn run(
   options: &InstallOptions,
-  mut weidu_stdin: ChildStdin,
+  mut weidu_stdin: impl Write,
   log: InternalLog,
-  eet_auto_fill: &str,
+  eet_auto_fill: Option<(&str, &PathBuf)>,
   parsed_output_receiver: Receiver<State>,
   wait_count: Arc<AtomicUsize>,
-  bg1_game_directory: Option<&PathBuf>,
+  user_input: &mut UserInput,
 ) -> Result<WeiduExitStatus, Box<dyn Error + 'static>> {
   let mut eet_check_completed = false;
   loop {
     match parsed_output_receiver.try_recv() {
       Ok(state) => {
         log::debug!("Current installer state is {state:?}");
+        log.state(&state);
         match state {
           State::Completed => {
             log::debug!("Weidu process completed");
@@ -51,23 +53,20 @@ fn run(
           },
           State::CompletedWithWarnings => {
             log::warn!("Weidu process seem to have completed with warnings");
-            let weidu_log = log.read();
-            log::warn!("Dumping log: {weidu_log}");
+            log::warn!("{}", log.report());
             return Ok(WeiduExitStatus::Warnings(
               "Weidu process exited with warnings".to_string(),
             ));
           },
           State::CompletedWithErrors { error_details } => {
             log::error!("Weidu process seem to have completed with errors");
-            let weidu_log = log.read();
-            log::error!("Dumping log: {weidu_log}");
+            log::error!("{}", log.report());
             return Err(error_details.into());
           },
           State::TimedOut => {
             let max_time = options.timeout;
             log::error!("Weidu process seem to have been running for {max_time} seconds, exiting");
-            let weidu_log = log.read();
-            log::error!("Dumping log: {weidu_log}");
+            log::error!("{}", log.report());
             return Err("Timed out".into());
           },
           State::InProgress => {
@@ -75,20 +74,17 @@ fn run(
           },
           #[cfg(target_os = "linux")]
           State::RequiresInput { question }
-            if bg1_game_directory.is_some()
-              && !eet_check_completed
+            if !eet_check_completed
+              && let Some((eet_auto_fill, bg1_game_directory)) = eet_auto_fill
               && question.contains(eet_auto_fill) =>
           {
             log::info!("🚨🚨🚨DETECTED EET INSTALL, AUTO FILL ENABLED🚨🚨🚨");
             let pre_eet_directory = &format!(
               "{}\n",
-              bg1_game_directory
-                .unwrap()
-                .as_os_str()
-                .to_str()
-                .unwrap_or_default()
+              bg1_game_directory.as_os_str().to_str().unwrap_or_default()
             );
             log::info!("Sending {}", pre_eet_directory);
+            log.input(pre_eet_directory);
             weidu_stdin.write_all(pre_eet_directory.as_bytes())?;
             eet_check_completed = true;
             log::debug!("Input sent");
@@ -97,54 +93,55 @@ fn run(
             log::info!("User Input required");
             log::info!("Question is");
             log::info!("{question}\n");
-            let user_input = get_user_input(options.tick)?;
+            let user_input = user_input(&question);
+            if CONTROL.is_skipped() {
+              return Ok(WeiduExitStatus::Skipped);
+            }
+            let user_input = user_input?;
             log::debug!("Read user input {user_input}, sending it to process ");
+            log.input(&user_input);
             weidu_stdin.write_all(user_input.as_bytes())?;
             log::debug!("Input sent");
           },
         }
       },
+      Err(TryRecvError::Empty) if CONTROL.is_aborted() => {
+        log::error!("Install aborted, stopping weidu");
+        return Err(ABORTED.into());
+      },
+      Err(TryRecvError::Empty) if CONTROL.is_skipped() => {
+        log::warn!("Skipping, stopping weidu");
+        return Ok(WeiduExitStatus::Skipped);
+      },
       Err(TryRecvError::Empty) => {
         wait_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
         log::trace!("Receiver is sleeping");
         sleep(options.tick);
       },
-      Err(TryRecvError::Disconnected) => return Ok(WeiduExitStatus::Success),
+      Err(TryRecvError::Disconnected) => {
+        return Err("Lost the weidu output parser before weidu finished".into());
+      },
     }
   }
 }
 
+/// Waits for weidu to exit, only a clean exit or one with warnings keeps the parsed result
 fn handle_result(
   mut child: Child,
-  options: &InstallOptions,
   result: Result<WeiduExitStatus, Box<dyn Error>>,
-  retry: i8,
 ) -> Result<WeiduExitStatus, Box<dyn Error>> {
-  match child.try_wait() {
-    Ok(Some(exit)) if !exit.success() && exit.code() != Some(3) => {
-      InstallationResult::Err(format!("Weidu command failed with exit status: {exit}").into())
-    },
-    Ok(Some(exit)) => {
-      log::debug!("Weidu exit status: {exit}");
-      result
-    },
-    Ok(None) if retry < 5 => {
-      log::debug!("Weidu not finished, sleeping then retrying, retry attempt number {retry}");
-      sleep(options.tick);
-      handle_result(child, options, result, retry + 1)
-    },
-    Ok(None) => {
-      log::warn!("Weidu not finished, exiting anyway");
-      if let Some(stdin) = child.stdin {
-        drop(stdin);
-      }
-      result
-    },
-    Err(err) => {
-      log::error!("Failed to close weidu process: {err}");
-      InstallationResult::Err(err.into())
-    },
+  let exit = child.wait().map_err(|err| {
+    log::error!("Failed to wait for weidu: {err}");
+    err
+  })?;
+  log::debug!("Weidu exit status: {exit}");
+  if signals::interrupted() {
+    return Err(format!("Weidu was interrupted, exit status: {exit}").into());
   }
+  if !exit.success() && exit.code() != Some(3) {
+    return Err(format!("Weidu command failed with exit status: {exit}").into());
+  }
+  result
 }
 
 pub(crate) fn handle_io(
@@ -152,6 +149,7 @@ pub(crate) fn handle_io(
   parser_config: Arc<ParserConfig>,
   options: &InstallOptions,
   bg1_game_directory: Option<&PathBuf>,
+  log: InternalLog,
 ) -> InstallationResult {
   let weidu_stdin = child
     .stdin
@@ -165,7 +163,6 @@ pub(crate) fn handle_io(
     .stderr
     .take()
     .ok_or("Failed to get weidu standard error")?;
-  let log = InternalLog::new();
   let raw_output_receiver = create_raw_reciever(weidu_stdout, weidu_stderr, log.clone());
   let (sender, parsed_output_receiver) = mpsc::channel::<State>();
 
@@ -178,16 +175,37 @@ pub(crate) fn handle_io(
     wait_count.clone(),
   );
 
+  CONTROL.set_log(Some(log.clone()));
+  // A skip left over from the last batch is not for this one
+  CONTROL.take_skip();
+  CONTROL.set_child(Some(child.id()));
   let result = run(
     options,
     weidu_stdin,
     log,
-    &parser_config.eet_auto_fill,
+    bg1_game_directory.map(|directory| (parser_config.eet_auto_fill.as_str(), directory)),
     parsed_output_receiver,
     wait_count,
-    bg1_game_directory,
+    &mut |question| CONTROL.user_input(question, options.tick),
   );
-  handle_result(child, options, result, 0)
+  CONTROL.set_log(None);
+  CONTROL.set_child(None);
+  let skipped = CONTROL.take_skip();
+  if skipped || CONTROL.is_aborted() {
+    // Interrupted rather than killed, so weidu gets to restore its backup before exiting
+    if child.try_wait()?.is_none() && !signals::interrupted() {
+      signals::interrupt(child.id());
+    }
+    let exit = child.wait()?;
+    log::debug!("Weidu exit status: {exit}");
+  }
+  if skipped {
+    return Ok(WeiduExitStatus::Skipped);
+  }
+  if CONTROL.is_aborted() {
+    return Err(ABORTED.into());
+  }
+  handle_result(child, result)
 }
 
 pub(crate) fn spawn(
@@ -197,6 +215,7 @@ pub(crate) fn spawn(
   installer_options: &InstallOptions,
   weidu_args: &[String],
   bg1_game_directory: Option<&PathBuf>,
+  log: InternalLog,
 ) -> InstallationResult {
   log::trace!("{:?}", weidu_args);
   let mut command = Command::new(options.weidu_binary.clone());
@@ -213,6 +232,19 @@ pub(crate) fn spawn(
       ))
   );
 
+  // Own process group, so a Ctrl-C in the terminal reaches us and not weidu
+  #[cfg(unix)]
+  {
+    use std::os::unix::process::CommandExt;
+    weidu_process.process_group(0);
+  }
+  #[cfg(windows)]
+  {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
+    weidu_process.creation_flags(CREATE_NEW_PROCESS_GROUP);
+  }
+
   let child = weidu_process
     .env("OCAMLRUNPARAM", &options.ocamlrunparam)
     .stdin(Stdio::piped())
@@ -220,5 +252,32 @@ pub(crate) fn spawn(
     .stderr(Stdio::piped())
     .spawn()?;
 
-  handle_io(child, parser_config, installer_options, bg1_game_directory)
+  handle_io(
+    child,
+    parser_config,
+    installer_options,
+    bg1_game_directory,
+    log,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use std::error::Error;
+
+  #[cfg(unix)]
+  #[test]
+  fn killed_weidu_is_not_a_success() -> Result<(), Box<dyn Error>> {
+    let exit = |script: &str| {
+      let child = Command::new("sh").args(["-c", script]).spawn()?;
+      handle_result(child, Ok(WeiduExitStatus::Success))
+    };
+    assert!(exit("kill -9 $$").is_err());
+    assert!(exit("exit 1").is_err());
+    assert!(matches!(exit("exit 3")?, WeiduExitStatus::Success));
+    assert!(matches!(exit("exit 0")?, WeiduExitStatus::Success));
+    Ok(())
+  }
 }
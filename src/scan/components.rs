@@ -1,11 +1,12 @@
 use std::ffi::{OsStr, OsString};
-use std::io::{BufReader, Read};
+use std::path::Path;
 use std::{error::Error, process::Command, process::Stdio};
 
 use crate::config::args::ScanComponents;
-use crate::scan::languages::scan_for_langauges;
+use crate::scan::languages::{read_langauges, scan_for_langauges};
 use crate::utils::find_all_mods;
 use crate::weidu::component::WeiduComponent;
+use crate::weidu::tp2::{Tp2, Tp2Component};
 
 fn generate_args_for_list_components_without_game_dir(
   mod_path: &OsStr,
@@ -20,46 +21,226 @@ fn generate_args_for_list_components_without_game_dir(
   ]
 }
 
+fn generate_args_for_list_components_json(mod_path: &OsStr, lang: &str) -> Vec<OsString> {
+  vec![
+    "--nogame".into(),
+    "--list-components-json".into(),
+    mod_path.into(),
+    lang.into(),
+    "--no-exit-pause".into(),
+  ]
+}
+
+/// Components weidu lists for an installed mod, labels are only there if this weidu reports them
+pub(crate) fn list_components_json(
+  weidu_binary: &Path,
+  game_directory: &Path,
+  component: &WeiduComponent,
+) -> Result<Vec<Tp2Component>, Box<dyn Error>> {
+  let weidu_args = generate_args_for_list_components_json(
+    component.full_component_name().as_ref(),
+    &component.lang,
+  );
+  log::debug!("{:?}", weidu_args);
+  let output = Command::new(weidu_binary)
+    .current_dir(game_directory)
+    .args(weidu_args)
+    .stdin(Stdio::null())
+    .output()?;
+  let weidu_output = String::from_utf8_lossy(&output.stdout);
+  log::trace!("{}", weidu_output);
+  match (weidu_output.find('['), weidu_output.rfind(']')) {
+    (Some(start), Some(end)) if start < end => parse_components_json(&weidu_output[start..=end]),
+    _ => Err(format!("weidu did not list the components of {component} as json").into()),
+  }
+}
+
+fn parse_components_json(json: &str) -> Result<Vec<Tp2Component>, Box<dyn Error>> {
+  let listed: Vec<serde_json::Value> = serde_json::from_str(json)?;
+  Ok(
+    listed
+      .iter()
+      .filter_map(|component| {
+        let number = u32::try_from(component.get("number")?.as_u64()?).ok()?;
+        let labels = match component.get("label") {
+          Some(serde_json::Value::String(label)) => vec![label.clone()],
+          Some(serde_json::Value::Array(labels)) => labels
+            .iter()
+            .filter_map(|label| label.as_str().map(String::from))
+            .collect(),
+          _ => vec![],
+        };
+        Some(Tp2Component {
+          number,
+          labels,
+          ..Default::default()
+        })
+      })
+      .collect(),
+  )
+}
+
+/// Components a mod has now, named in the given language
+pub(crate) fn list_components(
+  weidu_binary: &Path,
+  mod_path: &Path,
+  lang: &str,
+) -> Result<Vec<WeiduComponent>, Box<dyn Error>> {
+  let mod_root = mod_path
+    .parent()
+    .ok_or("tp2 file has no parent")?
+    .parent()
+    .ok_or("mod folder has no parent")?;
+  log::debug!("{:?}", mod_root);
+  let weidu_args = generate_args_for_list_components_without_game_dir(mod_path.as_os_str(), lang);
+  log::debug!("{:?}", weidu_args);
+  // Waits for weidu and reads stderr too, so a chatty weidu can not block on a full pipe
+  let output = Command::new(weidu_binary)
+    .current_dir(&mod_root.canonicalize()?)
+    .args(weidu_args)
+    .stdin(Stdio::null())
+    .output()?;
+  if !output.stderr.is_empty() {
+    log::debug!("{}", String::from_utf8_lossy(&output.stderr));
+  }
+  let weidu_output = String::from_utf8_lossy(&output.stdout);
+  log::debug!("{}", weidu_output);
+  Ok(
+    weidu_output
+      .split("\n")
+      .filter(|x| (*x).starts_with("~"))
+      .flat_map(|comp| WeiduComponent::try_from(comp.trim_end().to_string()))
+      .collect(),
+  )
+}
+
+/// Components named as weidu would list them, from the tp2 and its tra files
+pub(crate) fn read_components(
+  mod_path: &Path,
+  lang: &str,
+) -> Result<Vec<WeiduComponent>, Box<dyn Error>> {
+  let tp2 = Tp2::read(mod_path)?;
+  let mod_folder = mod_path.parent().ok_or("tp2 file has no parent")?;
+  let tra = tp2.tra(lang.parse()?, mod_folder);
+  let version = tp2
+    .version
+    .as_ref()
+    .map(|version| version.resolve(&tra))
+    .unwrap_or_default();
+  let tp_file = mod_path.file_name().unwrap_or_default().to_string_lossy();
+  let name = mod_folder.file_name().unwrap_or_default().to_string_lossy();
+  Ok(
+    tp2
+      .components
+      .iter()
+      .map(|component| {
+        // weidu shows a subcomponent as "Subcomponent -> Component"
+        let (component_name, sub_component) = match &component.subcomponent {
+          Some(subcomponent) => (subcomponent.resolve(&tra), component.name.resolve(&tra)),
+          None => (component.name.resolve(&tra), String::new()),
+        };
+        WeiduComponent {
+          tp_file: tp_file.to_string(),
+          name: name.to_string(),
+          lang: lang.to_string(),
+          component: component.number.to_string(),
+          component_name,
+          sub_component,
+          version: version.clone(),
+          label: component.labels.first().cloned().unwrap_or_default(),
+        }
+      })
+      .collect(),
+  )
+}
+
 pub(crate) fn scan_components(command: &ScanComponents) -> Result<(), Box<dyn Error>> {
   let mod_paths = find_all_mods(&command.options.mod_directories, command.options.depth);
 
   for (_, mod_path) in mod_paths {
-    let mod_root = mod_path
-      .parent()
-      .ok_or("tp2 file has no parent")?
-      .parent()
-      .ok_or("mod folder has no parent")?;
-    log::debug!("{:?}", mod_root);
-    let mod_langs = scan_for_langauges(
-      &mod_path,
-      &command.options.weidu_binary,
-      &command.filter_by_selected_language,
-    )?;
+    let mod_langs = if command.native {
+      read_langauges(&mod_path, &command.filter_by_selected_language)?
+    } else {
+      scan_for_langauges(
+        &mod_path,
+        &command.options.weidu_binary,
+        &command.filter_by_selected_language,
+      )?
+    };
     for mod_lang in mod_langs {
-      let weidu_args =
-        generate_args_for_list_components_without_game_dir(mod_path.as_os_str(), &mod_lang);
-      log::debug!("{:?}", weidu_args);
-      let mut run = Command::new(command.options.weidu_binary.clone());
-      let output = run
-        .current_dir(&mod_root.canonicalize()?)
-        .args(weidu_args)
-        .stdin(Stdio::null())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
-      if let Some(result) = output.stdout {
-        let mut buffered_reader = BufReader::new(result);
-        let mut buff = vec![];
-        buffered_reader.read_to_end(&mut buff)?;
-        let weidu_output = String::from_utf8(buff).unwrap_or_default();
-        log::debug!("{}", weidu_output);
-        weidu_output
-          .split("\n")
-          .filter(|x| (*x).starts_with("~"))
-          .flat_map(|comp| WeiduComponent::try_from(comp.to_string()))
-          .for_each(|comp| println!("{:?}", comp))
-      }
+      let components = if command.native {
+        read_components(&mod_path, &mod_lang)?
+      } else {
+        list_components(&command.options.weidu_binary, &mod_path, &mod_lang)?
+      };
+      components.iter().for_each(|comp| println!("{:?}", comp))
     }
   }
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::error::Error;
+
+  #[test]
+  fn parses_listed_labels() -> Result<(), Box<dyn Error>> {
+    let listed = parse_components_json(
+      r#"[{"tp2":"labels.tp2","number":0,"name":"Core","label":"LABELS_CORE"},
+      {"tp2":"labels.tp2","number":10,"name":"Tweak","label":["LABELS_TWEAK","TWEAK"]},
+      {"tp2":"labels.tp2","number":11,"name":"Extras"}]"#,
+    )?;
+    assert_eq!(
+      listed,
+      vec![
+        Tp2Component {
+          number: 0,
+          labels: vec!["LABELS_CORE".into()],
+          ..Default::default()
+        },
+        Tp2Component {
+          number: 10,
+          labels: vec!["LABELS_TWEAK".into(), "TWEAK".into()],
+          ..Default::default()
+        },
+        Tp2Component {
+          number: 11,
+          labels: vec![],
+          ..Default::default()
+        },
+      ]
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn reads_components_without_weidu() -> Result<(), Box<dyn Error>> {
+    let mod_path = Path::new("fixtures/tp2/metadata/setup-metadata.tp2");
+    assert_eq!(
+      read_langauges(mod_path, "deutsch")?,
+      ["1".to_string()].into()
+    );
+    let components = read_components(mod_path, "1")?;
+    assert_eq!(
+      components
+        .iter()
+        .map(|component| format!(
+          "#{} {} -> {}: {}",
+          component.component, component.component_name, component.sub_component, component.version
+        ))
+        .collect::<Vec<_>>(),
+      vec![
+        "#0 Kern -> : v3",
+        "#1 Speed -> Schnell: v3",
+        "#2 Speed -> Langsam: v3",
+        "#20 @99 -> : v3",
+      ]
+    );
+    assert_eq!(components[1].name, "metadata");
+    assert_eq!(components[1].tp_file, "setup-metadata.tp2");
+    Ok(())
+  }
+}
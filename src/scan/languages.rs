@@ -6,6 +6,7 @@ use std::{error::Error, process::ChildStdout, process::Command, process::Stdio};
 
 use crate::config::args::ScanLangauges;
 use crate::utils::find_all_mods;
+use crate::weidu::tp2::Tp2;
 
 fn generate_args_for_list_lang(mod_path: &Path) -> Vec<OsString> {
   vec![
@@ -71,16 +72,40 @@ pub(crate) fn scan_for_langauges(
   )
 }
 
+/// Languages from the LANGUAGE blocks of the tp2, without running weidu
+pub(crate) fn read_langauges(
+  mod_path: &Path,
+  filter_by_selected_language: &str,
+) -> Result<HashSet<String>, Box<dyn Error>> {
+  let filter = filter_by_selected_language.to_lowercase();
+  Ok(
+    Tp2::read(mod_path)?
+      .languages
+      .iter()
+      .enumerate()
+      .filter(|(_, language)| {
+        language.name.to_lowercase().contains(&filter)
+          || language.directory.to_lowercase().contains(&filter)
+      })
+      .map(|(index, _)| index.to_string())
+      .collect(),
+  )
+}
+
 pub(crate) fn scan_langauges(command: &ScanLangauges) -> Result<(), Box<dyn Error>> {
   let mods = find_all_mods(&command.options.mod_directories, command.options.depth);
   log::trace!("{:?}", mods);
 
   for (_, weidu_mod) in mods {
-    let langs = scan_for_langauges(
-      &weidu_mod,
-      &command.options.weidu_binary,
-      &command.filter_by_selected_language,
-    );
+    let langs = if command.native {
+      read_langauges(&weidu_mod, &command.filter_by_selected_language)
+    } else {
+      scan_for_langauges(
+        &weidu_mod,
+        &command.options.weidu_binary,
+        &command.filter_by_selected_language,
+      )
+    };
     println!("{:?} {:?}", weidu_mod, langs)
   }
   Ok(())
@@ -0,0 +1,752 @@
+use std::{
+  collections::HashMap,
+  error::Error,
+  ffi::OsString,
+  fs::{self, OpenOptions},
+  io::{BufRead, BufReader, ErrorKind, Read, Write},
+  net::{SocketAddr, TcpListener},
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex},
+  thread::{self, JoinHandle},
+};
+
+use clap::Parser;
+use serde_derive::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{
+  config::{
+    CARGO_PKG_NAME,
+    args::{Args, CommandType, Serve},
+    parser_config::ParserConfig,
+    profile::resolve_args,
+  },
+  control::{CONTROL, Control},
+  events,
+  installers::{eet_install, normal_install},
+  utils::find_all_mods,
+};
+
+/// One json object per line, ie: {"command": "answer", "id": 0, "text": "y"}
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub(crate) enum Request {
+  /// Args as they would be given on the command line, ie: ["normal", "--log-file", "weidu.log"]
+  Install {
+    args: Vec<String>,
+  },
+  Status,
+  Questions,
+  Answer {
+    id: usize,
+    text: String,
+  },
+  /// Pause once the current batch has finished
+  Pause,
+  Resume,
+  Abort,
+  /// Stop weidu and skip the component being installed
+  Skip,
+  /// Turns the connection into a stream of install events
+  Subscribe,
+}
+
+enum InstallState {
+  Idle,
+  Running(JoinHandle<Result<(), String>>),
+  Finished,
+  Failed(String),
+}
+
+pub(crate) const SERVE_LOCATION: &str = "serve";
+
+pub(crate) struct Server {
+  control: &'static Control,
+  parser_config: Arc<ParserConfig>,
+  install: Mutex<InstallState>,
+  token: String,
+}
+
+fn ok(value: Value) -> Value {
+  let mut out = json!({"ok": true});
+  if let (Some(out), Value::Object(value)) = (out.as_object_mut(), value) {
+    out.extend(value);
+  }
+  out
+}
+
+fn error(err: impl ToString) -> Value {
+  json!({"ok": false, "error": err.to_string()})
+}
+
+impl Server {
+  pub(crate) fn new(
+    control: &'static Control,
+    parser_config: Arc<ParserConfig>,
+    token: String,
+  ) -> Self {
+    control.set_remote(true);
+    Self {
+      control,
+      parser_config,
+      install: Mutex::new(InstallState::Idle),
+      token,
+    }
+  }
+
+  pub(crate) fn handle(&self, request: Request) -> Value {
+    log::debug!("Handling request {request:?}");
+    match request {
+      Request::Install { args } => match self.start(args) {
+        Ok(_) => ok(json!({"status": "running"})),
+        Err(err) => error(err),
+      },
+      Request::Status => self.status(),
+      Request::Questions => ok(json!({"questions": self.control.questions()})),
+      Request::Answer { id, text } => match self.control.answer(id, &text) {
+        Ok(_) => ok(json!({})),
+        Err(err) => error(err),
+      },
+      Request::Pause => {
+        self.control.pause();
+        ok(json!({}))
+      },
+      Request::Resume => {
+        self.control.resume();
+        ok(json!({}))
+      },
+      Request::Abort => {
+        self.control.abort();
+        ok(json!({}))
+      },
+      Request::Skip => match self.control.skip() {
+        Ok(_) => ok(json!({})),
+        Err(err) => error(err),
+      },
+      Request::Subscribe => error("Subscribe is handled by the connection"),
+    }
+  }
+
+  fn start(&self, args: Vec<String>) -> Result<(), Box<dyn Error>> {
+    let mut install = self.install.lock().map_err(|err| err.to_string())?;
+    if let InstallState::Running(handle) = &*install
+      && !handle.is_finished()
+    {
+      return Err("An install is already running".into());
+    }
+    let mut raw_args: Vec<OsString> = vec![CARGO_PKG_NAME.into()];
+    raw_args.extend(args.into_iter().map(OsString::from));
+    let command = Args::try_parse_from(resolve_args(raw_args)?)?.command;
+    if !matches!(command, CommandType::Normal(_) | CommandType::Eet(_)) {
+      return Err("Only normal and eet installs can be started".into());
+    }
+    self.control.reset();
+    let parser_config = self.parser_config.clone();
+    let handle = thread::spawn(move || {
+      let result = match command {
+        CommandType::Normal(command) => normal_install(
+          &command,
+          parser_config,
+          &mut find_all_mods(&command.options.mod_directories, command.options.depth),
+        ),
+        CommandType::Eet(command) => eet_install(
+          &command,
+          parser_config,
+          &mut find_all_mods(&command.options.mod_directories, command.options.depth),
+        ),
+        _ => Ok(()),
+      };
+      if let Err(err) = &result {
+        log::error!("{err}");
+      }
+      result.map_err(|err| err.to_string())
+    });
+    *install = InstallState::Running(handle);
+    Ok(())
+  }
+
+  fn status(&self) -> Value {
+    let mut install = match self.install.lock() {
+      Ok(install) => install,
+      Err(err) => return error(err),
+    };
+    if let InstallState::Running(handle) = &*install
+      && handle.is_finished()
+      && let InstallState::Running(handle) = std::mem::replace(&mut *install, InstallState::Idle)
+    {
+      *install = match handle.join() {
+        Ok(Ok(_)) => InstallState::Finished,
+        Ok(Err(err)) => InstallState::Failed(err),
+        Err(_) => InstallState::Failed("Install thread panicked".to_string()),
+      };
+    }
+    let (status, error) = match &*install {
+      InstallState::Idle => ("idle", None),
+      InstallState::Running(_) if self.control.is_aborted() => ("aborting", None),
+      InstallState::Running(_) if self.control.is_paused() => ("pausing", None),
+      InstallState::Running(_) => ("running", None),
+      InstallState::Finished => ("finished", None),
+      InstallState::Failed(err) => ("failed", Some(err.clone())),
+    };
+    ok(json!({
+      "status": status,
+      "error": error,
+      "paused": self.control.is_paused(),
+      "components": events::current_batch(),
+      "questions": self.control.questions().len(),
+    }))
+  }
+
+  /// Newline delimited json, every request carries the token. The first request that does not
+  /// parse, has the wrong token or looks like http closes the connection, so nothing smuggled in
+  /// after a line of some other protocol is ever run
+  pub(crate) fn connection(&self, reader: impl Read, mut writer: Box<dyn Write + Send>) {
+    for line in BufReader::new(reader).lines() {
+      let line = match line {
+        Ok(line) if line.trim().is_empty() => continue,
+        Ok(line) => line,
+        Err(err) => {
+          log::debug!("Connection closed, {err}");
+          return;
+        },
+      };
+      let request = match self.authenticate(&line) {
+        Ok(request) => request,
+        Err(err) => {
+          log::warn!("Closing connection, {err}");
+          let _ = writeln!(writer, "{}", error(err));
+          return;
+        },
+      };
+      let response = match request {
+        Request::Subscribe => {
+          if writeln!(writer, "{}", ok(json!({}))).is_ok() {
+            events::subscribe(writer);
+          }
+          return;
+        },
+        request => self.handle(request),
+      };
+      if let Err(err) = writeln!(writer, "{response}") {
+        log::debug!("Connection closed, {err}");
+        return;
+      }
+    }
+  }
+
+  fn authenticate(&self, line: &str) -> Result<Request, String> {
+    if looks_like_http(line) {
+      return Err("http requests are not served on this socket".to_string());
+    }
+    let request: Authenticated =
+      serde_json::from_str(line).map_err(|err| format!("Invalid request, {err}"))?;
+    if !same_token(&request.token, &self.token) {
+      return Err("Invalid token".to_string());
+    }
+    Ok(request.request)
+  }
+
+  /// One http request per connection, a json request posted to / with the token as a bearer token
+  pub(crate) fn http_connection(&self, reader: impl Read, mut writer: Box<dyn Write + Send>) {
+    let request = match self.read_http(&mut BufReader::new(reader)) {
+      Ok(request) => request,
+      Err((status, err)) => {
+        log::warn!("Refusing http request, {err}");
+        let _ = write_http(&mut writer, status, &error(err));
+        return;
+      },
+    };
+    match request {
+      Request::Subscribe => {
+        if write!(
+          writer,
+          "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nConnection: close\r\n\r\n{}\n",
+          ok(json!({}))
+        )
+        .and_then(|_| writer.flush())
+        .is_ok()
+        {
+          events::subscribe(writer);
+        }
+      },
+      request => {
+        let response = self.handle(request);
+        if let Err(err) = write_http(&mut writer, "200 OK", &response) {
+          log::debug!("Connection closed, {err}");
+        }
+      },
+    }
+  }
+
+  fn read_http(&self, reader: &mut impl BufRead) -> Result<Request, (&'static str, String)> {
+    let bad_request = |err: String| ("400 Bad Request", err);
+    let mut request_line = String::new();
+    read_http_line(reader, &mut request_line).map_err(bad_request)?;
+    let mut parts = request_line.split_whitespace();
+    match (parts.next(), parts.next()) {
+      (Some("POST"), Some("/")) => {},
+      (Some(_), Some("/")) => {
+        return Err(("405 Method Not Allowed", "Only POST is served".to_string()));
+      },
+      _ => return Err(("404 Not Found", "Only / is served".to_string())),
+    }
+    let mut headers = HashMap::new();
+    loop {
+      let mut line = String::new();
+      read_http_line(reader, &mut line).map_err(bad_request)?;
+      if line.trim().is_empty() {
+        break;
+      }
+      if headers.len() >= MAX_HTTP_HEADERS {
+        return Err(bad_request("Too many headers".to_string()));
+      }
+      let (name, value) = line
+        .split_once(':')
+        .ok_or_else(|| bad_request(format!("Invalid header {line:?}")))?;
+      headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+    }
+    // Browsers always send an origin on requests from a page, nothing else needs to
+    if headers.contains_key("origin") {
+      return Err((
+        "403 Forbidden",
+        "Requests from web pages are refused".to_string(),
+      ));
+    }
+    let token = headers
+      .get("authorization")
+      .and_then(|value| value.strip_prefix("Bearer "))
+      .unwrap_or_default();
+    if !same_token(token.trim(), &self.token) {
+      return Err(("401 Unauthorized", "Invalid token".to_string()));
+    }
+    if !headers
+      .get("content-type")
+      .is_some_and(|value| value.starts_with("application/json"))
+    {
+      return Err((
+        "415 Unsupported Media Type",
+        "Requests must be application/json".to_string(),
+      ));
+    }
+    let length: usize = headers
+      .get("content-length")
+      .and_then(|value| value.parse().ok())
+      .ok_or(("411 Length Required", "Missing content length".to_string()))?;
+    if length > MAX_HTTP_BODY {
+      return Err(("413 Content Too Large", "Request is too large".to_string()));
+    }
+    let mut body = vec![0; length];
+    reader
+      .read_exact(&mut body)
+      .map_err(|err| bad_request(err.to_string()))?;
+    serde_json::from_slice(&body).map_err(|err| bad_request(format!("Invalid request, {err}")))
+  }
+}
+
+/// A request on the unix socket, ie: {"token": "...", "command": "status"}
+#[derive(Debug, Deserialize)]
+struct Authenticated {
+  token: String,
+  #[serde(flatten)]
+  request: Request,
+}
+
+const MAX_HTTP_LINE: u64 = 8 * 1024;
+const MAX_HTTP_HEADERS: usize = 64;
+const MAX_HTTP_BODY: usize = 64 * 1024;
+
+fn looks_like_http(line: &str) -> bool {
+  const METHODS: [&str; 9] = [
+    "GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH",
+  ];
+  line.contains(" HTTP/")
+    || METHODS
+      .iter()
+      .any(|method| line.starts_with(&format!("{method} ")))
+}
+
+/// Compares every byte, so the time taken does not give away how much of a token was right
+fn same_token(given: &str, token: &str) -> bool {
+  given.len() == token.len()
+    && given
+      .bytes()
+      .zip(token.bytes())
+      .fold(0, |diff, (given, token)| diff | (given ^ token))
+      == 0
+}
+
+fn read_http_line(reader: &mut impl BufRead, line: &mut String) -> Result<(), String> {
+  match reader.take(MAX_HTTP_LINE).read_line(line) {
+    Ok(0) => Err("Connection closed".to_string()),
+    Ok(_) if !line.ends_with('\n') => Err("Line is too long".to_string()),
+    Ok(_) => Ok(()),
+    Err(err) => Err(err.to_string()),
+  }
+}
+
+fn write_http(writer: &mut impl Write, status: &str, body: &Value) -> std::io::Result<()> {
+  let body = body.to_string();
+  write!(
+    writer,
+    "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+    body.len()
+  )?;
+  writer.flush()
+}
+
+/// A file in the config directory, ie: serve.token
+fn config_file(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+  Ok(confy::get_configuration_file_path(CARGO_PKG_NAME, SERVE_LOCATION)?.with_file_name(name))
+}
+
+fn new_token() -> Result<String, Box<dyn Error>> {
+  let mut bytes = [0_u8; 32];
+  getrandom::fill(&mut bytes).map_err(|err| format!("Could not generate a token, {err}"))?;
+  Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Written fresh for every run, readable only by the user running the server
+fn write_token(path: &Path, token: &str) -> Result<(), Box<dyn Error>> {
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent)?;
+  }
+  // An existing file keeps its permissions when opened, so it is replaced rather than truncated
+  match fs::remove_file(path) {
+    Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+    _ => {},
+  }
+  let mut options = OpenOptions::new();
+  options.write(true).create_new(true);
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::OpenOptionsExt;
+    options.mode(0o600);
+  }
+  options.open(path)?.write_all(token.as_bytes())?;
+  Ok(())
+}
+
+fn default_listen() -> Result<String, Box<dyn Error>> {
+  if cfg!(unix) {
+    Ok(config_file("serve.sock")?.display().to_string())
+  } else {
+    Ok("127.0.0.1:7878".to_string())
+  }
+}
+
+/// Binds in a new directory only the user can enter and moves the socket into place once it is private,
+/// so there is no moment where another user can connect to it
+#[cfg(unix)]
+fn bind_private(path: &Path) -> Result<std::os::unix::net::UnixListener, Box<dyn Error>> {
+  use std::os::unix::{
+    fs::{DirBuilderExt, PermissionsExt},
+    net::UnixListener,
+  };
+  if path.symlink_metadata().is_ok() {
+    return Err(format!("{path:?} already exists, is another server running?").into());
+  }
+  let parent = match path.parent() {
+    Some(parent) if !parent.as_os_str().is_empty() => parent,
+    _ => Path::new("."),
+  };
+  let name = path
+    .file_name()
+    .ok_or(format!("{path:?} is not a socket path"))?;
+  let private = parent.join(format!(
+    ".{}.{}",
+    name.to_string_lossy(),
+    &new_token()?[..16]
+  ));
+  fs::DirBuilder::new().mode(0o700).create(&private)?;
+  let bound = private.join(name);
+  let listener = UnixListener::bind(&bound)
+    .map_err(Box::<dyn Error>::from)
+    .and_then(|listener| {
+      fs::set_permissions(&bound, fs::Permissions::from_mode(0o600))?;
+      fs::rename(&bound, path)?;
+      Ok(listener)
+    });
+  let _ = fs::remove_file(&bound);
+  fs::remove_dir(&private)?;
+  listener
+}
+
+pub(crate) fn serve(
+  command: &Serve,
+  parser_config: Arc<ParserConfig>,
+) -> Result<(), Box<dyn Error>> {
+  let token = new_token()?;
+  let token_file = match &command.token_file {
+    Some(token_file) => token_file.clone(),
+    None => config_file("serve.token")?,
+  };
+  write_token(&token_file, &token)?;
+  log::info!("Requests must carry the token in {token_file:?}");
+  let server = Arc::new(Server::new(&CONTROL, parser_config, token));
+  let listen = match &command.listen {
+    Some(listen) => listen.clone(),
+    None => default_listen()?,
+  };
+  if let Ok(address) = listen.parse::<SocketAddr>() {
+    if !address.ip().is_loopback() {
+      return Err(
+        format!(
+          "Refusing to listen on {address}, only loopback addresses are allowed, \
+          use an ssh tunnel to reach it from another machine"
+        )
+        .into(),
+      );
+    }
+    let listener = TcpListener::bind(address)?;
+    log::info!("Serving http on {address}");
+    for stream in listener.incoming() {
+      let stream = stream?;
+      let writer = Box::new(stream.try_clone()?);
+      let server = server.clone();
+      thread::spawn(move || server.http_connection(stream, writer));
+    }
+    return Ok(());
+  }
+  #[cfg(unix)]
+  {
+    use std::os::unix::{fs::FileTypeExt, net::UnixStream};
+    let path = Path::new(&listen);
+    // A socket left behind by a server that is no longer running
+    if path
+      .metadata()
+      .is_ok_and(|meta| meta.file_type().is_socket())
+      && UnixStream::connect(path).is_err()
+    {
+      fs::remove_file(path)?;
+    }
+    let listener = bind_private(path)?;
+    log::info!("Listening on {listen}");
+    for stream in listener.incoming() {
+      let stream = stream?;
+      let writer = Box::new(stream.try_clone()?);
+      let server = server.clone();
+      thread::spawn(move || server.connection(stream, writer));
+    }
+    Ok(())
+  }
+  #[cfg(not(unix))]
+  Err(format!("Could not parse {listen} as an ip and port").into())
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::{error::Error, net::TcpStream};
+
+  const TOKEN: &str = "0123456789abcdef";
+
+  fn server() -> Server {
+    let control: &'static Control = Box::leak(Box::new(Control::new()));
+    Server::new(
+      control,
+      Arc::new(ParserConfig::default()),
+      TOKEN.to_string(),
+    )
+  }
+
+  #[test]
+  fn parse_requests() -> Result<(), Box<dyn Error>> {
+    let tests = vec![
+      (r#"{"command": "status"}"#, Request::Status),
+      (
+        r#"{"command": "answer", "id": 3, "text": "y"}"#,
+        Request::Answer {
+          id: 3,
+          text: "y".to_string(),
+        },
+      ),
+      (
+        r#"{"command": "install", "args": ["normal", "-f", "weidu.log"]}"#,
+        Request::Install {
+          args: vec![
+            "normal".to_string(),
+            "-f".to_string(),
+            "weidu.log".to_string(),
+          ],
+        },
+      ),
+    ];
+    for (test, expected) in tests {
+      assert_eq!(serde_json::from_str::<Request>(test)?, expected);
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn control_requests() -> Result<(), Box<dyn Error>> {
+    let server = server();
+    assert_eq!(server.status()["status"], "idle");
+    assert_eq!(server.handle(Request::Pause)["ok"], true);
+    assert_eq!(server.status()["paused"], true);
+    assert_eq!(server.handle(Request::Resume)["ok"], true);
+    assert_eq!(server.status()["paused"], false);
+    assert_eq!(
+      server.handle(Request::Questions),
+      json!({"ok": true, "questions": []})
+    );
+    assert_eq!(
+      server.handle(Request::Answer {
+        id: 0,
+        text: "y".to_string()
+      })["ok"],
+      false
+    );
+    let refused = server.handle(Request::Install {
+      args: vec!["languages".to_string()],
+    });
+    assert_eq!(refused["ok"], false);
+    assert_eq!(server.status()["status"], "idle");
+    Ok(())
+  }
+
+  fn http(server: Arc<Server>, request: &str) -> Result<String, Box<dyn Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?;
+    thread::spawn(move || -> Result<(), String> {
+      let (stream, _) = listener.accept().map_err(|err| err.to_string())?;
+      let writer = Box::new(stream.try_clone().map_err(|err| err.to_string())?);
+      server.http_connection(stream, writer);
+      Ok(())
+    });
+    let mut client = TcpStream::connect(address)?;
+    client.write_all(request.as_bytes())?;
+    let mut response = String::new();
+    client.read_to_string(&mut response)?;
+    Ok(response)
+  }
+
+  fn post(headers: &str, body: &str) -> String {
+    format!(
+      "POST / HTTP/1.1\r\nHost: 127.0.0.1\r\n{headers}Content-Length: {}\r\n\r\n{body}",
+      body.len()
+    )
+  }
+
+  #[test]
+  fn serves_http() -> Result<(), Box<dyn Error>> {
+    let server = Arc::new(server());
+    let status = r#"{"command": "status"}"#;
+    let authorized = format!("Authorization: Bearer {TOKEN}\r\nContent-Type: application/json\r\n");
+    let response = http(server.clone(), &post(&authorized, status))?;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    let (_, body) = response.split_once("\r\n\r\n").ok_or("no body")?;
+    let body: Value = serde_json::from_str(body)?;
+    assert_eq!(body["status"], "idle");
+
+    let tests = vec![
+      (
+        post("Content-Type: application/json\r\n", status),
+        "HTTP/1.1 401 Unauthorized",
+      ),
+      (
+        post(
+          "Authorization: Bearer 0123456789abcdee\r\nContent-Type: application/json\r\n",
+          status,
+        ),
+        "HTTP/1.1 401 Unauthorized",
+      ),
+      (
+        post(
+          &format!("{authorized}Origin: http://example.com\r\n"),
+          status,
+        ),
+        "HTTP/1.1 403 Forbidden",
+      ),
+      (
+        post(
+          &format!("Authorization: Bearer {TOKEN}\r\nContent-Type: text/plain\r\n"),
+          status,
+        ),
+        "HTTP/1.1 415 Unsupported Media Type",
+      ),
+      (
+        format!("GET / HTTP/1.1\r\n{authorized}\r\n"),
+        "HTTP/1.1 405 Method Not Allowed",
+      ),
+      (post(&authorized, "not json"), "HTTP/1.1 400 Bad Request"),
+    ];
+    for (request, expected) in tests {
+      let response = http(server.clone(), &request)?;
+      assert!(
+        response.starts_with(expected),
+        "Expected {expected} for {request:?}, got {response}"
+      );
+    }
+    Ok(())
+  }
+
+  fn ndjson(server: &Server, lines: &str) -> Result<Vec<Value>, Box<dyn Error>> {
+    let (output, writer) = os_pipe::pipe()?;
+    server.connection(lines.as_bytes(), Box::new(writer));
+    let mut responses = vec![];
+    for line in BufReader::new(output).lines() {
+      responses.push(serde_json::from_str(&line?)?);
+    }
+    Ok(responses)
+  }
+
+  #[test]
+  fn closes_on_the_first_bad_request() -> Result<(), Box<dyn Error>> {
+    let server = server();
+    let status = format!(r#"{{"token": "{TOKEN}", "command": "status"}}"#);
+    let pause = format!(r#"{{"token": "{TOKEN}", "command": "pause"}}"#);
+    let tests = vec![
+      format!("{status}\nnot json\n{pause}\n"),
+      format!("{status}\n{{\"token\": \"wrong\", \"command\": \"status\"}}\n{pause}\n"),
+      format!("{status}\n{{\"command\": \"status\"}}\n{pause}\n"),
+      format!("{status}\nPOST / HTTP/1.1\n{pause}\n"),
+    ];
+    for test in tests {
+      let responses = ndjson(&server, &test)?;
+      assert_eq!(responses.len(), 2, "{test:?}");
+      assert_eq!(responses[0]["status"], "idle");
+      assert_eq!(responses[1]["ok"], false);
+      assert_eq!(server.status()["paused"], false, "{test:?}");
+    }
+    let responses = ndjson(&server, &format!("{status}\n{pause}\n"))?;
+    assert_eq!(responses.len(), 2);
+    assert_eq!(server.status()["paused"], true);
+    Ok(())
+  }
+
+  #[test]
+  fn token_file_is_private() -> Result<(), Box<dyn Error>> {
+    let directory = tempfile::tempdir()?;
+    let path = directory.path().join("serve.token");
+    fs::write(&path, "old")?;
+    let token = new_token()?;
+    assert_eq!(token.len(), 64);
+    assert_ne!(token, new_token()?);
+    write_token(&path, &token)?;
+    assert_eq!(fs::read_to_string(&path)?, token);
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::PermissionsExt;
+      assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+    }
+    Ok(())
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn socket_is_private_when_bound() -> Result<(), Box<dyn Error>> {
+    use std::os::unix::{fs::PermissionsExt, net::UnixStream};
+    let directory = tempfile::tempdir()?;
+    let path = directory.path().join("serve.sock");
+    let listener = bind_private(&path)?;
+    assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+    assert_eq!(fs::read_dir(directory.path())?.count(), 1);
+    UnixStream::connect(&path)?;
+    listener.accept()?;
+    assert!(bind_private(&path).is_err());
+    assert_eq!(fs::read_dir(directory.path())?.count(), 1);
+    Ok(())
+  }
+}
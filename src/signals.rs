@@ -0,0 +1,71 @@
+use std::{
+  error::Error,
+  sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::control::Control;
+
+static SIGNALS: AtomicUsize = AtomicUsize::new(0);
+
+/// Asks weidu to stop the way a terminal Ctrl-C would, so it can restore its backup
+#[cfg(unix)]
+pub(crate) fn interrupt(pid: u32) {
+  use nix::{
+    sys::signal::{Signal, kill},
+    unistd::Pid,
+  };
+  log::warn!("Interrupting weidu, waiting for it to exit");
+  if let Err(err) = kill(Pid::from_raw(pid as i32), Signal::SIGINT) {
+    log::error!("Failed to interrupt weidu {pid}, {err}");
+  }
+}
+
+/// Weidu runs in its own process group, which is the only way to send it a Ctrl-Break alone
+#[cfg(windows)]
+pub(crate) fn interrupt(pid: u32) {
+  use windows_sys::Win32::System::Console::{CTRL_BREAK_EVENT, GenerateConsoleCtrlEvent};
+  log::warn!("Interrupting weidu, waiting for it to exit");
+  // SAFETY: only takes the event and process group id, no memory is shared
+  if unsafe { GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, pid) } == 0 {
+    log::error!(
+      "Failed to interrupt weidu {pid}, {}",
+      std::io::Error::last_os_error()
+    );
+  }
+}
+
+/// A second Ctrl-C was passed on to weidu, whatever it exits with the batch did not finish
+pub(crate) fn interrupted() -> bool {
+  SIGNALS.load(Ordering::Relaxed) > 1
+}
+
+fn handle(control: &'static Control) {
+  match SIGNALS.fetch_add(1, Ordering::Relaxed) {
+    0 => {
+      log::warn!(
+        "Stopping once the current batch has finished, press Ctrl-C again to interrupt weidu"
+      );
+      control.stop();
+    },
+    1 => match control.child() {
+      Some(pid) => {
+        interrupt(pid);
+        // Stops waiting on a question weidu will no longer ask
+        control.abort();
+      },
+      None => std::process::exit(130),
+    },
+    _ => {
+      log::error!("Exiting without waiting for weidu");
+      std::process::exit(130);
+    },
+  }
+}
+
+/// First Ctrl-C or SIGTERM stops after the current batch, the second is passed on to weidu
+pub(crate) fn install_handler(control: &'static Control) -> Result<(), Box<dyn Error>> {
+  match ctrlc::set_handler(move || handle(control)) {
+    Ok(_) | Err(ctrlc::Error::MultipleHandlers) => Ok(()),
+    Err(err) => Err(err.into()),
+  }
+}
@@ -0,0 +1,160 @@
+use std::{
+  collections::HashMap, error::Error, ffi::OsString, fmt::Display, path::PathBuf, sync::Arc,
+};
+
+use crate::{
+  config::{
+    args::{InstallOptions, Normal, SyncCommand},
+    installed_order::InstalledOrder,
+    parser_config::ParserConfig,
+  },
+  installers::normal_install,
+  utils::confirm,
+  weidu::{
+    labels::Labels,
+    verify::{LogEntry, installed, installed_prefix, read_log},
+  },
+};
+
+/// Keeps the components installed in target order, uninstalls everything after the first
+/// divergence, last installed first, then installs the rest of the target log
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SyncPlan {
+  pub(crate) keep: usize,
+  pub(crate) uninstall: Vec<LogEntry>,
+  pub(crate) install: Vec<LogEntry>,
+}
+
+impl SyncPlan {
+  pub(crate) fn new(target: &[LogEntry], installed: &[LogEntry], strict_matching: bool) -> Self {
+    let prefix = installed_prefix(target, installed, strict_matching);
+    let mut uninstall = prefix.diverged;
+    uninstall.reverse();
+    Self {
+      keep: prefix.matched,
+      uninstall,
+      install: target[prefix.matched..].to_vec(),
+    }
+  }
+
+  pub(crate) fn is_empty(&self) -> bool {
+    self.uninstall.is_empty() && self.install.is_empty()
+  }
+}
+
+impl Display for SyncPlan {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "Keep {} components", self.keep)?;
+    if !self.uninstall.is_empty() {
+      writeln!(
+        f,
+        "Uninstall {} components, last installed first:",
+        self.uninstall.len()
+      )?;
+      for entry in &self.uninstall {
+        writeln!(f, "  weidu.log line {}: {}", entry.line, entry.component)?;
+      }
+    }
+    if !self.install.is_empty() {
+      writeln!(f, "Install {} components:", self.install.len())?;
+      for entry in &self.install {
+        writeln!(f, "  target log line {}: {}", entry.line, entry.component)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+pub(crate) fn sync(
+  command: &SyncCommand,
+  parser_config: Arc<ParserConfig>,
+  mod_folder_cache: &mut HashMap<OsString, PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+  let plan = SyncPlan::new(
+    &Labels::default().resolve_entries(
+      &read_log(&command.log_file)?,
+      &command.game_directory,
+      &command.options.weidu_binary,
+    ),
+    &installed(&command.game_directory)?,
+    command.install_options.strict_matching,
+  );
+  if plan.is_empty() {
+    log::info!(
+      "{:?} already matches {:?}",
+      command.game_directory,
+      command.log_file
+    );
+    return Ok(());
+  }
+  println!("Plan for {:?}:\n{plan}", command.game_directory);
+  if command.dry_run {
+    return Ok(());
+  }
+  if !command.yes && !confirm("Apply this plan?", command.install_options.tick)? {
+    return Err("Plan not applied".into());
+  }
+  // An order aware install does exactly this, and verifies the result
+  normal_install(
+    &Normal {
+      log_file: command.log_file.clone(),
+      game_directory: command.game_directory.clone(),
+      generate_directory: None,
+      options: command.options.clone(),
+      install_options: InstallOptions {
+        skip_installed: true,
+        installed_order: InstalledOrder::Reinstall,
+        // Everything it uninstalls is in the plan that was applied
+        uninstall_confirmed: true,
+        // The game should match the target log exactly afterwards
+        verify: true,
+        ..command.install_options.clone()
+      },
+    },
+    parser_config,
+    mod_folder_cache,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::{error::Error, fs, path::Path};
+
+  #[test]
+  fn plans_back_to_the_first_divergence() -> Result<(), Box<dyn Error>> {
+    let installed = tempfile::NamedTempFile::new()?;
+    fs::write(
+      installed.path(),
+      "~TEST_MOD_NAME_1/TEST.TP2~ #0 #0 // test mod one\n\
+      ~TEST_MOD_NAME_2/END.TP2~ #0 #0 // test mod with subcomponent information\n\
+      ~TEST_MOD_NAME_9/OLD.TP2~ #0 #0 // removed from the target\n",
+    )?;
+    let target = read_log(Path::new("fixtures/test.log"))?;
+    let installed = read_log(installed.path())?;
+    let plan = SyncPlan::new(&target, &installed, false);
+    assert_eq!(plan.keep, 1);
+    assert_eq!(
+      plan
+        .uninstall
+        .iter()
+        .map(|entry| entry.line)
+        .collect::<Vec<_>>(),
+      vec![3, 2]
+    );
+    assert_eq!(plan.install, target[1..].to_vec());
+    assert_eq!(
+      plan.to_string().lines().take(4).collect::<Vec<_>>(),
+      vec![
+        "Keep 1 components",
+        "Uninstall 2 components, last installed first:",
+        "  weidu.log line 3: test_mod_name_9/old.tp2 #0 #0 // removed from the target",
+        "  weidu.log line 2: test_mod_name_2/end.tp2 #0 #0 // test mod with subcomponent information",
+      ]
+    );
+    assert!(SyncPlan::new(&target, &target, true).is_empty());
+    Ok(())
+  }
+}
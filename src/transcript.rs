@@ -0,0 +1,384 @@
+use std::{
+  error::Error,
+  fmt::Display,
+  fs::{File, OpenOptions},
+  io::Write,
+  path::Path,
+  sync::{Arc, Mutex},
+  time::Instant,
+};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::config::{args::InstallOptions, state::State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Stream {
+  Stdout,
+  Stderr,
+}
+
+impl Display for Stream {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Stream::Stdout => write!(f, "stdout"),
+      Stream::Stderr => write!(f, "stderr"),
+    }
+  }
+}
+
+// One json object per line, so a transcript can be appended to while weidu is running
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum TranscriptEvent {
+  Start {
+    tick: u64,
+    lookback: usize,
+    weidu_args: Vec<String>,
+  },
+  Output {
+    at_ms: u64,
+    stream: Stream,
+    text: String,
+  },
+  Input {
+    at_ms: u64,
+    text: String,
+  },
+  State {
+    at_ms: u64,
+    state: State,
+  },
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct TranscriptRecorder {
+  start: Instant,
+  file: Arc<Mutex<File>>,
+}
+
+impl TranscriptRecorder {
+  pub(crate) fn create(
+    path: &Path,
+    options: &InstallOptions,
+    weidu_args: &[String],
+  ) -> Result<Self, Box<dyn Error>> {
+    let file = OpenOptions::new()
+      .create(true)
+      .truncate(true)
+      .write(true)
+      .open(path)?;
+    let recorder = Self {
+      start: Instant::now(),
+      file: Arc::new(Mutex::new(file)),
+    };
+    recorder.record(TranscriptEvent::Start {
+      tick: options.tick,
+      lookback: options.lookback,
+      weidu_args: weidu_args.to_vec(),
+    });
+    Ok(recorder)
+  }
+
+  fn at_ms(&self) -> u64 {
+    self.start.elapsed().as_millis() as u64
+  }
+
+  fn record(&self, event: TranscriptEvent) {
+    let line = match serde_json::to_string(&event) {
+      Ok(line) => line,
+      Err(err) => {
+        log::warn!("Failed to serialize transcript event {event:?}, {err}");
+        return;
+      },
+    };
+    if let Ok(mut file) = self.file.lock()
+      && let Err(err) = writeln!(file, "{line}")
+    {
+      log::warn!("Failed to write transcript, {err}");
+    }
+  }
+
+  pub(crate) fn output(&self, stream: Stream, text: &str) {
+    self.record(TranscriptEvent::Output {
+      at_ms: self.at_ms(),
+      stream,
+      text: text.to_string(),
+    });
+  }
+
+  pub(crate) fn input(&self, text: &str) {
+    self.record(TranscriptEvent::Input {
+      at_ms: self.at_ms(),
+      text: text.to_string(),
+    });
+  }
+
+  pub(crate) fn state(&self, state: &State) {
+    self.record(TranscriptEvent::State {
+      at_ms: self.at_ms(),
+      state: state.clone(),
+    });
+  }
+}
+
+#[cfg(test)]
+pub(crate) mod replay {
+
+  use std::{
+    collections::VecDeque,
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::{Arc, atomic::AtomicUsize, mpsc},
+    thread,
+    time::Duration,
+  };
+
+  use crate::{
+    config::{args::InstallOptions, parser_config::ParserConfig, state::State},
+    internal_log::InternalLog,
+    parser::parse_raw_output,
+    raw_reciever::create_raw_reciever,
+    runner::{self, WeiduExitStatus},
+  };
+
+  use super::{Stream, TranscriptEvent, TranscriptRecorder};
+
+  pub(crate) fn read_transcript(path: &Path) -> Result<Vec<TranscriptEvent>, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut events = vec![];
+    for (line_number, line) in reader.lines().enumerate() {
+      let line = line?;
+      if line.trim().is_empty() {
+        continue;
+      }
+      let event = serde_json::from_str(&line)
+        .map_err(|err| format!("{path:?}:{}: {err}", line_number + 1))?;
+      events.push(event);
+    }
+    Ok(events)
+  }
+
+  pub(crate) struct Replay {
+    pub(crate) expected_states: Vec<State>,
+    pub(crate) states: Vec<State>,
+    pub(crate) expected_inputs: Vec<String>,
+    pub(crate) inputs: Vec<String>,
+    pub(crate) result: Result<WeiduExitStatus, String>,
+  }
+
+  fn states(events: &[TranscriptEvent]) -> Vec<State> {
+    events
+      .iter()
+      .filter_map(|event| match event {
+        TranscriptEvent::State { state, .. } => Some(state.clone()),
+        _ => None,
+      })
+      .collect()
+  }
+
+  fn inputs(events: &[TranscriptEvent]) -> Vec<String> {
+    events
+      .iter()
+      .filter_map(|event| match event {
+        TranscriptEvent::Input { text, .. } => Some(text.clone()),
+        _ => None,
+      })
+      .collect()
+  }
+
+  /// Feeds a recorded transcript through the reader, parser and runner, answering
+  /// questions with the recorded input and recording what the runner saw.
+  /// The recorded timings are not replayed, output is written up to the next recorded state and
+  /// then held until the parser has reported a state, so a slow machine sees the same pauses
+  pub(crate) fn replay(path: &Path, parser_config: ParserConfig) -> Result<Replay, Box<dyn Error>> {
+    let events = read_transcript(path)?;
+    let lookback = match events.first() {
+      Some(TranscriptEvent::Start { lookback, .. }) => *lookback,
+      _ => return Err(format!("{path:?} does not start with a start event").into()),
+    };
+    let options = InstallOptions {
+      tick: 10,
+      lookback,
+      timeout: 100_000,
+      ..Default::default()
+    };
+
+    let (stdout_reader, mut stdout_writer) = os_pipe::pipe()?;
+    let (stderr_reader, mut stderr_writer) = os_pipe::pipe()?;
+    let (stdin_reader, stdin_writer) = os_pipe::pipe()?;
+    let (observed_sender, observed) = mpsc::channel::<State>();
+
+    let feed = events.clone();
+    let writer = thread::spawn(move || -> Result<Vec<String>, String> {
+      let mut stdin = BufReader::new(stdin_reader);
+      let mut received = vec![];
+      let (mut stdout, mut stderr) = (String::new(), String::new());
+      for event in feed {
+        match event {
+          TranscriptEvent::Output { stream, text, .. } => match stream {
+            Stream::Stdout => stdout.push_str(&text),
+            Stream::Stderr => stderr.push_str(&text),
+          },
+          // What came before a state is written in one go, so the parser never sees a pause in it
+          TranscriptEvent::State { .. } => {
+            stdout_writer
+              .write_all(std::mem::take(&mut stdout).as_bytes())
+              .map_err(|err| err.to_string())?;
+            stderr_writer
+              .write_all(std::mem::take(&mut stderr).as_bytes())
+              .map_err(|err| err.to_string())?;
+            observed
+              .recv_timeout(Duration::from_secs(30))
+              .map_err(|err| format!("No state reported for {event:?}, {err}"))?;
+          },
+          TranscriptEvent::Input { .. } => {
+            let mut line = String::new();
+            stdin.read_line(&mut line).map_err(|err| err.to_string())?;
+            received.push(line);
+          },
+          TranscriptEvent::Start { .. } => {},
+        }
+      }
+      stdout_writer
+        .write_all(stdout.as_bytes())
+        .and_then(|_| stderr_writer.write_all(stderr.as_bytes()))
+        .map_err(|err| err.to_string())?;
+      Ok(received)
+    });
+
+    let recorded = tempfile::NamedTempFile::new()?;
+    let recorder = TranscriptRecorder::create(recorded.path(), &options, &[])?;
+    let log = InternalLog::new().with_transcript(recorder);
+    let raw_output_receiver = create_raw_reciever(stdout_reader, stderr_reader, log.clone());
+    let (sender, parsed_receiver) = mpsc::channel::<State>();
+    let wait_count = Arc::new(AtomicUsize::new(0));
+    parse_raw_output(
+      &options,
+      sender,
+      raw_output_receiver,
+      Arc::new(parser_config),
+      wait_count.clone(),
+    );
+    // Every state the parser reports is passed on to the runner and lets the writer carry on
+    let (forward, parsed_output_receiver) = mpsc::channel::<State>();
+    thread::spawn(move || {
+      for state in parsed_receiver {
+        let _ = observed_sender.send(state.clone());
+        if forward.send(state).is_err() {
+          return;
+        }
+      }
+    });
+
+    let mut answers: VecDeque<String> = inputs(&events).into();
+    let result = runner::run(
+      &options,
+      stdin_writer,
+      log,
+      None,
+      parsed_output_receiver,
+      wait_count,
+      &mut |_| {
+        answers
+          .pop_front()
+          .ok_or("Transcript has no more recorded input".into())
+      },
+    )
+    .map_err(|err| err.to_string());
+
+    let inputs_received = writer.join().map_err(|_| "Transcript writer panicked")??;
+    let replayed = read_transcript(recorded.path())?;
+    Ok(Replay {
+      expected_states: states(&events),
+      states: states(&replayed),
+      expected_inputs: inputs(&events),
+      inputs: inputs_received,
+      result,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use crate::config::parser_config::ParserConfig;
+  use pretty_assertions::assert_eq;
+  use std::{error::Error, fs, path::PathBuf};
+
+  #[test]
+  fn round_trip_recorder() -> Result<(), Box<dyn Error>> {
+    let file = tempfile::NamedTempFile::new()?;
+    let recorder = TranscriptRecorder::create(
+      file.path(),
+      &InstallOptions {
+        tick: 500,
+        lookback: 10,
+        ..Default::default()
+      },
+      &["--force-install".to_string()],
+    )?;
+    recorder.output(Stream::Stdout, "Installing [Portraits]\n");
+    recorder.input("y\n");
+    recorder.state(&State::Completed);
+    let events = replay::read_transcript(file.path())?;
+    assert_eq!(events.len(), 4);
+    assert_eq!(
+      events[0],
+      TranscriptEvent::Start {
+        tick: 500,
+        lookback: 10,
+        weidu_args: vec!["--force-install".to_string()],
+      }
+    );
+    assert!(matches!(
+      &events[1],
+      TranscriptEvent::Output { stream: Stream::Stdout, text, .. } if text == "Installing [Portraits]\n"
+    ));
+    assert!(matches!(&events[2], TranscriptEvent::Input { text, .. } if text == "y\n"));
+    assert!(matches!(
+      &events[3],
+      TranscriptEvent::State {
+        state: State::Completed,
+        ..
+      }
+    ));
+    Ok(())
+  }
+
+  #[test]
+  fn replay_corpus() -> Result<(), Box<dyn Error>> {
+    let mut transcripts: Vec<PathBuf> = fs::read_dir("fixtures/transcripts")?
+      .flatten()
+      .map(|entry| entry.path())
+      .filter(|path| path.extension().is_some_and(|ext| ext == "ndjson"))
+      .collect();
+    transcripts.sort();
+    assert!(!transcripts.is_empty());
+    for transcript in transcripts {
+      let replayed = replay::replay(&transcript, ParserConfig::default())?;
+      assert_eq!(
+        replayed.states, replayed.expected_states,
+        "States did not match for {transcript:?}"
+      );
+      assert_eq!(
+        replayed.inputs, replayed.expected_inputs,
+        "Inputs did not match for {transcript:?}"
+      );
+      let finished_with_error = replayed
+        .expected_states
+        .iter()
+        .any(|state| matches!(state, State::CompletedWithErrors { .. }));
+      assert_eq!(
+        replayed.result.is_err(),
+        finished_with_error,
+        "Result did not match for {transcript:?}"
+      );
+    }
+    Ok(())
+  }
+}
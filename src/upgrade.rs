@@ -0,0 +1,241 @@
+use std::{
+  collections::HashMap, error::Error, ffi::OsString, fmt::Display, fs, path::PathBuf, sync::Arc,
+};
+
+use crate::{
+  config::{
+    args::{InstallOptions, Normal, Upgrade},
+    installed_order::InstalledOrder,
+    parser_config::ParserConfig,
+  },
+  installers::{install_locked, uninstall},
+  lock::GameLock,
+  run_log::log_directory,
+  utils::{confirm, search_mod_folders, swap_mod_folder},
+  weidu::{
+    component::WeiduComponent,
+    verify::{LogEntry, installed},
+  },
+};
+
+/// Matches a mod name (bg1npc) or its tp2 with or without the setup- prefix and extension
+fn is_mod(component: &WeiduComponent, tp2: &str) -> bool {
+  let tp2 = tp2.to_lowercase();
+  let tp2 = tp2.trim_end_matches(".tp2");
+  let tp_file = component.tp_file.to_lowercase();
+  let tp_file = tp_file.trim_end_matches(".tp2");
+  component.name.to_lowercase() == tp2
+    || tp_file == tp2
+    || tp_file.trim_start_matches("setup-") == tp2.trim_start_matches("setup-")
+}
+
+/// Everything installed from the earliest component of the mod onwards, in install order
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct UpgradePlan {
+  pub(crate) target: WeiduComponent,
+  pub(crate) reinstall: Vec<LogEntry>,
+}
+
+impl UpgradePlan {
+  pub(crate) fn new(installed: &[LogEntry], tp2: &str) -> Result<Self, Box<dyn Error>> {
+    let first = installed
+      .iter()
+      .position(|entry| is_mod(&entry.component, tp2))
+      .ok_or(format!("{tp2} is not installed"))?;
+    Ok(Self {
+      target: installed[first].component.clone(),
+      reinstall: installed[first..].to_vec(),
+    })
+  }
+
+  /// The components of the mod being upgraded, as they were before
+  fn mod_components(&self) -> impl Iterator<Item = &WeiduComponent> {
+    self
+      .reinstall
+      .iter()
+      .map(|entry| &entry.component)
+      .filter(|component| component.full_component_name() == self.target.full_component_name())
+  }
+}
+
+impl Display for UpgradePlan {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(
+      f,
+      "Uninstall {} components, last installed first, replace the {} folder, then install them again:",
+      self.reinstall.len(),
+      self.target.name
+    )?;
+    for entry in &self.reinstall {
+      writeln!(f, "  weidu.log line {}: {}", entry.line, entry.component)?;
+    }
+    Ok(())
+  }
+}
+
+/// Version and component changes between the mod as it was and as it was installed again
+pub(crate) fn drift(plan: &UpgradePlan, after: &[LogEntry]) -> Vec<String> {
+  plan
+    .mod_components()
+    .filter_map(|before| {
+      match after
+        .iter()
+        .map(|entry| &entry.component)
+        .find(|component| *component == before)
+      {
+        None => Some(format!("{before} was not installed again")),
+        Some(component) if component.component_name != before.component_name => Some(format!(
+          "component #{} of {} was {:?} and is now {:?}",
+          before.component, before.tp_file, before.component_name, component.component_name
+        )),
+        Some(component) if component.version != before.version => Some(format!(
+          "{} #{} went from version {:?} to {:?}",
+          before.full_component_name(),
+          before.component,
+          before.version,
+          component.version
+        )),
+        Some(_) => None,
+      }
+    })
+    .collect()
+}
+
+pub(crate) fn upgrade(
+  command: &Upgrade,
+  parser_config: Arc<ParserConfig>,
+  mod_folder_cache: &mut HashMap<OsString, PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+  let game_directory = &command.game_directory;
+  // Held from planning through the reinstall, so the plan still matches weidu.log when it is applied
+  let lock = GameLock::acquire(game_directory)?;
+  let plan = UpgradePlan::new(&installed(game_directory)?, &command.tp2)?;
+  let mod_folder = search_mod_folders(
+    &command.options.mod_directories,
+    &plan.target,
+    command.options.depth,
+  )?;
+  let installed_folder = game_directory.join(&plan.target.name);
+  if installed_folder.canonicalize().ok() == Some(mod_folder.canonicalize()?) {
+    return Err(
+      format!(
+        "Only found the installed copy of {} in {mod_folder:?}, point --mod-directories at the new version",
+        plan.target.name
+      )
+      .into(),
+    );
+  }
+  println!("Plan for {game_directory:?}:\n{plan}New version: {mod_folder:?}");
+  if command.dry_run {
+    return Ok(());
+  }
+  if !command.yes && !confirm("Apply this plan?", command.install_options.tick)? {
+    return Err("Plan not applied".into());
+  }
+
+  // The component list is the target for the reinstall, and a way to resume if it fails
+  let log_directory = log_directory(&command.install_options)?;
+  fs::create_dir_all(&log_directory)?;
+  let saved = log_directory.join(format!(
+    "weidu-before-upgrade-{}.log",
+    plan.target.name.to_lowercase()
+  ));
+  fs::copy(game_directory.join("weidu.log"), &saved)?;
+  log::info!(
+    "Saved weidu.log to {saved:?}, pass it to normal --log-file to resume a failed upgrade"
+  );
+  let components: Vec<WeiduComponent> = plan
+    .reinstall
+    .iter()
+    .map(|entry| entry.component.clone())
+    .collect();
+  uninstall(
+    &components,
+    game_directory,
+    &command.options,
+    &command.install_options,
+    parser_config.clone(),
+  )?;
+  log::info!("Replacing {installed_folder:?} with {mod_folder:?}");
+  swap_mod_folder(&mod_folder, game_directory, &plan.target.name)?;
+
+  let result = install_locked(
+    &Normal {
+      log_file: saved,
+      game_directory: game_directory.clone(),
+      generate_directory: None,
+      options: command.options.clone(),
+      install_options: InstallOptions {
+        skip_installed: true,
+        installed_order: InstalledOrder::Prefix,
+        // Versions are expected to change
+        strict_matching: false,
+        overwrite: false,
+        ..command.install_options.clone()
+      },
+    },
+    &lock,
+    parser_config,
+    mod_folder_cache,
+  );
+  match installed(game_directory) {
+    Ok(installed) => {
+      for change in drift(&plan, &installed) {
+        log::warn!("{change}");
+      }
+    },
+    Err(err) => log::warn!("Could not compare the upgraded components, {err}"),
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::{error::Error, path::Path};
+
+  use crate::weidu::verify::read_log;
+
+  #[test]
+  fn plans_from_the_first_component_of_the_mod() -> Result<(), Box<dyn Error>> {
+    let installed = read_log(Path::new("fixtures/test.log"))?;
+    for tp2 in ["test_mod_name_2", "END", "end.tp2"] {
+      let plan = UpgradePlan::new(&installed, tp2)?;
+      assert_eq!(plan.target, installed[2].component);
+      assert_eq!(plan.reinstall, installed[2..].to_vec());
+    }
+    assert!(UpgradePlan::new(&installed, "bg1npc").is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn reports_version_and_component_drift() -> Result<(), Box<dyn Error>> {
+    let before = tempfile::NamedTempFile::new()?;
+    fs::write(
+      before.path(),
+      "~A/SETUP-A.TP2~ #0 #0 // core: v1\n\
+      ~B/B.TP2~ #0 #0 // other\n\
+      ~A/SETUP-A.TP2~ #0 #1 // tweaks: v1\n\
+      ~A/SETUP-A.TP2~ #0 #2 // extras: v1\n",
+    )?;
+    let after = tempfile::NamedTempFile::new()?;
+    fs::write(
+      after.path(),
+      "~A/SETUP-A.TP2~ #0 #0 // core: v2\n\
+      ~B/B.TP2~ #0 #0 // other\n\
+      ~A/SETUP-A.TP2~ #0 #1 // portraits: v2\n",
+    )?;
+    let plan = UpgradePlan::new(&read_log(before.path())?, "setup-a")?;
+    assert_eq!(
+      drift(&plan, &read_log(after.path())?),
+      vec![
+        r#"a/setup-a.tp2 #0 went from version "v1" to "v2""#,
+        r#"component #1 of SETUP-A.TP2 was "tweaks" and is now "portraits""#,
+        "a/setup-a.tp2 #0 #2 // extras was not installed again",
+      ]
+    );
+    Ok(())
+  }
+}
@@ -3,8 +3,7 @@ use std::{
   collections::HashMap,
   error::Error,
   ffi::OsString,
-  fs::{self, File},
-  io::{BufRead, BufReader},
+  fs,
   path::{Path, PathBuf},
   process::Command,
   thread,
@@ -13,7 +12,12 @@ use tempfile::tempfile;
 use url::{Host, Url};
 use walkdir::WalkDir;
 
-use crate::weidu::component::WeiduComponent;
+use crate::{
+  control::CONTROL,
+  events::{self, Event},
+  lock::LOCK_FILE_NAME,
+  weidu::{component::WeiduComponent, verify::read_log},
+};
 
 pub fn delete_folder(path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
   if path.as_ref().exists() {
@@ -28,28 +32,43 @@ pub fn copy_folder(
   dst: impl AsRef<Path>,
   casefold: bool,
 ) -> Result<(), Box<dyn Error>> {
+  events::emit(Event::Copy {
+    from: src.as_ref().to_path_buf(),
+    to: dst.as_ref().to_path_buf(),
+  });
   copy_folder_at_depth(src, dst, 0, casefold)
 }
 
-fn copy_folder_at_depth(
-  src: impl AsRef<Path>,
-  dst: impl AsRef<Path>,
-  depth: u64,
-  casefold: bool,
-) -> Result<(), Box<dyn Error>> {
-  let destination = dst.as_ref();
+/// Creates a folder if it is missing, casefolded on linux when asked,
+/// which can only be set while the folder is empty
+pub fn create_folder(destination: &Path, casefold: bool) -> Result<(), Box<dyn Error>> {
   if !destination.exists() {
     fs::create_dir(destination)?;
     #[cfg(target_os = "linux")]
-    if depth == 0 && casefold {
+    if casefold {
       Command::new("chattr")
         .arg("+F")
         .arg(destination.to_str().unwrap_or_default())
         .output()?;
     }
   }
+  Ok(())
+}
+
+fn copy_folder_at_depth(
+  src: impl AsRef<Path>,
+  dst: impl AsRef<Path>,
+  depth: u64,
+  casefold: bool,
+) -> Result<(), Box<dyn Error>> {
+  let destination = dst.as_ref();
+  create_folder(destination, depth == 0 && casefold)?;
   for entry in fs::read_dir(src.as_ref().canonicalize()?)? {
     let entry = entry?;
+    // Belongs to whatever is installing into the source directory
+    if depth == 0 && entry.file_name() == LOCK_FILE_NAME {
+      continue;
+    }
     let full_path = entry.path().canonicalize()?;
     if entry.file_type()?.is_dir() {
       copy_folder_at_depth(
@@ -65,6 +84,21 @@ fn copy_folder_at_depth(
   Ok(())
 }
 
+/// Replaces a mod folder in the game directory, the old one is only removed once the new one is copied
+pub fn swap_mod_folder(
+  mod_folder: &Path,
+  game_directory: &Path,
+  name: &str,
+) -> Result<(), Box<dyn Error>> {
+  let destination = game_directory.join(name);
+  let incoming = game_directory.join(format!("{name}.upgrade"));
+  delete_folder(&incoming)?;
+  copy_folder(mod_folder, &incoming, false)?;
+  delete_folder(&destination)?;
+  fs::rename(&incoming, &destination)?;
+  Ok(())
+}
+
 pub fn mod_folder_present_in_game_directory(game_directory: &Path, mod_name: &str) -> bool {
   game_directory.join(mod_name).is_dir()
 }
@@ -180,11 +214,20 @@ pub fn search_or_download(
 
 pub fn try_download_mod(weidu_mod: &WeiduComponent, tick: u64) -> Result<PathBuf, Box<dyn Error>> {
   log::info!("Please provide mod url, or exit");
-  let user_input = get_user_input(tick)?;
+  let user_input = CONTROL.user_input(
+    &format!(
+      "Please provide a url for {}",
+      weidu_mod.full_component_name()
+    ),
+    tick,
+  )?;
   let url = Url::parse(&user_input)?;
   if url.host() == Some(Host::Domain("github.com")) {
     let mut zip_path = tempfile()?;
     log::info!("Downloading: {url}");
+    events::emit(Event::Download {
+      url: url.to_string(),
+    });
     reqwest::blocking::get(url.as_str())?.copy_to(&mut zip_path)?;
     let mut zip = zip::ZipArchive::new(zip_path)?;
     let dest = tempfile::tempdir()?.path().to_path_buf();
@@ -197,10 +240,11 @@ pub fn try_download_mod(weidu_mod: &WeiduComponent, tick: u64) -> Result<PathBuf
 }
 
 pub fn get_last_installed(game_dir: &Path) -> Result<WeiduComponent, Box<dyn Error>> {
-  let file = File::open(game_dir.join("weidu.log"))?;
-  let reader = BufReader::new(file);
-  let last_line = reader.lines().last().ok_or("")??;
-  WeiduComponent::try_from(last_line)
+  let path = game_dir.join("weidu.log");
+  read_log(&path)?
+    .pop()
+    .map(|entry| entry.component)
+    .ok_or(format!("{path:?} has no components").into())
 }
 
 pub fn sleep(millis: u64) {
@@ -208,6 +252,12 @@ pub fn sleep(millis: u64) {
   thread::sleep(duration);
 }
 
+/// Asks a yes or no question on stdin, anything but y is a no
+pub fn confirm(question: &str, tick: u64) -> Result<bool, Box<dyn Error>> {
+  println!("{question} [y/N]");
+  Ok(get_user_input(tick)?.trim().eq_ignore_ascii_case("y"))
+}
+
 pub fn get_user_input(tick: u64) -> Result<String, Box<dyn Error>> {
   let stdin = std::io::stdin();
   let mut input = String::new();
@@ -241,6 +291,7 @@ mod tests {
       component_name: "".to_string(),
       sub_component: "".to_string(),
       version: "".to_string(),
+      label: "".to_string(),
     };
     let mod_folder = find_mod_folder(&mod_component, Path::new("fixtures/mods"), 3);
 
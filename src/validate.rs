@@ -0,0 +1,447 @@
+use std::{collections::HashMap, error::Error, fmt::Display, path::Path};
+
+use crate::{
+  config::{args::InstallOptions, options::Options, rules::Rules},
+  utils::search_mod_folders,
+  weidu::{
+    component::WeiduComponent,
+    tp2::{ComponentId, Tp2, Tra, find_tp2},
+    verify::{LogEntry, installed, read_log},
+  },
+};
+
+/// setup-a.tp2, A/A.TP2 and a all name the same mod
+fn normalize(tp2: &str) -> String {
+  let tp2 = tp2.rsplit(['/', '\\']).next().unwrap_or(tp2).to_lowercase();
+  let tp2 = tp2.trim_end_matches(".tp2");
+  tp2.strip_prefix("setup-").unwrap_or(tp2).to_string()
+}
+
+/// A component of a mod, or any component of it when there is no number
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ComponentPattern {
+  tp2: String,
+  component: Option<String>,
+}
+
+impl ComponentPattern {
+  fn new(tp2: &str, component: Option<String>) -> Self {
+    Self {
+      tp2: normalize(tp2),
+      component: component.map(|component| component.trim().to_string()),
+    }
+  }
+
+  /// "setup-a.tp2 #3", "a #3" or "a"
+  pub(crate) fn parse(value: &str) -> Self {
+    match value.split_once('#') {
+      Some((tp2, component)) => Self::new(tp2.trim(), Some(component.to_string())),
+      None => Self::new(value.trim(), None),
+    }
+  }
+
+  fn matches(&self, component: &WeiduComponent) -> bool {
+    normalize(&component.tp_file) == self.tp2
+      && self
+        .component
+        .as_ref()
+        .is_none_or(|number| *number == component.component)
+  }
+}
+
+impl Display for ComponentPattern {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match &self.component {
+      Some(component) => write!(f, "{} #{component}", self.tp2),
+      None => write!(f, "any component of {}", self.tp2),
+    }
+  }
+}
+
+/// Found in the target log before anything is installed
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Problem {
+  Duplicate {
+    line: usize,
+    first_line: usize,
+    component: WeiduComponent,
+  },
+  Missing {
+    line: usize,
+    component: WeiduComponent,
+    requires: ComponentPattern,
+    reason: String,
+  },
+  OrderedAfter {
+    line: usize,
+    component: WeiduComponent,
+    requires: ComponentPattern,
+    required_line: usize,
+  },
+  Conflict {
+    line: usize,
+    component: WeiduComponent,
+    forbids: ComponentPattern,
+    other_line: Option<usize>,
+    reason: String,
+  },
+}
+
+fn because(reason: &str) -> String {
+  match reason.trim() {
+    "" => String::new(),
+    reason => format!(", {reason}"),
+  }
+}
+
+impl Display for Problem {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Problem::Duplicate {
+        line,
+        first_line,
+        component,
+      } => write!(
+        f,
+        "log line {line}: {component} is already on line {first_line}"
+      ),
+      Problem::Missing {
+        line,
+        component,
+        requires,
+        reason,
+      } => write!(
+        f,
+        "log line {line}: {component} requires {requires}, which is not in the log{}",
+        because(reason)
+      ),
+      Problem::OrderedAfter {
+        line,
+        component,
+        requires,
+        required_line,
+      } => write!(
+        f,
+        "log line {line}: {component} requires {requires}, which is installed after it on line {required_line}"
+      ),
+      Problem::Conflict {
+        line,
+        component,
+        forbids,
+        other_line,
+        reason,
+      } => {
+        let other = match other_line {
+          Some(other_line) => format!("on line {other_line}"),
+          None => "already installed in the game directory".to_string(),
+        };
+        write!(
+          f,
+          "log line {line}: {component} cannot be installed with {forbids} {other}{}",
+          because(reason)
+        )
+      },
+    }
+  }
+}
+
+/// A mod's tp2, with the strings of the language it is installed in
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct ModMetadata {
+  pub(crate) tp2: Tp2,
+  pub(crate) tra: Tra,
+}
+
+type Rule = (ComponentPattern, String);
+
+/// What a component needs installed before it and what it cannot be installed with
+fn rules_for(
+  component: &WeiduComponent,
+  metadata: &HashMap<String, ModMetadata>,
+  rules: &Rules,
+) -> (Vec<Rule>, Vec<Rule>) {
+  let mut requires: Vec<Rule> = vec![];
+  let mut forbids: Vec<Rule> = vec![];
+  let number = |tp2: &str, id: &ComponentId| match id {
+    ComponentId::Number(number) => Some(number.to_string()),
+    ComponentId::Label(label) => metadata
+      .get(&normalize(tp2))?
+      .tp2
+      .components
+      .iter()
+      .find(|component| component.has_label(label))
+      .map(|component| component.number.to_string()),
+  };
+  if let Some(mod_metadata) = metadata.get(&normalize(&component.tp_file))
+    && let Some(tp2_component) = mod_metadata
+      .tp2
+      .components
+      .iter()
+      .find(|tp2_component| tp2_component.number.to_string() == component.component)
+  {
+    let tra = &mod_metadata.tra;
+    for required in &tp2_component.requires {
+      if let Some(number) = number(&required.tp2, &required.component) {
+        requires.push((
+          ComponentPattern::new(&required.tp2, Some(number)),
+          required.message.resolve(tra),
+        ));
+      }
+    }
+    for forbidden in &tp2_component.forbids {
+      if let Some(number) = number(&forbidden.tp2, &forbidden.component) {
+        forbids.push((
+          ComponentPattern::new(&forbidden.tp2, Some(number)),
+          forbidden.message.resolve(tra),
+        ));
+      }
+    }
+    for predicate in &tp2_component.predicates {
+      for (tp2, required) in predicate.required_components() {
+        if let Some(number) = number(&tp2, &required) {
+          requires.push((
+            ComponentPattern::new(&tp2, Some(number)),
+            predicate.message.resolve(tra),
+          ));
+        }
+      }
+    }
+  }
+  for rule in &rules.require {
+    if ComponentPattern::parse(&rule.component).matches(component) {
+      requires.push((ComponentPattern::parse(&rule.requires), rule.reason.clone()));
+    }
+  }
+  for rule in &rules.forbid {
+    if ComponentPattern::parse(&rule.component).matches(component) {
+      forbids.push((ComponentPattern::parse(&rule.forbids), rule.reason.clone()));
+    }
+  }
+  (requires, forbids)
+}
+
+/// Components already in the game directory count as installed before the whole target log
+pub(crate) fn find_problems(
+  target: &[LogEntry],
+  installed: &[LogEntry],
+  metadata: &HashMap<String, ModMetadata>,
+  rules: &Rules,
+) -> Vec<Problem> {
+  let mut problems = vec![];
+  for (index, entry) in target.iter().enumerate() {
+    let (before, after) = (&target[..index], &target[index + 1..]);
+    let component = &entry.component;
+    if let Some(first) = before.iter().find(|other| other.component == *component) {
+      problems.push(Problem::Duplicate {
+        line: entry.line,
+        first_line: first.line,
+        component: component.clone(),
+      });
+      continue;
+    }
+    let (requires, forbids) = rules_for(component, metadata, rules);
+    for (pattern, reason) in requires {
+      let present = |entries: &[LogEntry]| {
+        entries
+          .iter()
+          .any(|other| pattern.matches(&other.component))
+      };
+      if present(installed) || present(before) {
+        continue;
+      }
+      problems.push(
+        match after.iter().find(|other| pattern.matches(&other.component)) {
+          Some(later) => Problem::OrderedAfter {
+            line: entry.line,
+            component: component.clone(),
+            requires: pattern,
+            required_line: later.line,
+          },
+          None => Problem::Missing {
+            line: entry.line,
+            component: component.clone(),
+            requires: pattern,
+            reason,
+          },
+        },
+      );
+    }
+    for (pattern, reason) in forbids {
+      let earlier = before
+        .iter()
+        .find(|other| pattern.matches(&other.component));
+      if earlier.is_some()
+        || installed
+          .iter()
+          .any(|other| pattern.matches(&other.component))
+      {
+        problems.push(Problem::Conflict {
+          line: entry.line,
+          component: component.clone(),
+          forbids: pattern,
+          other_line: earlier.map(|other| other.line),
+          reason,
+        });
+      }
+    }
+  }
+  problems
+}
+
+/// Reads the tp2 of each mod in the log found in the mod directories, others are not checked
+pub(crate) fn read_metadata(
+  target: &[LogEntry],
+  options: &Options,
+) -> HashMap<String, ModMetadata> {
+  let mut metadata = HashMap::new();
+  for entry in target {
+    let key = normalize(&entry.component.tp_file);
+    if metadata.contains_key(&key) {
+      continue;
+    }
+    let Ok(mod_folder) =
+      search_mod_folders(&options.mod_directories, &entry.component, options.depth)
+    else {
+      log::debug!(
+        "Not checking {}, it is not in the mod directories",
+        entry.component.tp_file
+      );
+      continue;
+    };
+    let Some(tp2_path) = find_tp2(&mod_folder, &entry.component.tp_file) else {
+      continue;
+    };
+    match Tp2::read(&tp2_path) {
+      Ok(tp2) => {
+        let tra = tp2.tra(
+          entry.component.lang.parse().unwrap_or_default(),
+          &mod_folder,
+        );
+        metadata.insert(key, ModMetadata { tp2, tra });
+      },
+      Err(err) => log::warn!("{err}"),
+    }
+  }
+  metadata
+}
+
+pub(crate) fn validate_log(
+  log_file: &Path,
+  game_directory: Option<&Path>,
+  options: &Options,
+  rules_file: Option<&Path>,
+) -> Result<Vec<Problem>, Box<dyn Error>> {
+  let target = read_log(log_file)?;
+  let installed = game_directory
+    .map(installed)
+    .transpose()?
+    .unwrap_or_default();
+  let rules = Rules::find(rules_file)?;
+  Ok(find_problems(
+    &target,
+    &installed,
+    &read_metadata(&target, options),
+    &rules,
+  ))
+}
+
+/// Stops an install before it starts if the target log cannot install as it is
+pub(crate) fn validate(
+  log_file: &Path,
+  game_directory: &Path,
+  options: &Options,
+  install_options: &InstallOptions,
+) -> Result<(), Box<dyn Error>> {
+  let problems = validate_log(
+    log_file,
+    Some(game_directory),
+    options,
+    install_options.rules_file.as_deref(),
+  )?;
+  if problems.is_empty() {
+    log::info!("{log_file:?} has no missing requirements or conflicts");
+    return Ok(());
+  }
+  for problem in &problems {
+    log::error!("{problem}");
+  }
+  if install_options.never_abort {
+    log::info!("{log_file:?} has problems but never abort set, so continuing");
+    return Ok(());
+  }
+  Err(
+    format!(
+      "{log_file:?} has {} problems, fix the log or use --validate false to install anyway",
+      problems.len()
+    )
+    .into(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use crate::config::rules::{ForbidRule, RequireRule};
+  use pretty_assertions::assert_eq;
+  use std::{error::Error, fs};
+
+  fn entries(log: &str) -> Result<Vec<LogEntry>, Box<dyn Error>> {
+    let file = tempfile::NamedTempFile::new()?;
+    fs::write(file.path(), log)?;
+    read_log(file.path())
+  }
+
+  #[test]
+  fn finds_missing_misordered_and_conflicting_components() -> Result<(), Box<dyn Error>> {
+    let metadata = HashMap::from([(
+      "a".to_string(),
+      ModMetadata {
+        tp2: Tp2::parse(
+          "BEGIN ~Core~ LABEL ~A_CORE~\n\
+          BEGIN ~Tweak~ REQUIRE_COMPONENT ~setup-a.tp2~ ID_OF_LABEL ~setup-a.tp2~ ~A_CORE~ @1\n\
+          BEGIN ~Extra~ REQUIRE_PREDICATE MOD_IS_INSTALLED ~b/b.tp2~ ~0~ ~Needs b~\n\
+            FORBID_COMPONENT ~c.tp2~ ~0~ ~Not with c~\n\
+          BEGIN ~Other~ REQUIRE_PREDICATE MOD_IS_INSTALLED ~x.tp2~ ~0~ OR GAME_IS ~bgee~ ~Either~",
+        ),
+        tra: Tra::parse("@1 = ~Needs the core~"),
+      },
+    )]);
+    let rules = Rules {
+      require: vec![RequireRule {
+        component: "d".into(),
+        requires: "setup-e.tp2 #1".into(),
+        reason: "d patches e".into(),
+      }],
+      forbid: vec![ForbidRule {
+        component: "setup-d.tp2 #0".into(),
+        forbids: "f".into(),
+        reason: "".into(),
+      }],
+    };
+    let target = entries(
+      "~A/SETUP-A.TP2~ #0 #1 // Tweak\n\
+      ~A/SETUP-A.TP2~ #0 #0 // Core\n\
+      ~A/SETUP-A.TP2~ #0 #2 // Extra\n\
+      ~A/SETUP-A.TP2~ #0 #3 // Other\n\
+      ~D/SETUP-D.TP2~ #0 #0 // D\n\
+      ~A/SETUP-A.TP2~ #0 #0 // Core\n",
+    )?;
+    let installed = entries("~C/C.TP2~ #0 #0 // C\n~F/F.TP2~ #0 #4 // F\n")?;
+    assert_eq!(
+      find_problems(&target, &installed, &metadata, &rules)
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>(),
+      vec![
+        "log line 1: a/setup-a.tp2 #0 #1 // Tweak requires a #0, which is installed after it on line 2",
+        "log line 3: a/setup-a.tp2 #0 #2 // Extra requires b #0, which is not in the log, Needs b",
+        "log line 3: a/setup-a.tp2 #0 #2 // Extra cannot be installed with c #0 already installed in the game directory, Not with c",
+        "log line 5: d/setup-d.tp2 #0 #0 // D requires e #1, which is not in the log, d patches e",
+        "log line 5: d/setup-d.tp2 #0 #0 // D cannot be installed with any component of f already installed in the game directory",
+        "log line 6: a/setup-a.tp2 #0 #0 // Core is already on line 2",
+      ]
+    );
+    Ok(())
+  }
+}
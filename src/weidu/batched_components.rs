@@ -1,19 +1,17 @@
 use std::{
   error::Error,
-  fs::File,
-  io::{BufRead, BufReader},
   path::{Path, PathBuf},
   slice::Iter,
 };
 
 use crate::{
   config::{log_options::LogOptions, weidu_log_options::WeiduLogOptions},
-  runner::LINE_ENDING,
   weidu::component::WeiduComponent,
   weidu::install_block::WeiduInstallBlock,
+  weidu::verify::{installed, read_log},
 };
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub(crate) struct WeiduBatchedComponents(Vec<WeiduComponent>);
 
 impl WeiduBatchedComponents {
@@ -23,14 +21,12 @@ impl WeiduBatchedComponents {
     game_directory: &Path,
   ) -> Result<(), Box<dyn Error>> {
     let number_of_mods_found = self.len();
-    let existing_weidu_log_file_path = game_directory.join("weidu").with_extension("log");
-    if let Ok(installed_mods) = WeiduBatchedComponents::try_from(existing_weidu_log_file_path) {
-      for installed_mod in &installed_mods {
-        if strict_matching {
-          self.retain(|mod_to_install| installed_mod.strict_matching(mod_to_install));
-        } else {
-          self.retain(|mod_to_install| installed_mod != mod_to_install);
-        }
+    for entry in installed(game_directory)? {
+      let installed_mod = &entry.component;
+      if strict_matching {
+        self.retain(|mod_to_install| installed_mod.strict_matching(mod_to_install));
+      } else {
+        self.retain(|mod_to_install| installed_mod != mod_to_install);
       }
     }
 
@@ -41,6 +37,10 @@ impl WeiduBatchedComponents {
     );
     Ok(())
   }
+  /// Drops the first components, ie: those already installed in target order
+  pub(crate) fn remove_first(&mut self, count: usize) {
+    self.0.drain(..count.min(self.0.len()));
+  }
   pub(crate) fn push(&mut self, component: WeiduComponent) {
     if !self.0.contains(&component) {
       self.0.push(component);
@@ -55,6 +55,36 @@ impl WeiduBatchedComponents {
   pub(crate) fn len(&self) -> usize {
     self.0.len()
   }
+  /// Uninstalls these components, the last installed first
+  pub(crate) fn generate_weidu_uninstall_args(
+    &self,
+    weidu_log_mode: Vec<LogOptions>,
+    language: &str,
+    generic_weidu_args: &[String],
+  ) -> Vec<String> {
+    let mut args = vec![];
+    if let Some(first) = self.first() {
+      args.push(first.full_component_name());
+      args.push("--force-uninstall-list".to_string());
+      args.extend(
+        self
+          .0
+          .iter()
+          .rev()
+          .map(|component| component.component.to_string()),
+      );
+      args.push("--use-lang".to_string());
+      args.push(language.to_string());
+      args.push("--language".to_string());
+      args.push(first.lang.to_string());
+      args.push("--no-exit-pause".to_string());
+    }
+    args.extend(
+      WeiduLogOptions::new(weidu_log_mode).to_args(&format!("uninstall-{}", self.log_file_name())),
+    );
+    args.extend_from_slice(generic_weidu_args);
+    args
+  }
   pub(crate) fn retain<F: FnMut(&WeiduComponent) -> bool>(&mut self, mut f: F) {
     self.0.retain_mut(|elem| f(elem));
   }
@@ -73,16 +103,10 @@ impl TryFrom<PathBuf> for WeiduBatchedComponents {
   type Error = Box<dyn Error>;
 
   fn try_from(value: PathBuf) -> Result<Self, Self::Error> {
-    let file = File::open(value)?;
-    let reader = BufReader::new(file);
-    let mut components = vec![];
-
-    for line in reader.lines().map_while(|line| line.ok()) {
-      // Ignore comments and empty lines
-      if !line.is_empty() && !line.starts_with(LINE_ENDING) && !line.starts_with("//") {
-        components.push(WeiduComponent::try_from(line)?)
-      }
-    }
+    let components = read_log(&value)?
+      .into_iter()
+      .map(|entry| entry.component)
+      .collect();
     Ok(Self(components))
   }
 }
@@ -156,6 +180,7 @@ mod tests {
         component_name: "test mod one".to_string(),
         sub_component: "".to_string(),
         version: "".to_string(),
+        label: "".to_string(),
       },
       WeiduComponent {
         tp_file: "TEST.TP2".to_string(),
@@ -165,6 +190,7 @@ mod tests {
         component_name: "test mod two".to_string(),
         sub_component: "".to_string(),
         version: "".to_string(),
+        label: "".to_string(),
       },
       WeiduComponent {
         tp_file: "END.TP2".to_string(),
@@ -174,6 +200,7 @@ mod tests {
         component_name: "test mod with subcomponent information".to_string(),
         sub_component: "Standard installation".to_string(),
         version: "".to_string(),
+        label: "".to_string(),
       },
       WeiduComponent {
         tp_file: "END.TP2".to_string(),
@@ -183,6 +210,7 @@ mod tests {
         component_name: "test mod with version".to_string(),
         sub_component: "".to_string(),
         version: "1.02".to_string(),
+        label: "".to_string(),
       },
       WeiduComponent {
         tp_file: "TWEAKS.TP2".to_string(),
@@ -192,6 +220,7 @@ mod tests {
         component_name: "test mod with both subcomponent information and version".to_string(),
         sub_component: "Casting speed only".to_string(),
         version: "v16".to_string(),
+        label: "".to_string(),
       },
     ]);
     assert_eq!(expected, result);
@@ -205,6 +234,32 @@ mod tests {
     log_file.remove_existing(false, &game_directory)?;
     let expected = WeiduBatchedComponents::try_from(PathBuf::from("./fixtures/expected.log"))?;
     assert_eq!(expected, log_file);
+
+    // A weidu.log that cannot be read is an error, not an empty game
+    let unreadable = tempfile::tempdir()?;
+    std::fs::create_dir(unreadable.path().join("weidu.log"))?;
+    assert!(log_file.remove_existing(false, unreadable.path()).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_uninstall_args() -> Result<(), Box<dyn Error>> {
+    let mut first_mod = WeiduBatchedComponents::try_from(PathBuf::from("fixtures/test.log"))?;
+    first_mod.retain(|component| component.name == "TEST_MOD_NAME_1");
+    assert_eq!(
+      first_mod.generate_weidu_uninstall_args(vec![], "en_US", &[]),
+      vec![
+        "test_mod_name_1/test.tp2",
+        "--force-uninstall-list",
+        "1",
+        "0",
+        "--use-lang",
+        "en_US",
+        "--language",
+        "0",
+        "--no-exit-pause",
+      ]
+    );
     Ok(())
   }
 }
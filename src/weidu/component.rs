@@ -1,12 +1,14 @@
-use std::error::Error;
+use std::{error::Error, fmt::Display};
+
+use serde_derive::Serialize;
 
 use crate::config::{log_options::LogOptions, weidu_log_options::WeiduLogOptions};
 
-use crate::weidu::install_block::WeiduInstallBlock;
+use crate::weidu::{install_block::WeiduInstallBlock, log_file::parse_line, tp2::Tp2Component};
 
 // This should mirror the weidu component
 // https://github.com/WeiDUorg/weidu/blob/devel/src/tp.ml#L98
-#[derive(Debug, PartialOrd, Ord, Eq, Clone, Default)]
+#[derive(Debug, PartialOrd, Ord, Eq, Clone, Default, Serialize)]
 pub(crate) struct WeiduComponent {
   pub(crate) tp_file: String,
   pub(crate) name: String,
@@ -15,6 +17,8 @@ pub(crate) struct WeiduComponent {
   pub(crate) component_name: String,
   pub(crate) sub_component: String,
   pub(crate) version: String,
+  /// tp2 LABEL, from a `// @label NAME` annotation after the component in the log
+  pub(crate) label: String,
 }
 
 impl PartialEq for WeiduComponent {
@@ -27,6 +31,8 @@ impl PartialEq for WeiduComponent {
 }
 
 impl WeiduComponent {
+  pub(crate) const LABEL_ANNOTATION: &str = "@label";
+
   pub(crate) fn strict_matching(&self, other: &Self) -> bool {
     self.eq(other)
       && self.component_name == other.component_name
@@ -34,100 +40,78 @@ impl WeiduComponent {
       && self.version == other.version
   }
 
+  /// Looks the label up in the components the tp2 provides now, the number in the log is only
+  /// used when the mod has no labels
+  pub(crate) fn resolve_label(&self, available: &[Tp2Component]) -> Result<Self, Box<dyn Error>> {
+    if self.label.is_empty() || available.iter().all(|tp2| tp2.labels.is_empty()) {
+      return Ok(self.clone());
+    }
+    let found = available
+      .iter()
+      .find(|tp2| tp2.has_label(&self.label))
+      .ok_or(format!(
+        "{self} is labelled {}, which {} no longer has",
+        self.label, self.tp_file
+      ))?;
+    Ok(Self {
+      component: found.number.to_string(),
+      ..self.clone()
+    })
+  }
+
+  /// What weidu writes after the `//` of a log line, with the label annotation if there is one
+  pub(crate) fn log_comment(&self) -> String {
+    let mut comment = self.component_name.clone();
+    if !self.sub_component.is_empty() {
+      comment = format!("{comment} -> {}", self.sub_component);
+    }
+    if !self.version.is_empty() {
+      comment = format!("{comment}: {}", self.version);
+    } else if comment.contains(": ") || comment.ends_with(':') {
+      // Otherwise the end of the name would be read as the version
+      comment.push(':');
+    }
+    if !self.label.is_empty() {
+      comment = format!("{comment} // {} {}", Self::LABEL_ANNOTATION, self.label);
+    }
+    comment
+  }
+
+  /// The line weidu writes for this component, ie: `~MOD/SETUP-MOD.TP2~ #0 #3 // Name -> Sub: v1`
+  pub(crate) fn to_log_line(&self) -> String {
+    let line = format!(
+      "~{}/{}~ #{} #{}",
+      self.name, self.tp_file, self.lang, self.component
+    );
+    match self.log_comment() {
+      comment if comment.is_empty() => line,
+      comment => format!("{line} // {comment}"),
+    }
+  }
+
   pub(crate) fn full_component_name(&self) -> String {
     format!("{}{}{}", self.name, std::path::MAIN_SEPARATOR, self.tp_file).to_lowercase()
   }
 }
 
+impl Display for WeiduComponent {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{} #{} #{} // {}",
+      self.full_component_name(),
+      self.lang,
+      self.component,
+      self.component_name
+    )
+  }
+}
+
 impl TryFrom<String> for WeiduComponent {
   type Error = Box<dyn Error>;
 
   fn try_from(line: String) -> Result<Self, Self::Error> {
-    let mut parts = line.split('~');
-
-    let install_path = parts.nth(1).ok_or(format!(
-      "Could not get full name of mod, from provided string: {line}"
-    ))?;
-
-    let (tp_file, name) = if install_path.split('\\').nth(1).is_some() {
-      let mut component_path_string = install_path
-        .split('\\')
-        .collect::<Vec<&str>>()
-        .into_iter()
-        .rev();
-      (
-        component_path_string.next().unwrap_or_default(),
-        component_path_string.next().unwrap_or_default(),
-      )
-    } else if install_path.split('/').nth(1).is_some() {
-      let mut component_path_string = install_path
-        .split('/')
-        .collect::<Vec<&str>>()
-        .into_iter()
-        .rev();
-      (
-        component_path_string.next().unwrap_or_default(),
-        component_path_string.next().unwrap_or_default(),
-      )
-    } else {
-      return Err(format!("Could not find tp2 file name, from provided string: {line}").into());
-    };
-
-    let mut tail = parts
-      .next()
-      .ok_or(format!(
-        "Could not find lang and component, from provided string {line}"
-      ))?
-      .split("//");
-
-    let mut lang_and_component = tail.next().unwrap_or_default().split(' ');
-
-    let lang = lang_and_component
-      .nth(1)
-      .ok_or(format!("Could not find lang, from provided string: {line}"))?
-      .replace('#', "");
-
-    let component = lang_and_component
-      .next()
-      .ok_or(format!(
-        "Could not find component, from provided string {line}"
-      ))?
-      .replace('#', "");
-
-    let mut component_name_sub_component_version = tail.next().unwrap_or_default().split(':');
-
-    let mut component_name_sub_component = component_name_sub_component_version
-      .next()
-      .unwrap_or_default()
-      .split("->");
-
-    let component_name = component_name_sub_component
-      .next()
-      .unwrap_or_default()
-      .trim()
-      .to_string();
-
-    let sub_component = component_name_sub_component
-      .next()
-      .unwrap_or_default()
-      .trim()
-      .to_string();
-
-    let version = component_name_sub_component_version
-      .next()
-      .unwrap_or_default()
-      .trim()
-      .to_string();
-
-    Ok(Self {
-      tp_file: tp_file.to_string(),
-      name: name.to_string(),
-      lang,
-      component,
-      component_name,
-      sub_component,
-      version,
-    })
+    Ok(parse_line(line.trim_end_matches(['\r', '\n']), 1)?)
   }
 }
 
@@ -175,6 +159,7 @@ mod tests {
       component_name: "TobEx - Core".to_string(),
       sub_component: "".to_string(),
       version: "v28".to_string(),
+      label: "".to_string(),
     };
     assert_eq!(mod_component, expected);
     Ok(())
@@ -190,6 +175,7 @@ mod tests {
       component_name: "TobEx - Core".to_string(),
       sub_component: "".to_string(),
       version: "v28".to_string(),
+      label: "".to_string(),
     };
 
     let non_strict_match_2 = WeiduComponent {
@@ -200,6 +186,7 @@ mod tests {
       component_name: "TobEx - Core Chicken".to_string(),
       sub_component: "".to_string(),
       version: "v28".to_string(),
+      label: "".to_string(),
     };
     assert_eq!(non_strict_match_1, non_strict_match_2);
     assert_eq!(
@@ -208,4 +195,43 @@ mod tests {
     );
     Ok(())
   }
+
+  #[test]
+  fn installs_by_label() -> Result<(), Box<dyn Error>> {
+    let component = WeiduComponent::try_from(
+      "~LABELS/LABELS.TP2~ #0 #1 // Tweak: v1 // @label LABELS_TWEAK".to_string(),
+    )?;
+    assert_eq!(component.label, "LABELS_TWEAK");
+    assert_eq!(component.component_name, "Tweak");
+    assert_eq!(component.version, "v1");
+
+    let available = vec![
+      Tp2Component {
+        number: 0,
+        labels: vec!["LABELS_CORE".into()],
+        ..Default::default()
+      },
+      Tp2Component {
+        number: 10,
+        labels: vec!["labels_tweak".into()],
+        ..Default::default()
+      },
+    ];
+    assert_eq!(component.resolve_label(&available)?.component, "10");
+    assert_eq!(
+      component
+        .resolve_label(&[Tp2Component::default()])?
+        .component,
+      "1"
+    );
+    let removed = match component.resolve_label(&available[..1]) {
+      Ok(_) => return Err("Label was removed from the tp2".into()),
+      Err(err) => err.to_string(),
+    };
+    assert_eq!(
+      removed,
+      "labels/labels.tp2 #0 #1 // Tweak is labelled LABELS_TWEAK, which LABELS.TP2 no longer has"
+    );
+    Ok(())
+  }
 }
@@ -1,4 +1,4 @@
-use std::{error::Error, slice::Iter};
+use std::{error::Error, slice::Iter, vec::IntoIter};
 
 use crate::{
   config::args::BatchOptions,
@@ -61,6 +61,15 @@ impl<'a> IntoIterator for &'a WeiduBatchedInstallOrder {
   }
 }
 
+impl IntoIterator for WeiduBatchedInstallOrder {
+  type Item = WeiduBatchedComponents;
+  type IntoIter = IntoIter<WeiduBatchedComponents>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.0.into_iter()
+  }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -79,6 +88,7 @@ mod tests {
         batch_mode: true,
         batch_size: 5,
         batch_skip: vec!["setup-stratagems.tp2".into()],
+        ..Default::default()
       },
     )?;
     let expected = WeiduBatchedInstallOrder(vec![
@@ -90,6 +100,7 @@ mod tests {
         component_name: "test mod one".into(),
         sub_component: "".into(),
         version: "".into(),
+        label: "".into(),
       }]
       .into(),
       vec![WeiduComponent {
@@ -100,6 +111,7 @@ mod tests {
         component_name: "Add Baddies Portraits".into(),
         sub_component: "".into(),
         version: "".into(),
+        label: "".into(),
       }]
       .into(),
       vec![WeiduComponent {
@@ -110,6 +122,7 @@ mod tests {
         component_name: "test mod two".into(),
         sub_component: "".into(),
         version: "".into(),
+        label: "".into(),
       }]
       .into(),
       vec![WeiduComponent {
@@ -120,6 +133,7 @@ mod tests {
         component_name: "test mod with subcomponent information".into(),
         sub_component: "Standard installation".into(),
         version: "".into(),
+        label: "".into(),
       }]
       .into(),
       vec![WeiduComponent {
@@ -130,6 +144,7 @@ mod tests {
         component_name: "test mod with version".into(),
         sub_component: "".into(),
         version: "1.02".into(),
+        label: "".into(),
       }]
       .into(),
       vec![WeiduComponent {
@@ -140,6 +155,7 @@ mod tests {
         component_name: "test mod with both subcomponent information and version".into(),
         sub_component: "Casting speed only".into(),
         version: "v16".into(),
+        label: "".into(),
       }]
       .into(),
       vec![
@@ -151,6 +167,7 @@ mod tests {
           component_name: "Add Portraits".into(),
           sub_component: "".into(),
           version: "".into(),
+          label: "".into(),
         },
         WeiduComponent {
           tp_file: "Portraits.TP2".into(),
@@ -160,6 +177,7 @@ mod tests {
           component_name: "Add Baddies Portraits".into(),
           sub_component: "".into(),
           version: "".into(),
+          label: "".into(),
         },
         WeiduComponent {
           tp_file: "Portraits.TP2".into(),
@@ -169,6 +187,7 @@ mod tests {
           component_name: "Add Good Portraits".into(),
           sub_component: "".into(),
           version: "".into(),
+          label: "".into(),
         },
         WeiduComponent {
           tp_file: "Portraits.TP2".into(),
@@ -178,6 +197,7 @@ mod tests {
           component_name: "Add Bg1 Portraits".into(),
           sub_component: "".into(),
           version: "".into(),
+          label: "".into(),
         },
         WeiduComponent {
           tp_file: "Portraits.TP2".into(),
@@ -187,6 +207,7 @@ mod tests {
           component_name: "Add bg2 Portraits".into(),
           sub_component: "".into(),
           version: "".into(),
+          label: "".into(),
         },
         WeiduComponent {
           tp_file: "Portraits.TP2".into(),
@@ -196,6 +217,7 @@ mod tests {
           component_name: "Add bg3 Portraits".into(),
           sub_component: "".into(),
           version: "".into(),
+          label: "".into(),
         },
       ]
       .into(),
@@ -208,6 +230,7 @@ mod tests {
           component_name: "Add iwd Portraits".into(),
           sub_component: "".into(),
           version: "".into(),
+          label: "".into(),
         },
         WeiduComponent {
           tp_file: "Portraits.TP2".into(),
@@ -217,6 +240,7 @@ mod tests {
           component_name: "Add iwd2 Portraits".into(),
           sub_component: "".into(),
           version: "".into(),
+          label: "".into(),
         },
       ]
       .into(),
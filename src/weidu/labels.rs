@@ -0,0 +1,198 @@
+use std::{
+  collections::{HashMap, hash_map::Entry},
+  error::Error,
+  path::Path,
+};
+
+use crate::{
+  scan::components::list_components_json,
+  utils::mod_folder_present_in_game_directory,
+  weidu::{
+    batched_components::WeiduBatchedComponents,
+    component::WeiduComponent,
+    tp2::{Tp2, Tp2Component, find_tp2},
+    verify::LogEntry,
+  },
+};
+
+/// Components of the mods in a game directory, listed once per mod as listing runs weidu
+#[derive(Debug, Default)]
+pub(crate) struct Labels {
+  listed: HashMap<String, Vec<Tp2Component>>,
+}
+
+/// Asks weidu first, the tp2 is only read when this weidu does not report labels
+fn list(
+  component: &WeiduComponent,
+  game_directory: &Path,
+  weidu_binary: &Path,
+) -> Result<Vec<Tp2Component>, Box<dyn Error>> {
+  let available = match list_components_json(weidu_binary, game_directory, component) {
+    Ok(listed) if listed.iter().any(|component| !component.labels.is_empty()) => listed,
+    listed => {
+      log::debug!("Reading labels from the tp2, weidu listed {listed:?}");
+      match find_tp2(&game_directory.join(&component.name), &component.tp_file) {
+        Some(tp2) => Tp2::read(&tp2)?.components,
+        None => vec![],
+      }
+    },
+  };
+  if available
+    .iter()
+    .all(|component| component.labels.is_empty())
+  {
+    log::warn!(
+      "{} has no labels, installing by component number",
+      component.tp_file
+    );
+  }
+  Ok(available)
+}
+
+impl Labels {
+  fn available(
+    &mut self,
+    component: &WeiduComponent,
+    game_directory: &Path,
+    weidu_binary: &Path,
+  ) -> Result<&[Tp2Component], Box<dyn Error>> {
+    let listed = match self.listed.entry(component.tp_file.to_lowercase()) {
+      Entry::Occupied(entry) => entry.into_mut(),
+      Entry::Vacant(entry) => entry.insert(list(component, game_directory, weidu_binary)?),
+    };
+    Ok(listed)
+  }
+
+  /// The mod folder in the game directory was replaced, so its components are listed again
+  pub(crate) fn forget(&mut self, component: &WeiduComponent) {
+    self.listed.remove(&component.tp_file.to_lowercase());
+  }
+
+  /// Components labelled in the log are installed by label, numbers can change between versions
+  pub(crate) fn resolve(
+    &mut self,
+    components: &WeiduBatchedComponents,
+    game_directory: &Path,
+    weidu_binary: &Path,
+  ) -> Result<WeiduBatchedComponents, Box<dyn Error>> {
+    let first = match components.first() {
+      Some(first)
+        if components
+          .into_iter()
+          .any(|component| !component.label.is_empty()) =>
+      {
+        first
+      },
+      _ => return Ok(components.clone()),
+    };
+    let available = self.available(first, game_directory, weidu_binary)?;
+    let mut resolved = vec![];
+    for component in components {
+      let found = component.resolve_label(available)?;
+      if found.component != component.component {
+        log::warn!(
+          "{component} is labelled {}, which is now component #{}",
+          component.label,
+          found.component
+        );
+      }
+      resolved.push(found);
+    }
+    Ok(resolved.into())
+  }
+
+  /// The target log with the numbers weidu.log has for its labelled components.
+  /// Mods that are not in the game directory, or no longer have the label, keep the number in the log
+  pub(crate) fn resolve_entries(
+    &mut self,
+    entries: &[LogEntry],
+    game_directory: &Path,
+    weidu_binary: &Path,
+  ) -> Vec<LogEntry> {
+    entries
+      .iter()
+      .map(|entry| {
+        if entry.component.label.is_empty()
+          || !mod_folder_present_in_game_directory(game_directory, &entry.component.name)
+        {
+          return entry.clone();
+        }
+        match self
+          .available(&entry.component, game_directory, weidu_binary)
+          .and_then(|available| entry.component.resolve_label(available))
+        {
+          Ok(component) => LogEntry {
+            component,
+            ..entry.clone()
+          },
+          Err(err) => {
+            log::debug!("Comparing {} by number, {err}", entry.component);
+            entry.clone()
+          },
+        }
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::{error::Error, fs};
+
+  #[test]
+  fn resolves_target_entries_in_the_game_directory() -> Result<(), Box<dyn Error>> {
+    let game_directory = tempfile::tempdir()?;
+    let mod_folder = game_directory.path().join("LABELS");
+    fs::create_dir(&mod_folder)?;
+    fs::copy("fixtures/tp2/labels.tp2", mod_folder.join("labels.tp2"))?;
+    let target: Vec<LogEntry> = [
+      "~LABELS/LABELS.TP2~ #0 #1 // Tweak: v1 // @label LABELS_TWEAK",
+      "~LABELS/LABELS.TP2~ #0 #2 // Gone: v1 // @label LABELS_GONE",
+      "~OTHER/OTHER.TP2~ #0 #1 // Other: v1 // @label OTHER_TWEAK",
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(index, line)| {
+      Ok(LogEntry {
+        line: index + 1,
+        component: WeiduComponent::try_from(line.to_string())?,
+      })
+    })
+    .collect::<Result<_, Box<dyn Error>>>()?;
+
+    let mut labels = Labels::default();
+    let weidu_binary = game_directory.path().join("no-weidu");
+    let resolved = labels.resolve_entries(&target, game_directory.path(), &weidu_binary);
+    assert_eq!(
+      resolved
+        .iter()
+        .map(|entry| entry.component.component.as_str())
+        .collect::<Vec<_>>(),
+      vec!["10", "2", "1"]
+    );
+    assert_eq!(resolved[0].line, 1);
+
+    // Listed once, until the mod folder is replaced
+    fs::remove_file(mod_folder.join("labels.tp2"))?;
+    let batch = WeiduBatchedComponents::from(vec![target[0].component.clone()]);
+    let installed = labels.resolve(&batch, game_directory.path(), &weidu_binary)?;
+    assert_eq!(
+      installed
+        .first()
+        .map(|component| component.component.as_str()),
+      Some("10")
+    );
+    labels.forget(&target[0].component);
+    assert_eq!(
+      labels
+        .resolve(&batch, game_directory.path(), &weidu_binary)?
+        .first()
+        .map(|component| component.component.as_str()),
+      Some("1")
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,756 @@
+use std::{
+  error::Error,
+  fmt::Display,
+  fs,
+  path::{Path, PathBuf},
+};
+
+use crate::weidu::{component::WeiduComponent, verify::LogEntry};
+
+/// A line of a log that is not a component, with where it went wrong
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ParseError {
+  pub(crate) path: Option<PathBuf>,
+  /// 1 based, as an editor would show it
+  pub(crate) line: usize,
+  /// 1 based, in characters
+  pub(crate) column: usize,
+  pub(crate) text: String,
+  pub(crate) message: String,
+}
+
+impl Display for ParseError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match &self.path {
+      Some(path) => write!(
+        f,
+        "{}:{}:{}: {}",
+        path.display(),
+        self.line,
+        self.column,
+        self.message
+      )?,
+      None => write!(
+        f,
+        "line {}, column {}: {}",
+        self.line, self.column, self.message
+      )?,
+    }
+    // Tabs are kept so the caret lines up with the text above it
+    let padding: String = self
+      .text
+      .chars()
+      .take(self.column - 1)
+      .map(|char| if char == '\t' { '\t' } else { ' ' })
+      .collect();
+    let gutter = " ".repeat(self.line.to_string().len());
+    write!(f, "\n{} | {}\n{gutter} | {padding}^", self.line, self.text)
+  }
+}
+
+impl Error for ParseError {}
+
+/// Every line of a log that did not parse
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ParseErrors(pub(crate) Vec<ParseError>);
+
+impl Display for ParseErrors {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let errors: Vec<String> = self.0.iter().map(ToString::to_string).collect();
+    write!(f, "{}", errors.join("\n"))
+  }
+}
+
+impl Error for ParseErrors {}
+
+struct Cursor<'a> {
+  text: &'a str,
+  line: usize,
+  position: usize,
+}
+
+impl<'a> Cursor<'a> {
+  fn rest(&self) -> &'a str {
+    &self.text[self.position..]
+  }
+
+  fn column(&self) -> usize {
+    self.text[..self.position].chars().count() + 1
+  }
+
+  fn advance(&mut self, bytes: usize) {
+    self.position += bytes;
+  }
+
+  fn skip_whitespace(&mut self) {
+    let rest = self.rest();
+    self.advance(rest.len() - rest.trim_start().len());
+  }
+
+  fn eat(&mut self, expected: &str) -> bool {
+    let found = self.rest().starts_with(expected);
+    if found {
+      self.advance(expected.len());
+    }
+    found
+  }
+
+  fn error(&self, column: usize, message: impl Into<String>) -> ParseError {
+    ParseError {
+      path: None,
+      line: self.line,
+      column,
+      text: self.text.to_string(),
+      message: message.into(),
+    }
+  }
+
+  /// A `#` and the digits after it, with or without spaces between them
+  fn number(&mut self, name: &str) -> Result<String, ParseError> {
+    if !self.eat("#") {
+      return Err(self.error(self.column(), format!("expected # and the {name} number")));
+    }
+    self.skip_whitespace();
+    let digits: String = self
+      .rest()
+      .chars()
+      .take_while(char::is_ascii_digit)
+      .collect();
+    if digits.is_empty() {
+      return Err(self.error(self.column(), format!("expected the {name} number after #")));
+    }
+    self.advance(digits.len());
+    Ok(digits)
+  }
+}
+
+/// The tp2 path of a log line without its delimiters, `~` and `"` are both used
+pub(crate) fn tp2_path(text: &str) -> Option<&str> {
+  let text = text.trim_start();
+  let delimiter = text
+    .chars()
+    .next()
+    .filter(|char| matches!(char, '~' | '"'))?;
+  let text = &text[1..];
+  text.find(delimiter).map(|end| &text[..end])
+}
+
+/// Parses one component line, ie: `~FOLDER/SETUP-MOD.TP2~ #0 #1 // Name -> Sub: v1`
+pub(crate) fn parse_line(text: &str, line: usize) -> Result<WeiduComponent, ParseError> {
+  parse_annotated(text, line).map(|(component, _)| component)
+}
+
+/// Parses a component line along with any `//` annotations after its name other than the label
+fn parse_annotated(text: &str, line: usize) -> Result<(WeiduComponent, Vec<String>), ParseError> {
+  let mut cursor = Cursor {
+    text,
+    line,
+    position: 0,
+  };
+  cursor.skip_whitespace();
+  let start = cursor.column();
+  let Some(path) = tp2_path(cursor.rest()) else {
+    return Err(match cursor.rest().chars().next() {
+      Some(delimiter @ ('~' | '"')) => cursor.error(
+        start,
+        format!("the tp2 path is not closed, expected another {delimiter}"),
+      ),
+      _ => cursor.error(start, "expected the tp2 path, between ~ or \""),
+    });
+  };
+  let mut parts = path.rsplit(['/', '\\']);
+  let tp_file = parts.next().unwrap_or_default();
+  let Some(name) = parts.next() else {
+    return Err(cursor.error(
+      start + 1,
+      "expected the mod folder and tp2 file, ie: ~FOLDER/SETUP-MOD.TP2~",
+    ));
+  };
+  cursor.advance(path.len() + 2);
+  cursor.skip_whitespace();
+  let lang = cursor.number("language")?;
+  cursor.skip_whitespace();
+  let component = cursor.number("component")?;
+  cursor.skip_whitespace();
+  let comment = if cursor.rest().is_empty() {
+    ""
+  } else if cursor.eat("//") {
+    cursor.rest()
+  } else {
+    return Err(cursor.error(
+      cursor.column(),
+      "expected // and the component name, or the end of the line",
+    ));
+  };
+
+  let mut comment = comment.split("//");
+  let name_and_version = comment.next().unwrap_or_default().trim_end();
+  // Names can have colons of their own, weidu writes the version after the last one
+  let (name_and_sub_component, version) = match name_and_version.strip_suffix(':') {
+    Some(name) => (name, ""),
+    None => name_and_version
+      .rsplit_once(": ")
+      .unwrap_or((name_and_version, "")),
+  };
+  let mut component_name_sub_component = name_and_sub_component.split("->");
+  let component_name = component_name_sub_component
+    .next()
+    .unwrap_or_default()
+    .trim()
+    .to_string();
+  let sub_component = component_name_sub_component
+    .next()
+    .unwrap_or_default()
+    .trim()
+    .to_string();
+  let version = version.trim().to_string();
+  let mut label = String::new();
+  let mut annotations = vec![];
+  for annotation in comment.map(str::trim) {
+    match annotation.strip_prefix(WeiduComponent::LABEL_ANNOTATION) {
+      Some(found) if label.is_empty() => label = found.trim().to_string(),
+      _ => annotations.push(annotation.to_string()),
+    }
+  }
+
+  Ok((
+    WeiduComponent {
+      tp_file: tp_file.to_string(),
+      name: name.to_string(),
+      lang,
+      component,
+      component_name,
+      sub_component,
+      version,
+      label,
+    },
+    annotations,
+  ))
+}
+
+/// The header weidu writes at the top of every weidu.log
+pub(crate) const WEIDU_LOG_HEADER: [&str; 3] = [
+  "// Log of Currently Installed WeiDU Mods",
+  "// The top of the file is the 'oldest' mod",
+  "// ~TP2_File~ #language_number #component_number // [Subcomponent Name -> ] Component Name [ : Version]",
+];
+
+/// A component line of a log, written back as it was read unless it has been changed
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LogComponent {
+  pub(crate) component: WeiduComponent,
+  /// `//` sections after the name that are not the label, kept as they are
+  pub(crate) annotations: Vec<String>,
+  text: Option<String>,
+}
+
+impl LogComponent {
+  pub(crate) fn new(component: WeiduComponent, annotations: Vec<String>) -> Self {
+    Self {
+      component,
+      annotations,
+      text: None,
+    }
+  }
+
+  fn generate(&self) -> String {
+    let mut line = self.component.to_log_line();
+    if !self.annotations.is_empty() {
+      if self.component.log_comment().is_empty() {
+        line.push_str(" //");
+      }
+      for annotation in &self.annotations {
+        line.push_str(&format!(" // {annotation}"));
+      }
+    }
+    line
+  }
+}
+
+impl Display for LogComponent {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    // The fields are public, so the text read is only used if they still describe it
+    let unchanged = self.text.as_ref().filter(|text| {
+      parse_annotated(text, 1).is_ok_and(|(component, annotations)| {
+        component.to_log_line() == self.component.to_log_line() && annotations == self.annotations
+      })
+    });
+    match unchanged {
+      Some(text) => write!(f, "{text}"),
+      None => write!(f, "{}", self.generate()),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum LogLine {
+  Component(Box<LogComponent>),
+  /// Comments and empty lines, written back unchanged
+  Text(String),
+}
+
+impl Display for LogLine {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      LogLine::Component(component) => write!(f, "{component}"),
+      LogLine::Text(text) => write!(f, "{text}"),
+    }
+  }
+}
+
+/// Every line of a log, so it can be edited and written back without losing anything weidu ignores
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct WeiduLog {
+  pub(crate) lines: Vec<LogLine>,
+  pub(crate) line_ending: String,
+  /// Whether the last line ends with a line ending
+  pub(crate) final_line_ending: bool,
+}
+
+impl WeiduLog {
+  pub(crate) fn parse(source: &str, path: Option<&Path>) -> Result<Self, ParseErrors> {
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+    let line_ending = match source.find('\n') {
+      Some(end) if source[..end].ends_with('\r') => "\r\n",
+      _ => "\n",
+    };
+    let mut lines = vec![];
+    let mut errors = vec![];
+    for (index, text) in source.lines().enumerate() {
+      let text = text.strip_suffix('\r').unwrap_or(text);
+      if !is_entry(text) {
+        lines.push(LogLine::Text(text.to_string()));
+        continue;
+      }
+      match parse_annotated(text, index + 1) {
+        Ok((component, annotations)) => lines.push(LogLine::Component(Box::new(LogComponent {
+          component,
+          annotations,
+          text: Some(text.to_string()),
+        }))),
+        Err(err) => errors.push(ParseError {
+          path: path.map(Path::to_path_buf),
+          ..err
+        }),
+      }
+    }
+    if !errors.is_empty() {
+      return Err(ParseErrors(errors));
+    }
+    Ok(Self {
+      lines,
+      line_ending: line_ending.to_string(),
+      final_line_ending: source.is_empty() || source.ends_with('\n'),
+    })
+  }
+
+  pub(crate) fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+    Ok(Self::parse(&read_source(path)?, Some(path))?)
+  }
+
+  pub(crate) fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+    fs::write(path, self.to_string())
+      .map_err(|err| format!("Failed to write {path:?}, {err}").into())
+  }
+}
+
+/// A new log, with the header weidu writes
+impl<'a, T: IntoIterator<Item = &'a WeiduComponent>> From<T> for WeiduLog {
+  fn from(components: T) -> Self {
+    let mut lines: Vec<LogLine> = WEIDU_LOG_HEADER
+      .iter()
+      .map(|header| LogLine::Text(header.to_string()))
+      .collect();
+    lines.extend(
+      components.into_iter().map(|component| {
+        LogLine::Component(Box::new(LogComponent::new(component.clone(), vec![])))
+      }),
+    );
+    Self {
+      lines,
+      line_ending: "\n".to_string(),
+      final_line_ending: true,
+    }
+  }
+}
+
+impl Display for WeiduLog {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let lines: Vec<String> = self.lines.iter().map(ToString::to_string).collect();
+    write!(f, "{}", lines.join(&self.line_ending))?;
+    if self.final_line_ending && !self.lines.is_empty() {
+      write!(f, "{}", self.line_ending)?;
+    }
+    Ok(())
+  }
+}
+
+/// Comments and empty lines are left out, as weidu does
+pub(crate) fn is_entry(text: &str) -> bool {
+  let text = text.trim();
+  !text.is_empty() && !text.starts_with("//")
+}
+
+/// Every component line of a log, in order, whether it parsed or not
+pub(crate) fn parse_log(source: &str, path: Option<&Path>) -> Vec<Result<LogEntry, ParseError>> {
+  source
+    .strip_prefix('\u{feff}')
+    .unwrap_or(source)
+    .lines()
+    .enumerate()
+    .filter(|(_, text)| is_entry(text))
+    .map(|(index, text)| {
+      parse_line(text.trim_end_matches('\r'), index + 1)
+        .map(|component| LogEntry {
+          line: index + 1,
+          component,
+        })
+        .map_err(|err| ParseError {
+          path: path.map(Path::to_path_buf),
+          ..err
+        })
+    })
+    .collect()
+}
+
+/// Logs are written by weidu as utf-8, but editors on windows may save them as utf-16
+pub(crate) fn decode(bytes: &[u8]) -> String {
+  let utf16 = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+    let units: Vec<u16> = bytes
+      .as_chunks::<2>()
+      .0
+      .iter()
+      .map(|pair| from(*pair))
+      .collect();
+    String::from_utf16_lossy(&units)
+  };
+  if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+    return String::from_utf8_lossy(rest).into_owned();
+  }
+  if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+    return utf16(rest, u16::from_le_bytes);
+  }
+  if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+    return utf16(rest, u16::from_be_bytes);
+  }
+  // Without a byte order mark, ascii in utf-16 has a zero in every other byte
+  if bytes.len() >= 2 && bytes.len().is_multiple_of(2) {
+    if bytes.iter().skip(1).step_by(2).all(|byte| *byte == 0) {
+      return utf16(bytes, u16::from_le_bytes);
+    }
+    if bytes.iter().step_by(2).all(|byte| *byte == 0) {
+      return utf16(bytes, u16::from_be_bytes);
+    }
+  }
+  String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// The text of a log, whatever its encoding
+pub(crate) fn read_source(path: &Path) -> Result<String, Box<dyn Error>> {
+  let bytes = fs::read(path).map_err(|err| format!("Failed to open {path:?}, {err}"))?;
+  Ok(decode(&bytes))
+}
+
+/// Reads every component of a log, or every line that did not parse
+pub(crate) fn read_entries(path: &Path) -> Result<Vec<LogEntry>, Box<dyn Error>> {
+  let (entries, errors): (Vec<_>, Vec<_>) = parse_log(&read_source(path)?, Some(path))
+    .into_iter()
+    .partition(Result::is_ok);
+  if !errors.is_empty() {
+    return Err(Box::new(ParseErrors(
+      errors.into_iter().filter_map(Result::err).collect(),
+    )));
+  }
+  Ok(entries.into_iter().filter_map(Result::ok).collect())
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use crate::weidu::batched_components::WeiduBatchedComponents;
+  use pretty_assertions::assert_eq;
+  use proptest::{collection::vec, option, prelude::*};
+  use std::error::Error;
+
+  fn component(
+    name: &str,
+    tp_file: &str,
+    lang: &str,
+    number: &str,
+    component_name: &str,
+  ) -> WeiduComponent {
+    WeiduComponent {
+      tp_file: tp_file.into(),
+      name: name.into(),
+      lang: lang.into(),
+      component: number.into(),
+      component_name: component_name.into(),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn parses_the_weidu_log_grammar() -> Result<(), Box<dyn Error>> {
+    let source = "\u{feff}// Log of Currently Installed WeiDU Mods\r\n\
+      ~A/SETUP-A.TP2~ #0 #1 // Core -> Fast: v2 // @label A_FAST\r\n\
+      \"My Mod/setup-my mod.tp2\" #1 #2 // Spaces\r\n\
+      \t~B\\B.TP2~   # 0\t#3//Tight\r\n\
+      ~C/C.TP2~ #0#4\r\n\
+      ~BG1NPC/BG1NPC.TP2~ #0 #0 // The BG1 NPC Project: Required Modifications: v24\r\n\
+      \r\n";
+    let entries: Vec<LogEntry> = parse_log(source, None)
+      .into_iter()
+      .collect::<Result<_, _>>()?;
+    assert_eq!(
+      entries,
+      vec![
+        LogEntry {
+          line: 2,
+          component: WeiduComponent {
+            sub_component: "Fast".into(),
+            version: "v2".into(),
+            label: "A_FAST".into(),
+            ..component("A", "SETUP-A.TP2", "0", "1", "Core")
+          },
+        },
+        LogEntry {
+          line: 3,
+          component: component("My Mod", "setup-my mod.tp2", "1", "2", "Spaces"),
+        },
+        LogEntry {
+          line: 4,
+          component: component("B", "B.TP2", "0", "3", "Tight"),
+        },
+        LogEntry {
+          line: 5,
+          component: component("C", "C.TP2", "0", "4", ""),
+        },
+        LogEntry {
+          line: 6,
+          component: WeiduComponent {
+            version: "v24".into(),
+            ..component(
+              "BG1NPC",
+              "BG1NPC.TP2",
+              "0",
+              "0",
+              "The BG1 NPC Project: Required Modifications",
+            )
+          },
+        },
+      ]
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn reports_every_error_with_a_caret() -> Result<(), Box<dyn Error>> {
+    let file = tempfile::NamedTempFile::new()?;
+    fs::write(
+      file.path(),
+      "~A/A.TP2~ #0 #1 // Fine\n\
+      ~A/A.TP2~ #0 #x // Bad\n\
+      \t~A.TP2~ #0 #1\n\
+      ~A/A.TP2 #0 #1\n\
+      ~A/A.TP2~ #0 #1 trailing\n\
+      A/A.TP2 #0 #1\n",
+    )?;
+    let err = match read_entries(file.path()) {
+      Ok(_) => return Err("read a log with errors".into()),
+      Err(err) => err.to_string(),
+    };
+    let path = file.path().display();
+    assert_eq!(
+      err,
+      format!(
+        "{path}:2:15: expected the component number after #\n\
+        2 | ~A/A.TP2~ #0 #x // Bad\n  |               ^\n\
+        {path}:3:3: expected the mod folder and tp2 file, ie: ~FOLDER/SETUP-MOD.TP2~\n\
+        3 | \t~A.TP2~ #0 #1\n  | \t ^\n\
+        {path}:4:1: the tp2 path is not closed, expected another ~\n\
+        4 | ~A/A.TP2 #0 #1\n  | ^\n\
+        {path}:5:17: expected // and the component name, or the end of the line\n\
+        5 | ~A/A.TP2~ #0 #1 trailing\n  |                 ^\n\
+        {path}:6:1: expected the tp2 path, between ~ or \"\n\
+        6 | A/A.TP2 #0 #1\n  | ^"
+      )
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn decodes_utf16_logs() -> Result<(), Box<dyn Error>> {
+    let text = "~A/A.TP2~ #0 #1 // Caf\u{e9}\r\n";
+    let le: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let be: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+    assert_eq!(decode(&[vec![0xFF, 0xFE], le.clone()].concat()), text);
+    assert_eq!(decode(&[vec![0xFE, 0xFF], be.clone()].concat()), text);
+    assert_eq!(decode(&le), text);
+    assert_eq!(decode(&be), text);
+    assert_eq!(decode(text.as_bytes()), text);
+    Ok(())
+  }
+
+  #[test]
+  fn writes_logs_back_as_they_were_read() -> Result<(), Box<dyn Error>> {
+    let source = "// Log of Currently Installed WeiDU Mods\r\n\
+      \r\n\
+      ~a\\setup-a.tp2~   #0 #1 // Core: v1 // @label A_CORE // keep me\r\n\
+      // a note\r\n\
+      \"B B/B.TP2\"#0#2";
+    let mut log = WeiduLog::parse(source, None)?;
+    assert_eq!(log.to_string(), source);
+    if let Some(LogLine::Component(found)) = log.lines.get_mut(2) {
+      found.component.component = "7".into();
+    }
+    assert_eq!(
+      log.to_string(),
+      "// Log of Currently Installed WeiDU Mods\r\n\
+      \r\n\
+      ~a/setup-a.tp2~ #0 #7 // Core: v1 // @label A_CORE // keep me\r\n\
+      // a note\r\n\
+      \"B B/B.TP2\"#0#2"
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn writes_new_logs_with_the_weidu_header() -> Result<(), Box<dyn Error>> {
+    let components = WeiduBatchedComponents::from(vec![
+      WeiduComponent {
+        sub_component: "Fast".into(),
+        version: "v2".into(),
+        ..component("A", "SETUP-A.TP2", "0", "1", "Tweaks")
+      },
+      component("B", "B.TP2", "1", "0", ""),
+    ]);
+    assert_eq!(
+      WeiduLog::from(&components).to_string(),
+      format!(
+        "{}\n~A/SETUP-A.TP2~ #0 #1 // Tweaks -> Fast: v2\n~B/B.TP2~ #1 #0\n",
+        WEIDU_LOG_HEADER.join("\n")
+      )
+    );
+    Ok(())
+  }
+
+  /// Text between separators, without the characters the grammar splits on
+  fn text(pattern: &'static str) -> impl Strategy<Value = String> {
+    prop_oneof![Just(String::new()), pattern]
+  }
+
+  fn log_components() -> impl Strategy<Value = Vec<LogComponent>> {
+    let component = (
+      "[A-Za-z0-9_][A-Za-z0-9 _.-]{0,10}",
+      "(SETUP-)?[A-Za-z0-9_]{1,8}\\.TP2",
+      0u32..100,
+      0u32..1000,
+      text("[A-Za-z0-9(][A-Za-z0-9 ()',.!?:-]{0,15}[A-Za-z0-9)]"),
+      text("[A-Za-z0-9][A-Za-z0-9 :]{0,8}"),
+      text("v?[0-9]{1,2}(\\.[0-9]{1,2})?"),
+      text("[A-Z_]{1,10}"),
+      vec("[a-z#][a-z ]{0,8}[a-z]", 0..3),
+    );
+    vec(
+      component.prop_map(
+        |(
+          name,
+          tp_file,
+          lang,
+          number,
+          component_name,
+          sub_component,
+          version,
+          label,
+          annotations,
+        )| {
+          LogComponent::new(
+            WeiduComponent {
+              tp_file,
+              name,
+              lang: lang.to_string(),
+              component: number.to_string(),
+              component_name,
+              sub_component: sub_component.trim().to_string(),
+              version,
+              label,
+            },
+            annotations,
+          )
+        },
+      ),
+      0..8,
+    )
+  }
+
+  /// Every field, as WeiduComponent only compares some of them
+  fn fields(log: &WeiduLog) -> Vec<(String, Vec<String>)> {
+    log
+      .lines
+      .iter()
+      .filter_map(|line| match line {
+        LogLine::Component(found) => {
+          Some((format!("{:?}", found.component), found.annotations.clone()))
+        },
+        LogLine::Text(_) => None,
+      })
+      .collect()
+  }
+
+  proptest! {
+    #[test]
+    fn parse_write_parse_is_identity(
+      components in log_components(),
+      comments in vec(option::of("// [a-z ~#]{0,12}"), 8),
+      crlf in any::<bool>(),
+    ) {
+      let mut log = WeiduLog::from(&[]);
+      for (found, comment) in components.into_iter().zip(comments) {
+        log.lines.push(LogLine::Component(Box::new(found)));
+        if let Some(comment) = comment {
+          log.lines.push(LogLine::Text(comment));
+        }
+      }
+      if crlf {
+        log.line_ending = "\r\n".into();
+      }
+      let written = log.to_string();
+      let parsed = WeiduLog::parse(&written, None)?;
+      prop_assert_eq!(fields(&parsed), fields(&log));
+      prop_assert_eq!(parsed.to_string(), written.clone());
+      prop_assert_eq!(WeiduLog::parse(&parsed.to_string(), None)?, parsed);
+    }
+
+    #[test]
+    fn unchanged_lines_are_written_back_exactly(
+      lines in vec(
+        (
+          "[ \t]{0,2}",
+          prop_oneof![Just(('~', '/')), Just(('"', '\\'))],
+          "[A-Za-z][A-Za-z ]{0,6}",
+          "[A-Z]{1,6}\\.(TP2|tp2)",
+          "[ \t]{0,3}#[ ]{0,2}[0-9]{1,2}[ \t]{0,3}#[ ]{0,2}[0-9]{1,3}[ \t]{0,3}",
+          option::of("//[ A-Za-z:>-]{0,20}(//[ @a-z]{0,10})?"),
+        ),
+        0..8,
+      ),
+      final_line_ending in any::<bool>(),
+    ) {
+      let lines: Vec<String> = lines
+        .into_iter()
+        .map(|(indent, (delimiter, separator), name, tp_file, numbers, comment)| {
+          format!(
+            "{indent}{delimiter}{name}{separator}{tp_file}{delimiter}{numbers}{}",
+            comment.unwrap_or_default()
+          )
+        })
+        .collect();
+      let mut source = lines.join("\n");
+      if final_line_ending && !lines.is_empty() {
+        source.push('\n');
+      }
+      prop_assert_eq!(WeiduLog::parse(&source, None)?.to_string(), source);
+    }
+  }
+}
@@ -2,3 +2,7 @@ pub(crate) mod batched_components;
 pub(crate) mod component;
 pub(crate) mod install_block;
 pub(crate) mod install_order;
+pub(crate) mod labels;
+pub(crate) mod log_file;
+pub(crate) mod tp2;
+pub(crate) mod verify;
@@ -0,0 +1,809 @@
+use std::{
+  collections::HashMap,
+  error::Error,
+  fmt::Display,
+  fs,
+  path::{Path, PathBuf},
+};
+
+/// A word or a string from a tp2 or tra, comments are dropped
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+  Word(String),
+  Text(String),
+}
+
+impl Token {
+  fn is_word(&self, word: &str) -> bool {
+    matches!(self, Token::Word(value) if value == word)
+  }
+
+  fn value(&self) -> &str {
+    match self {
+      Token::Word(value) | Token::Text(value) => value,
+    }
+  }
+
+  fn tp2_string(&self) -> Option<Tp2String> {
+    match self {
+      Token::Text(value) => Some(Tp2String::Literal(value.clone())),
+      Token::Word(value) => value
+        .strip_prefix('@')
+        .and_then(|number| number.parse().ok())
+        .map(Tp2String::Tra),
+    }
+  }
+}
+
+impl Display for Token {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Token::Word(value) => write!(f, "{value}"),
+      Token::Text(value) => write!(f, "~{value}~"),
+    }
+  }
+}
+
+pub(crate) fn tokenize(source: &str) -> Vec<Token> {
+  let mut tokens = vec![];
+  let mut rest = source;
+  while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+    rest = &rest[start..];
+    let (token, len) = if rest.starts_with("//") {
+      (None, rest.find('\n').unwrap_or(rest.len()))
+    } else if rest.starts_with("/*") {
+      (None, rest.find("*/").map_or(rest.len(), |end| end + 2))
+    } else if rest.starts_with(['(', ')']) {
+      (Some(Token::Word(rest[..1].to_string())), 1)
+    } else if let Some(delimiter) = ["~~~~~", "~", "\"", "%"]
+      .into_iter()
+      .find(|delimiter| rest.starts_with(delimiter))
+    {
+      let body = &rest[delimiter.len()..];
+      let end = body.find(delimiter).unwrap_or(body.len());
+      (
+        Some(Token::Text(body[..end].to_string())),
+        (delimiter.len() * 2 + end).min(rest.len()),
+      )
+    } else {
+      let end = rest
+        .find(|c: char| c.is_whitespace() || matches!(c, '~' | '"' | '(' | ')'))
+        .unwrap_or(rest.len());
+      let end = ["//", "/*"]
+        .into_iter()
+        .filter_map(|comment| rest[..end].find(comment))
+        .min()
+        .unwrap_or(end);
+      (Some(Token::Word(rest[..end].to_string())), end)
+    };
+    tokens.extend(token);
+    rest = &rest[len..];
+  }
+  tokens
+}
+
+/// A string in a tp2, either written out or a reference into the tra files of a language
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Tp2String {
+  Literal(String),
+  Tra(u32),
+}
+
+impl Default for Tp2String {
+  fn default() -> Self {
+    Tp2String::Literal(String::new())
+  }
+}
+
+impl Tp2String {
+  /// Unresolved references are kept as `@N`, as weidu shows them
+  pub(crate) fn resolve(&self, tra: &Tra) -> String {
+    match self {
+      Tp2String::Literal(value) => value.clone(),
+      Tp2String::Tra(number) => tra
+        .get(*number)
+        .map_or(format!("@{number}"), ToString::to_string),
+    }
+  }
+}
+
+/// Strings of one language, read from its tra files
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Tra(HashMap<u32, String>);
+
+impl Tra {
+  /// `@N = ~text~`, a second string for female speakers and a sound are ignored
+  pub(crate) fn parse(source: &str) -> Self {
+    let mut strings = HashMap::new();
+    let tokens = tokenize(source);
+    let mut tokens = tokens.iter().peekable();
+    while let Some(token) = tokens.next() {
+      let number = match token {
+        Token::Word(word) => word
+          .strip_prefix('@')
+          .map(|number| number.trim_end_matches('='))
+          .and_then(|number| number.parse().ok()),
+        Token::Text(_) => None,
+      };
+      if let Some(number) = number {
+        if tokens.peek().is_some_and(|token| token.is_word("=")) {
+          tokens.next();
+        }
+        if let Some(Token::Text(text)) = tokens.peek() {
+          strings.insert(number, text.clone());
+        }
+      }
+    }
+    Self(strings)
+  }
+
+  pub(crate) fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+    let bytes = fs::read(path).map_err(|err| format!("Failed to read {path:?}, {err}"))?;
+    Ok(Self::parse(&String::from_utf8_lossy(&bytes)))
+  }
+
+  pub(crate) fn get(&self, number: u32) -> Option<&str> {
+    self.0.get(&number).map(String::as_str)
+  }
+
+  fn extend(&mut self, other: Tra) {
+    self.0.extend(other.0);
+  }
+}
+
+/// A LANGUAGE block, its position is the language number in weidu.log
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Tp2Language {
+  pub(crate) name: String,
+  pub(crate) directory: String,
+  pub(crate) tra_files: Vec<String>,
+}
+
+/// A component number, or the label weidu looks it up by
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ComponentId {
+  Number(u32),
+  Label(String),
+}
+
+impl Display for ComponentId {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ComponentId::Number(number) => write!(f, "#{number}"),
+      ComponentId::Label(label) => write!(f, "{label}"),
+    }
+  }
+}
+
+/// REQUIRE_COMPONENT or FORBID_COMPONENT, with the message weidu shows when it is not met
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ComponentRef {
+  pub(crate) tp2: String,
+  pub(crate) component: ComponentId,
+  pub(crate) message: Tp2String,
+}
+
+/// REQUIRE_PREDICATE, the condition is kept as tokens as only weidu can evaluate it
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Predicate {
+  pub(crate) condition: Vec<Token>,
+  pub(crate) message: Tp2String,
+}
+
+impl Predicate {
+  /// Components a condition of only MOD_IS_INSTALLED and AND needs, others are left to weidu.
+  /// Only components given as a number or by ID_OF_LABEL are returned, variables are left to weidu too
+  pub(crate) fn required_components(&self) -> Vec<(String, ComponentId)> {
+    let negated = self.condition.iter().any(
+      |token| matches!(token, Token::Word(word) if ["OR", "||", "NOT", "!"].contains(&word.as_str())),
+    );
+    if negated {
+      return vec![];
+    }
+    let condition = &self.condition;
+    let token = |index: usize| condition.get(index).map(Token::value);
+    condition
+      .iter()
+      .enumerate()
+      .filter(|(_, token)| token.is_word("MOD_IS_INSTALLED"))
+      .filter_map(|(index, _)| {
+        let component = match (token(index + 2)?, token(index + 3)) {
+          ("ID_OF_LABEL", _) => ComponentId::Label(token(index + 4)?.to_string()),
+          ("(", Some("ID_OF_LABEL")) => ComponentId::Label(token(index + 5)?.to_string()),
+          (number, _) => ComponentId::Number(number.trim().parse().ok()?),
+        };
+        Some((token(index + 1)?.to_string(), component))
+      })
+      .collect()
+  }
+}
+
+impl Display for Predicate {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let condition: Vec<String> = self.condition.iter().map(ToString::to_string).collect();
+    write!(f, "{}", condition.join(" "))
+  }
+}
+
+/// A component as weidu numbers it, with the flags from its header
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Tp2Component {
+  pub(crate) number: u32,
+  pub(crate) labels: Vec<String>,
+  pub(crate) name: Tp2String,
+  pub(crate) subcomponent: Option<Tp2String>,
+  pub(crate) groups: Vec<Tp2String>,
+  pub(crate) requires: Vec<ComponentRef>,
+  pub(crate) forbids: Vec<ComponentRef>,
+  pub(crate) predicates: Vec<Predicate>,
+}
+
+impl Tp2Component {
+  pub(crate) fn has_label(&self, label: &str) -> bool {
+    self
+      .labels
+      .iter()
+      .any(|value| value.eq_ignore_ascii_case(label))
+  }
+}
+
+/// What weidu would read from a tp2 before installing anything
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Tp2 {
+  pub(crate) backup: String,
+  pub(crate) author: String,
+  pub(crate) version: Option<Tp2String>,
+  pub(crate) languages: Vec<Tp2Language>,
+  pub(crate) components: Vec<Tp2Component>,
+}
+
+/// Predicate functions and how many arguments they take, so a string argument is not read as
+/// the message that follows the predicate
+const PREDICATE_FUNCTIONS: [(&str, usize); 14] = [
+  ("MOD_IS_INSTALLED", 2),
+  ("ID_OF_LABEL", 2),
+  ("GAME_IS", 1),
+  ("GAME_INCLUDES", 1),
+  ("ENGINE_IS", 1),
+  ("FILE_EXISTS", 1),
+  ("FILE_EXISTS_IN_GAME", 1),
+  ("DIRECTORY_EXISTS", 1),
+  ("VARIABLE_IS_SET", 1),
+  ("IS_AN_INT", 1),
+  ("FILE_CONTAINS", 2),
+  ("FILE_CONTAINS_EVALUATED", 2),
+  ("FILE_MD5", 2),
+  ("FILE_SIZE", 2),
+];
+
+const PREDICATE_OPERATORS: [&str; 18] = [
+  "AND",
+  "OR",
+  "NOT",
+  "&&",
+  "||",
+  "!",
+  "=",
+  "==",
+  "!=",
+  "<",
+  ">",
+  "<=",
+  ">=",
+  "STRING_EQUAL",
+  "STRING_EQUAL_CASE",
+  "STRING_COMPARE",
+  "STRING_COMPARE_CASE",
+  "STRING_MATCHES_REGEXP",
+];
+
+/// Statements whose body is the next BEGIN ... END, so that BEGIN never starts a component
+const BLOCK_STATEMENTS: [&str; 36] = [
+  "ACTION_IF",
+  "PATCH_IF",
+  "ELSE",
+  "ACTION_FOR_EACH",
+  "PATCH_FOR_EACH",
+  "ACTION_PHP_EACH",
+  "PHP_EACH",
+  "ACTION_BASH_FOR",
+  "PATCH_BASH_FOR",
+  "OUTER_FOR",
+  "FOR",
+  "OUTER_WHILE",
+  "WHILE",
+  "ACTION_DEFINE_ARRAY",
+  "DEFINE_ARRAY",
+  "ACTION_DEFINE_ASSOCIATIVE_ARRAY",
+  "DEFINE_ASSOCIATIVE_ARRAY",
+  "ACTION_WITH_TRA",
+  "WITH_TRA",
+  "ACTION_TIME",
+  "PATCH_TIME",
+  "WITH_SCOPE",
+  "PATCH_WITH_SCOPE",
+  "INNER_ACTION",
+  "INNER_PATCH",
+  "INNER_PATCH_FILE",
+  "INNER_PATCH_SAVE",
+  "OUTER_PATCH",
+  "OUTER_PATCH_SAVE",
+  "OUTER_INNER_PATCH",
+  "OUTER_INNER_PATCH_SAVE",
+  "REPLACE_EVALUATE",
+  "DEFINE_ACTION_FUNCTION",
+  "DEFINE_PATCH_FUNCTION",
+  "DEFINE_ACTION_MACRO",
+  "DEFINE_PATCH_MACRO",
+];
+
+/// Statements closed by an END of their own, without a BEGIN
+const END_STATEMENTS: [&str; 9] = [
+  "ALWAYS",
+  "LAF",
+  "LPF",
+  "LAUNCH_ACTION_FUNCTION",
+  "LAUNCH_PATCH_FUNCTION",
+  "ACTION_MATCH",
+  "PATCH_MATCH",
+  "ACTION_TRY",
+  "PATCH_TRY",
+];
+
+struct Parser {
+  tokens: Vec<Token>,
+  index: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.index)
+  }
+
+  fn next(&mut self) -> Option<Token> {
+    let token = self.tokens.get(self.index).cloned();
+    self.index += 1;
+    token
+  }
+
+  fn next_string(&mut self) -> Option<Tp2String> {
+    let string = self.peek()?.tp2_string()?;
+    self.index += 1;
+    Some(string)
+  }
+
+  /// An optional condition in brackets, after SUBCOMPONENT or GROUP
+  fn skip_condition(&mut self) {
+    if self.peek().is_some_and(|token| token.is_word("(")) {
+      self.condition(true);
+    }
+  }
+
+  /// One argument of a predicate function, a bracketed argument is read as a whole
+  fn argument(&mut self) -> Vec<Token> {
+    if self.peek().is_some_and(|token| token.is_word("(")) {
+      return self.condition(true);
+    }
+    self.next().into_iter().collect()
+  }
+
+  /// Tokens up to the string that follows a condition, or the closing bracket if bracketed
+  fn condition(&mut self, bracketed: bool) -> Vec<Token> {
+    let mut condition: Vec<Token> = vec![];
+    let mut depth: usize = 0;
+    while let Some(token) = self.peek() {
+      let operand = condition
+        .last()
+        .is_some_and(|last| PREDICATE_OPERATORS.contains(&last.value()));
+      if depth == 0 && !condition.is_empty() && !operand && token.tp2_string().is_some() {
+        break;
+      }
+      let token = token.clone();
+      self.index += 1;
+      if token.is_word("(") {
+        depth += 1;
+      } else if token.is_word(")") {
+        // A stray closing bracket would otherwise keep the condition open to the end of the file
+        depth = depth.saturating_sub(1);
+      }
+      let arguments = PREDICATE_FUNCTIONS
+        .iter()
+        .find(|(function, _)| token.is_word(function))
+        .map_or(0, |(_, arguments)| *arguments);
+      condition.push(token);
+      for _ in 0..arguments {
+        condition.extend(self.argument());
+      }
+      if bracketed && depth == 0 {
+        break;
+      }
+    }
+    condition
+  }
+
+  fn component_ref(&mut self) -> Option<ComponentRef> {
+    let tp2 = self.next()?.value().to_string();
+    let component = match self.next()? {
+      Token::Word(word) if word == "ID_OF_LABEL" => {
+        self.next();
+        ComponentId::Label(self.next()?.value().to_string())
+      },
+      token => match token.value().trim().parse() {
+        Ok(number) => ComponentId::Number(number),
+        Err(_) => ComponentId::Label(token.value().to_string()),
+      },
+    };
+    Some(ComponentRef {
+      tp2,
+      component,
+      message: self.next_string().unwrap_or_default(),
+    })
+  }
+
+  fn language(&mut self) -> Tp2Language {
+    let mut language = Tp2Language::default();
+    let mut strings = vec![];
+    while let Some(Token::Text(text)) = self.peek() {
+      strings.push(text.clone());
+      self.index += 1;
+    }
+    let mut strings = strings.into_iter();
+    language.name = strings.next().unwrap_or_default();
+    language.directory = strings.next().unwrap_or_default();
+    language.tra_files = strings.collect();
+    language
+  }
+
+  /// Reads flags until the first token of the component body
+  fn header(&mut self, component: &mut Tp2Component) {
+    while let Some(Token::Word(flag)) = self.peek().cloned() {
+      self.index += 1;
+      match flag.as_str() {
+        "DESIGNATED" => {
+          if let Some(number) = self.peek().and_then(|n| n.value().parse().ok()) {
+            component.number = number;
+            self.index += 1;
+          }
+        },
+        "LABEL" => component
+          .labels
+          .extend(self.next().map(|l| l.value().to_string())),
+        "SUBCOMPONENT" | "FORCED_SUBCOMPONENT" => {
+          component.subcomponent = self.next_string();
+          self.skip_condition();
+        },
+        "GROUP" => {
+          component.groups.extend(self.next_string());
+          self.skip_condition();
+        },
+        "REQUIRE_COMPONENT" => component.requires.extend(self.component_ref()),
+        "FORBID_COMPONENT" => component.forbids.extend(self.component_ref()),
+        "REQUIRE_PREDICATE" => {
+          let condition = self.condition(false);
+          component.predicates.push(Predicate {
+            condition,
+            message: self.next_string().unwrap_or_default(),
+          });
+        },
+        "DEPRECATED" | "METADATA" => {
+          self.next();
+        },
+        "INSTALL_BY_DEFAULT" | "NO_LOG_RECORD" => {},
+        _ => {
+          self.index -= 1;
+          return;
+        },
+      }
+    }
+  }
+}
+
+impl Tp2 {
+  /// A component starts with BEGIN and its name outside of any block, ALWAYS and the bodies of
+  /// statements are skipped. Numbers follow on from the previous component unless DESIGNATED
+  pub(crate) fn parse(source: &str) -> Self {
+    let mut tp2 = Tp2::default();
+    let mut parser = Parser {
+      tokens: tokenize(source),
+      index: 0,
+    };
+    // Blocks the parser is in, and whether the next BEGIN opens the body of a statement
+    let mut depth: usize = 0;
+    let mut statement = false;
+    while let Some(token) = parser.next() {
+      let Token::Word(word) = token else {
+        continue;
+      };
+      match word.as_str() {
+        "BEGIN" if depth > 0 || statement => {
+          depth += 1;
+          statement = false;
+        },
+        "BEGIN" => {
+          let Some(name) = parser.peek().and_then(Token::tp2_string) else {
+            depth += 1;
+            continue;
+          };
+          parser.index += 1;
+          let mut component = Tp2Component {
+            number: tp2.components.last().map_or(0, |last| last.number + 1),
+            name,
+            ..Default::default()
+          };
+          parser.header(&mut component);
+          tp2.components.push(component);
+        },
+        // A stray END would otherwise hide every component after it
+        "END" => depth = depth.saturating_sub(1),
+        word if END_STATEMENTS.contains(&word) => depth += 1,
+        word if BLOCK_STATEMENTS.contains(&word) => statement = true,
+        // Everything below is in the preamble, before the first component
+        _ if !tp2.components.is_empty() || depth > 0 => {},
+        "BACKUP" => {
+          tp2.backup = parser
+            .next()
+            .map(|t| t.value().to_string())
+            .unwrap_or_default()
+        },
+        "AUTHOR" | "SUPPORT" => {
+          tp2.author = parser
+            .next()
+            .map(|t| t.value().to_string())
+            .unwrap_or_default()
+        },
+        "VERSION" => tp2.version = parser.next_string(),
+        "LANGUAGE" => tp2.languages.push(parser.language()),
+        _ => {},
+      }
+    }
+    tp2
+  }
+
+  pub(crate) fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+    let bytes = fs::read(path).map_err(|err| format!("Failed to read {path:?}, {err}"))?;
+    Ok(Self::parse(&String::from_utf8_lossy(&bytes)))
+  }
+
+  /// Strings of a language, tra paths are relative to the folder the mod folder is in
+  pub(crate) fn tra(&self, language: usize, mod_folder: &Path) -> Tra {
+    let mut tra = Tra::default();
+    let Some(language) = self.languages.get(language) else {
+      return tra;
+    };
+    let mod_root = mod_folder.parent().unwrap_or(mod_folder);
+    let folder_name = mod_folder.file_name().unwrap_or_default().to_string_lossy();
+    for tra_file in &language.tra_files {
+      let relative = tra_file
+        .replace("%MOD_FOLDER%", &folder_name)
+        .replace("%LANGUAGE%", &language.directory);
+      match find_path(mod_root, &relative).map(|path| Tra::read(&path)) {
+        Some(Ok(strings)) => tra.extend(strings),
+        Some(Err(err)) => log::warn!("{err}"),
+        None => log::warn!("Could not find {relative:?} in {mod_root:?}"),
+      }
+    }
+    tra
+  }
+}
+
+/// Follows a relative path ignoring case, mods are written on case insensitive file systems
+fn find_path(root: &Path, relative: &str) -> Option<PathBuf> {
+  relative
+    .split(['/', '\\'])
+    .filter(|part| !part.is_empty() && *part != ".")
+    .try_fold(root.to_path_buf(), |path, part| {
+      let exact = path.join(part);
+      if exact.exists() {
+        return Some(exact);
+      }
+      fs::read_dir(&path)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|candidate| {
+          candidate
+            .file_name()
+            .is_some_and(|name| name.eq_ignore_ascii_case(part))
+        })
+    })
+}
+
+/// Finds the tp2 in a mod folder, ignoring case as the log and the file system may disagree
+pub(crate) fn find_tp2(mod_folder: &Path, tp_file: &str) -> Option<PathBuf> {
+  find_path(mod_folder, tp_file)
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::error::Error;
+
+  #[test]
+  fn tokenizes_strings_and_comments() -> Result<(), Box<dyn Error>> {
+    assert_eq!(
+      tokenize("BEGIN ~a // b~ /* c */ LABEL \"d\"// e\n%f% ~~~~~g~h~~~~~ !(@1)"),
+      vec![
+        Token::Word("BEGIN".into()),
+        Token::Text("a // b".into()),
+        Token::Word("LABEL".into()),
+        Token::Text("d".into()),
+        Token::Text("f".into()),
+        Token::Text("g~h".into()),
+        Token::Word("!".into()),
+        Token::Word("(".into()),
+        Token::Word("@1".into()),
+        Token::Word(")".into()),
+      ]
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn numbers_and_labels_components() -> Result<(), Box<dyn Error>> {
+    let components = Tp2::read(Path::new("fixtures/tp2/labels.tp2"))?.components;
+    assert_eq!(
+      components
+        .iter()
+        .map(|component| (component.number, component.labels.clone()))
+        .collect::<Vec<_>>(),
+      vec![
+        (0, vec!["LABELS_CORE".into()]),
+        (10, vec!["LABELS_TWEAK".into()]),
+        (11, vec![]),
+      ]
+    );
+    assert_eq!(
+      find_tp2(Path::new("fixtures/tp2"), "LABELS.TP2"),
+      Some(PathBuf::from("fixtures/tp2/labels.tp2"))
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn skips_blocks_in_actions() -> Result<(), Box<dyn Error>> {
+    let tp2 = Tp2::read(Path::new("fixtures/tp2/blocks.tp2"))?;
+    assert_eq!(
+      tp2
+        .components
+        .iter()
+        .map(|component| (component.number, component.labels.clone()))
+        .collect::<Vec<_>>(),
+      vec![
+        (0, vec!["BLOCKS_CORE".into()]),
+        (1, vec!["BLOCKS_TWEAK".into()]),
+      ]
+    );
+    assert_eq!(tp2.version, Some(Tp2String::Literal("v1".into())));
+    Ok(())
+  }
+
+  #[test]
+  fn reads_bracketed_predicate_arguments() -> Result<(), Box<dyn Error>> {
+    let tp2 = Tp2::parse(
+      "BEGIN ~One~ REQUIRE_PREDICATE MOD_IS_INSTALLED ~x.tp2~ (ID_OF_LABEL ~x.tp2~ ~LBL~) ~Needs x~\n\
+      COPY ~a~ ~override~\n\
+      BEGIN ~Two~ REQUIRE_PREDICATE ) GAME_IS ~bg2ee~ ~Stray~\n\
+      BEGIN ~Three~\n",
+    );
+    assert_eq!(tp2.components.len(), 3);
+    assert_eq!(
+      tp2.components[0].predicates,
+      vec![Predicate {
+        condition: tokenize("MOD_IS_INSTALLED ~x.tp2~ (ID_OF_LABEL ~x.tp2~ ~LBL~)"),
+        message: Tp2String::Literal("Needs x".into()),
+      }]
+    );
+    assert_eq!(
+      tp2.components[1].predicates[0].message,
+      Tp2String::Literal("Stray".into())
+    );
+    assert_eq!(
+      tp2.components[0].predicates[0].required_components(),
+      vec![("x.tp2".to_string(), ComponentId::Label("LBL".into()))]
+    );
+    let predicates = Tp2::parse(
+      "BEGIN ~One~ REQUIRE_PREDICATE MOD_IS_INSTALLED ~x.tp2~ ~3~ AND MOD_IS_INSTALLED ~y.tp2~ %comp% ~Needs~",
+    )
+    .components[0]
+      .predicates
+      .clone();
+    assert_eq!(
+      predicates[0].required_components(),
+      vec![("x.tp2".to_string(), ComponentId::Number(3))]
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn reads_metadata() -> Result<(), Box<dyn Error>> {
+    let mod_folder = Path::new("fixtures/tp2/metadata");
+    let tp2 = Tp2::read(&mod_folder.join("setup-metadata.tp2"))?;
+    assert_eq!(tp2.backup, "weidu_external/backup/metadata");
+    assert_eq!(tp2.author, "someone");
+    assert_eq!(tp2.version, Some(Tp2String::Literal("v3".into())));
+    assert_eq!(
+      tp2.languages,
+      vec![
+        Tp2Language {
+          name: "English".into(),
+          directory: "english".into(),
+          tra_files: vec!["metadata/tra/english/setup.tra".into()],
+        },
+        Tp2Language {
+          name: "Deutsch".into(),
+          directory: "german".into(),
+          tra_files: vec![
+            "%MOD_FOLDER%/tra/english/setup.tra".into(),
+            "%MOD_FOLDER%/tra/%LANGUAGE%/setup.tra".into(),
+          ],
+        },
+      ]
+    );
+    assert_eq!(
+      tp2.components[1],
+      Tp2Component {
+        number: 1,
+        labels: vec!["METADATA_FAST".into()],
+        name: Tp2String::Tra(11),
+        subcomponent: Some(Tp2String::Tra(10)),
+        groups: vec![Tp2String::Literal("Tweaks".into())],
+        requires: vec![ComponentRef {
+          tp2: "setup-metadata.tp2".into(),
+          component: ComponentId::Number(0),
+          message: Tp2String::Tra(20),
+        }],
+        forbids: vec![ComponentRef {
+          tp2: "setup-other.tp2".into(),
+          component: ComponentId::Label("OTHER_FAST".into()),
+          message: Tp2String::Literal("Conflicts with other".into()),
+        }],
+        predicates: vec![Predicate {
+          condition: tokenize("GAME_IS ~bg2ee eet~ AND !(MOD_IS_INSTALLED ~setup-x.tp2~ ~3~)"),
+          message: Tp2String::Tra(21),
+        }],
+      }
+    );
+    assert_eq!(
+      tp2.components[1].predicates[0].to_string(),
+      "GAME_IS ~bg2ee eet~ AND ! ( MOD_IS_INSTALLED ~setup-x.tp2~ ~3~ )"
+    );
+
+    let english = tp2.tra(0, mod_folder);
+    let german = tp2.tra(1, mod_folder);
+    let names = |tra: &Tra| {
+      tp2
+        .components
+        .iter()
+        .map(|component| component.name.resolve(tra))
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&english), vec!["Core", "Fast", "Slow", "@99"]);
+    assert_eq!(
+      tp2.components[2].groups,
+      vec![Tp2String::Literal("Tweaks".into())]
+    );
+    assert_eq!(
+      tp2.components[1]
+        .subcomponent
+        .as_ref()
+        .map(|subcomponent| subcomponent.resolve(&english)),
+      Some("Speed".into())
+    );
+    assert_eq!(
+      tp2.components[1].predicates[0].message.resolve(&english),
+      r#"Needs "bg2ee""#
+    );
+    assert_eq!(names(&german), vec!["Kern", "Schnell", "Langsam", "@99"]);
+    assert_eq!(
+      tp2
+        .components
+        .iter()
+        .map(|component| component.number)
+        .collect::<Vec<_>>(),
+      vec![0, 1, 2, 20]
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,576 @@
+use std::{error::Error, fmt::Display, path::Path};
+
+use serde_derive::Serialize;
+
+use crate::{
+  config::args::Verify,
+  weidu::{
+    batched_components::WeiduBatchedComponents, component::WeiduComponent, log_file::read_entries,
+  },
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct LogEntry {
+  /// 1 based, as an editor would show it
+  pub(crate) line: usize,
+  pub(crate) component: WeiduComponent,
+}
+
+/// Unlike WeiduBatchedComponents this keeps duplicates and where each component came from
+pub(crate) fn read_log(path: &Path) -> Result<Vec<LogEntry>, Box<dyn Error>> {
+  read_entries(path)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Divergence {
+  Duplicate {
+    installed_line: usize,
+    first_line: usize,
+    component: WeiduComponent,
+  },
+  Missing {
+    target_line: usize,
+    component: WeiduComponent,
+  },
+  Extra {
+    installed_line: usize,
+    component: WeiduComponent,
+  },
+  OutOfOrder {
+    target_line: usize,
+    installed_line: usize,
+    component: WeiduComponent,
+  },
+}
+
+impl Display for Divergence {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Divergence::Duplicate {
+        installed_line,
+        first_line,
+        component,
+      } => write!(
+        f,
+        "duplicate: {component} on weidu.log line {installed_line}, first installed on line {first_line}"
+      ),
+      Divergence::Missing {
+        target_line,
+        component,
+      } => write!(f, "missing: {component} from target log line {target_line}"),
+      Divergence::Extra {
+        installed_line,
+        component,
+      } => write!(
+        f,
+        "extra: {component} on weidu.log line {installed_line} is not in the target log"
+      ),
+      Divergence::OutOfOrder {
+        target_line,
+        installed_line,
+        component,
+      } => write!(
+        f,
+        "out of order: {component} on weidu.log line {installed_line}, target log line {target_line}"
+      ),
+    }
+  }
+}
+
+fn matches(left: &WeiduComponent, right: &WeiduComponent, strict_matching: bool) -> bool {
+  if strict_matching {
+    left.strict_matching(right)
+  } else {
+    left == right
+  }
+}
+
+/// Drops later copies of a component, returning them alongside the line of the first copy
+fn dedupe(
+  entries: &[LogEntry],
+  strict_matching: bool,
+) -> (Vec<&LogEntry>, Vec<(&LogEntry, usize)>) {
+  let mut unique: Vec<&LogEntry> = vec![];
+  let mut duplicates = vec![];
+  for entry in entries {
+    match unique
+      .iter()
+      .find(|seen| matches(&seen.component, &entry.component, strict_matching))
+    {
+      Some(first) => duplicates.push((entry, first.line)),
+      None => unique.push(entry),
+    }
+  }
+  (unique, duplicates)
+}
+
+/// Positions, into the given indexes, of their longest increasing run
+pub(crate) fn longest_increasing(indexes: &[usize]) -> Vec<usize> {
+  let mut lengths = vec![1; indexes.len()];
+  let mut previous: Vec<Option<usize>> = vec![None; indexes.len()];
+  for current in 0..indexes.len() {
+    for before in 0..current {
+      if indexes[before] < indexes[current] && lengths[before] + 1 > lengths[current] {
+        lengths[current] = lengths[before] + 1;
+        previous[current] = Some(before);
+      }
+    }
+  }
+  let mut position = (0..indexes.len()).max_by_key(|position| lengths[*position]);
+  let mut run = vec![];
+  while let Some(current) = position {
+    run.push(current);
+    position = previous[current];
+  }
+  run.reverse();
+  run
+}
+
+/// Compares the components in a game's weidu.log with a target log
+pub(crate) fn compare(
+  target: &[LogEntry],
+  installed: &[LogEntry],
+  strict_matching: bool,
+) -> Vec<Divergence> {
+  // The installer only installs the first copy of a component in the target log
+  let (target, _) = dedupe(target, strict_matching);
+  let (installed, duplicates) = dedupe(installed, strict_matching);
+  let mut divergences: Vec<Divergence> = duplicates
+    .into_iter()
+    .map(|(entry, first_line)| Divergence::Duplicate {
+      installed_line: entry.line,
+      first_line,
+      component: entry.component.clone(),
+    })
+    .collect();
+
+  divergences.extend(
+    target
+      .iter()
+      .filter(|wanted| {
+        !installed
+          .iter()
+          .any(|entry| matches(&entry.component, &wanted.component, strict_matching))
+      })
+      .map(|wanted| Divergence::Missing {
+        target_line: wanted.line,
+        component: wanted.component.clone(),
+      }),
+  );
+
+  // Installed components paired with their position in the target log
+  let mut common: Vec<(&LogEntry, usize)> = vec![];
+  for entry in &installed {
+    match target
+      .iter()
+      .position(|wanted| matches(&entry.component, &wanted.component, strict_matching))
+    {
+      Some(position) => common.push((entry, position)),
+      None => divergences.push(Divergence::Extra {
+        installed_line: entry.line,
+        component: entry.component.clone(),
+      }),
+    }
+  }
+
+  let positions: Vec<usize> = common.iter().map(|(_, position)| *position).collect();
+  let in_order = longest_increasing(&positions);
+  divergences.extend(
+    common
+      .iter()
+      .enumerate()
+      .filter(|(index, _)| !in_order.contains(index))
+      .map(|(_, (entry, position))| Divergence::OutOfOrder {
+        target_line: target[*position].line,
+        installed_line: entry.line,
+        component: entry.component.clone(),
+      }),
+  );
+  divergences
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum BatchDivergence {
+  Skipped {
+    component: WeiduComponent,
+    reason: Option<String>,
+  },
+  OutOfOrder {
+    component: WeiduComponent,
+    installed_line: usize,
+  },
+}
+
+impl Display for BatchDivergence {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      BatchDivergence::Skipped {
+        component,
+        reason: Some(reason),
+      } => write!(f, "weidu skipped {component}, {reason}"),
+      BatchDivergence::Skipped {
+        component,
+        reason: None,
+      } => write!(
+        f,
+        "weidu skipped {component}, no reason found in its output"
+      ),
+      BatchDivergence::OutOfOrder {
+        component,
+        installed_line,
+      } => write!(
+        f,
+        "out of order: {component} on weidu.log line {installed_line}"
+      ),
+    }
+  }
+}
+
+/// How much of a game's weidu.log follows the target log in order
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct InstalledPrefix {
+  /// Number of leading components installed in target order
+  pub(crate) matched: usize,
+  /// Installed components after the point the game diverges from the target
+  pub(crate) diverged: Vec<LogEntry>,
+  /// The target component expected where the game diverges
+  pub(crate) expected: Option<LogEntry>,
+}
+
+impl Display for InstalledPrefix {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let Some(first) = self.diverged.first() else {
+      return write!(
+        f,
+        "the first {} components follow the target log",
+        self.matched
+      );
+    };
+    write!(
+      f,
+      "weidu.log diverges from the target log after {} components, line {} is {}",
+      self.matched, first.line, first.component
+    )?;
+    match &self.expected {
+      Some(expected) => write!(
+        f,
+        " where target log line {} wants {}",
+        expected.line, expected.component
+      )?,
+      None => write!(f, " after the end of the target log")?,
+    }
+    write!(
+      f,
+      ", {} components are installed after it",
+      self.diverged.len()
+    )
+  }
+}
+
+pub(crate) fn installed_prefix(
+  target: &[LogEntry],
+  installed: &[LogEntry],
+  strict_matching: bool,
+) -> InstalledPrefix {
+  let matched = target
+    .iter()
+    .zip(installed)
+    .take_while(|(wanted, entry)| matches(&entry.component, &wanted.component, strict_matching))
+    .count();
+  InstalledPrefix {
+    matched,
+    diverged: installed[matched..].to_vec(),
+    expected: target.get(matched).cloned(),
+  }
+}
+
+/// Components in weidu.log, empty if nothing has been installed yet.
+/// A log that can not be read or parsed is an error, not an empty game
+pub(crate) fn installed(game_directory: &Path) -> Result<Vec<LogEntry>, Box<dyn Error>> {
+  let path = game_directory.join("weidu").with_extension("log");
+  if !path.exists() {
+    return Ok(vec![]);
+  }
+  read_log(&path)
+}
+
+/// Whether the line has the number as a token of its own, so #1 is not found in #10
+fn mentions_number(line: &str, number: &str) -> bool {
+  line.match_indices(number).any(|(start, _)| {
+    !line[start + number.len()..].starts_with(|next: char| next.is_ascii_alphanumeric())
+  })
+}
+
+/// Picks the skip lines that name the component, all of them when only one was skipped
+fn skip_reason(
+  component: &WeiduComponent,
+  skip_lines: &[String],
+  only_skipped: bool,
+) -> Option<String> {
+  let name = component.component_name.to_lowercase();
+  let number = format!("#{}", component.component);
+  let named: Vec<&str> = skip_lines
+    .iter()
+    .filter(|line| {
+      let line = line.to_lowercase();
+      (!name.is_empty() && line.contains(&name)) || mentions_number(&line, &number)
+    })
+    .map(String::as_str)
+    .collect();
+  match (named.is_empty(), only_skipped && !skip_lines.is_empty()) {
+    (false, _) => Some(named.join("; ")),
+    (true, true) => Some(skip_lines.join("; ")),
+    (true, false) => None,
+  }
+}
+
+/// Confirms every component in a batch was appended to weidu.log, in order
+pub(crate) fn check_batch(
+  batch: &WeiduBatchedComponents,
+  installed_before: usize,
+  game_directory: &Path,
+  strict_matching: bool,
+  skip_lines: &[String],
+) -> Result<Vec<BatchDivergence>, Box<dyn Error>> {
+  let installed = installed(game_directory)?;
+  let appended = installed.get(installed_before..).unwrap_or_default();
+  let mut cursor = 0;
+  let mut skipped = vec![];
+  let mut divergences = vec![];
+  for component in batch {
+    let found = |entry: &&LogEntry| matches(&entry.component, component, strict_matching);
+    if let Some(position) = appended[cursor..].iter().position(|entry| found(&entry)) {
+      cursor += position + 1;
+    } else if let Some(entry) = appended[..cursor].iter().find(found) {
+      divergences.push(BatchDivergence::OutOfOrder {
+        component: component.clone(),
+        installed_line: entry.line,
+      });
+    } else {
+      skipped.push(component);
+    }
+  }
+  let only_skipped = skipped.len() == 1;
+  divergences.extend(
+    skipped
+      .into_iter()
+      .map(|component| BatchDivergence::Skipped {
+        component: component.clone(),
+        reason: skip_reason(component, skip_lines, only_skipped),
+      }),
+  );
+  Ok(divergences)
+}
+
+pub(crate) fn verify(
+  log_file: &Path,
+  game_directory: &Path,
+  strict_matching: bool,
+) -> Result<(), Box<dyn Error>> {
+  verify_entries(
+    &read_log(log_file)?,
+    log_file,
+    game_directory,
+    strict_matching,
+  )
+}
+
+/// Compares weidu.log with entries read from the log file, which may have had their labels resolved
+pub(crate) fn verify_entries(
+  target: &[LogEntry],
+  log_file: &Path,
+  game_directory: &Path,
+  strict_matching: bool,
+) -> Result<(), Box<dyn Error>> {
+  let installed_log = game_directory.join("weidu").with_extension("log");
+  log::info!("Verifying {installed_log:?} against {log_file:?}");
+  let divergences = compare(target, &read_log(&installed_log)?, strict_matching);
+  for divergence in &divergences {
+    log::error!("{divergence}");
+  }
+  if !divergences.is_empty() {
+    return Err(
+      format!(
+        "{installed_log:?} differs from {log_file:?} in {} places",
+        divergences.len()
+      )
+      .into(),
+    );
+  }
+  log::info!("{installed_log:?} matches {log_file:?}");
+  Ok(())
+}
+
+pub(crate) fn verify_command(command: &Verify) -> Result<(), Box<dyn Error>> {
+  verify(
+    &command.log_file,
+    &command.game_directory,
+    command.strict_matching,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::{error::Error, fs};
+
+  fn entries(lines: &[&str]) -> Result<Vec<LogEntry>, Box<dyn Error>> {
+    let file = tempfile::NamedTempFile::new()?;
+    fs::write(file.path(), format!("// header\n{}\n", lines.join("\n")))?;
+    read_log(file.path())
+  }
+
+  #[test]
+  fn matching_logs_have_no_divergence() -> Result<(), Box<dyn Error>> {
+    let game_directory = tempfile::tempdir()?;
+    fs::copy("fixtures/test.log", game_directory.path().join("weidu.log"))?;
+    verify(Path::new("fixtures/test.log"), game_directory.path(), true)?;
+    assert!(
+      verify(
+        Path::new("fixtures/weidu.log"),
+        game_directory.path(),
+        false
+      )
+      .is_err()
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn reports_each_kind_of_divergence() -> Result<(), Box<dyn Error>> {
+    let target = entries(&[
+      "~A/A.TP2~ #0 #0 // a zero",
+      "~A/A.TP2~ #0 #1 // a one",
+      "~B/B.TP2~ #0 #0 // b zero",
+      "~C/C.TP2~ #0 #0 // c zero",
+    ])?;
+    let installed = entries(&[
+      "~A/A.TP2~ #0 #0 // a zero",
+      "~B/B.TP2~ #0 #0 // b zero",
+      "~A/A.TP2~ #0 #1 // a one",
+      "~D/D.TP2~ #0 #0 // d zero",
+      "~A/A.TP2~ #0 #0 // a zero",
+    ])?;
+    let component = |line: usize| installed[line - 2].component.clone();
+    assert_eq!(
+      compare(&target, &installed, false),
+      vec![
+        Divergence::Duplicate {
+          installed_line: 6,
+          first_line: 2,
+          component: component(6),
+        },
+        Divergence::Missing {
+          target_line: 5,
+          component: target[3].component.clone(),
+        },
+        Divergence::Extra {
+          installed_line: 5,
+          component: component(5),
+        },
+        Divergence::OutOfOrder {
+          target_line: 4,
+          installed_line: 3,
+          component: component(3),
+        },
+      ]
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn reports_components_weidu_skipped() -> Result<(), Box<dyn Error>> {
+    let game_directory = tempfile::tempdir()?;
+    let weidu_log = game_directory.path().join("weidu.log");
+    fs::write(&weidu_log, "~A/A.TP2~ #0 #0 // a zero\n")?;
+    let before = installed(game_directory.path())?.len();
+    fs::write(
+      &weidu_log,
+      "~A/A.TP2~ #0 #0 // a zero\n~B/B.TP2~ #0 #0 // b zero\n~B/B.TP2~ #0 #3 // b three\n",
+    )?;
+    let batch = WeiduBatchedComponents::from(
+      entries(&[
+        "~B/B.TP2~ #0 #0 // b zero",
+        "~B/B.TP2~ #0 #1 // b one",
+        "~B/B.TP2~ #0 #2 // b two",
+        "~B/B.TP2~ #0 #3 // b three",
+      ])?
+      .into_iter()
+      .map(|entry| entry.component)
+      .collect::<Vec<_>>(),
+    );
+    let skip_lines = vec![
+      "SKIPPING: [B One] requires the original game".to_string(),
+      "Component #2 not installed, already present".to_string(),
+    ];
+    let divergences = check_batch(&batch, before, game_directory.path(), false, &skip_lines)?;
+    assert_eq!(
+      divergences
+        .iter()
+        .map(|divergence| divergence.to_string())
+        .collect::<Vec<_>>(),
+      vec![
+        "weidu skipped b/b.tp2 #0 #1 // b one, SKIPPING: [B One] requires the original game",
+        "weidu skipped b/b.tp2 #0 #2 // b two, Component #2 not installed, already present",
+      ]
+    );
+    assert_eq!(
+      check_batch(&batch, before, game_directory.path(), false, &[])?.len(),
+      2
+    );
+    assert_eq!(
+      skip_reason(
+        batch
+          .into_iter()
+          .nth(1)
+          .ok_or("Batch has a second component")?,
+        &["Component #10 not installed".to_string()],
+        false
+      ),
+      None
+    );
+
+    fs::write(&weidu_log, "not a weidu log\n")?;
+    assert!(installed(game_directory.path()).is_err());
+    assert!(check_batch(&batch, before, game_directory.path(), false, &[]).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn finds_where_the_game_diverges() -> Result<(), Box<dyn Error>> {
+    let target = entries(&[
+      "~A/A.TP2~ #0 #0 // a zero",
+      "~A/A.TP2~ #0 #3 // a three",
+      "~A/A.TP2~ #0 #5 // a five",
+    ])?;
+    let installed = entries(&["~A/A.TP2~ #0 #0 // a zero", "~A/A.TP2~ #0 #5 // a five"])?;
+    let prefix = installed_prefix(&target, &installed, false);
+    assert_eq!(prefix.matched, 1);
+    assert_eq!(prefix.diverged, installed[1..].to_vec());
+    assert_eq!(
+      prefix.to_string(),
+      "weidu.log diverges from the target log after 1 components, line 3 is a/a.tp2 #0 #5 // a five \
+      where target log line 3 wants a/a.tp2 #0 #3 // a three, 1 components are installed after it"
+    );
+
+    let prefix = installed_prefix(&target, &installed[..1], false);
+    assert_eq!(prefix.matched, 1);
+    assert_eq!(prefix.diverged, vec![]);
+    assert_eq!(
+      installed_prefix(&target[..1], &installed, false).expected,
+      None
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn strict_matching_compares_versions() -> Result<(), Box<dyn Error>> {
+    let target = entries(&["~A/A.TP2~ #0 #0 // a zero: v2"])?;
+    let installed = entries(&["~A/A.TP2~ #0 #0 // a zero: v1"])?;
+    assert_eq!(compare(&target, &installed, false), vec![]);
+    assert_eq!(compare(&target, &installed, true).len(), 2);
+    Ok(())
+  }
+}